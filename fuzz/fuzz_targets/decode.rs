@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use chip8_rs::chip8;
+
+fuzz_target!(|raw_inst: u16| {
+    if let Ok(instruction) = chip8::decode(raw_inst) {
+        let _ = instruction.to_string();
+    }
+});
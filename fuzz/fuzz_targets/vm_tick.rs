@@ -0,0 +1,36 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use chip8_rs::chip8::{self, VM};
+
+const MAX_STEPS: usize = 1000;
+
+// The first two bytes pick the pressed keys, the rest is a memory image.
+// Nothing may panic, every failure has to come back as a VMError.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+
+    let keys = u16::from_be_bytes([data[0], data[1]]);
+    let image = &data[2..data.len().min(chip8::MEMORY_SIZE + 2)];
+
+    let mut vm = VM::default();
+    vm.mem_copy(image, 0);
+    vm.program_counter = 0x200;
+    vm.enable_coverage();
+
+    for key in 0..16 {
+        vm.keyboard.keys[key] = keys & (1 << key) != 0;
+    }
+
+    for step in 0..MAX_STEPS {
+        if vm.tick().is_err() {
+            break;
+        }
+
+        if step % 8 == 0 {
+            vm.tick_timers();
+        }
+    }
+});
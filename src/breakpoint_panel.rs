@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+
+use macroquad::{prelude::*, ui::{hash, root_ui, widgets}};
+
+use chip8_rs::chip8::{Breakpoint, VM};
+
+// Hit and logpoint messages shown at the bottom of the window.
+const LOG_LINES: usize = 12;
+
+// Lists the VM's breakpoints with their hit counts and adds new ones from
+// the same text the Breakpoint parser takes, e.g. "pc 2A4 if v3 == 0x10".
+// It also takes the debugger's messages, which go to stdout as well.
+pub struct BreakpointPanel {
+    pub open: bool,
+    text: String,
+    error: Option<String>,
+    log: VecDeque<String>
+}
+
+impl BreakpointPanel {
+    pub fn new() -> BreakpointPanel {
+        BreakpointPanel { open: false, text: String::new(), error: None, log: VecDeque::new() }
+    }
+
+    pub fn update(&mut self, vm: &mut VM) {
+        for message in vm.debugger.take_messages() {
+            println!("Debugger: {}", message);
+            if self.log.len() == LOG_LINES {
+                self.log.pop_front();
+            }
+            self.log.push_back(message);
+        }
+
+        if !self.open {
+            return;
+        }
+
+        let mut add = false;
+        let mut remove = None;
+        let mut resume = false;
+        let stopped = vm.debugger.stopped;
+
+        widgets::Window::new(hash!(), vec2(screen_width() - 430.0, 10.0), vec2(420.0, screen_height() - 20.0))
+            .label("Breakpoints")
+            .ui(&mut root_ui(), |ui| {
+                ui.label(None, "pc 2A4 | read 300..310 | write 300+4 | op Draw | when v0 == 1");
+                ui.label(None, "... if v3 == 0x10 && i > 0x300   ... log score {v3}");
+                ui.input_text(hash!(), "Breakpoint", &mut self.text);
+
+                if ui.button(None, "Add") {
+                    add = true;
+                }
+                if vm.paused {
+                    ui.same_line(0.0);
+                    if ui.button(None, "Continue") {
+                        resume = true;
+                    }
+                }
+
+                if let Some(error) = &self.error {
+                    ui.label(None, error);
+                }
+
+                ui.separator();
+
+                for (index, breakpoint) in vm.debugger.breakpoints.iter_mut().enumerate() {
+                    let marker = if stopped == Some(index) { "> " } else { "" };
+                    let label = format!("{}{} ({} hits)", marker, breakpoint.source, breakpoint.hits);
+                    ui.checkbox(hash!("breakpoint", index), &label, &mut breakpoint.enabled);
+                    ui.same_line(0.0);
+                    if ui.button(None, "Remove") {
+                        remove = Some(index);
+                    }
+                }
+
+                ui.separator();
+                for message in &self.log {
+                    ui.label(None, message);
+                }
+            });
+
+        if add {
+            match Breakpoint::parse(&self.text) {
+                Ok(breakpoint) => {
+                    vm.debugger.add(breakpoint);
+                    self.text.clear();
+                    self.error = None;
+                },
+                Err(err) => self.error = Some(err)
+            }
+        }
+        if let Some(index) = remove {
+            vm.debugger.remove(index);
+        }
+        if resume {
+            vm.paused = false;
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{fmt, fs::File, io::{self, BufWriter}, path::{Path, PathBuf}};
+
+use crate::{chip8::{self, Display}, frontend::FRAME_RATE};
+
+pub const DEFAULT_SCALE: u32 = 8;
+// GIF sizes are 16 bit.
+pub const MAX_SCALE: u32 = u16::MAX as u32 / chip8::DISPLAY_WIDTH as u32;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub off: [u8; 3],
+    pub on: [u8; 3]
+}
+
+impl Palette {
+    // "RRGGBB,RRGGBB", background first.
+    pub fn parse(text: &str) -> Option<Palette> {
+        let (off, on) = text.split_once(',')?;
+        Some(Palette { off: parse_color(off)?, on: parse_color(on)? })
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        [self.off, self.on].concat()
+    }
+}
+
+// The same "RRGGBB,RRGGBB" that `parse` reads.
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b] = self.off;
+        let [r2, g2, b2] = self.on;
+        write!(f, "{:02X}{:02X}{:02X},{:02X}{:02X}{:02X}", r, g, b, r2, g2, b2)
+    }
+}
+
+impl Default for Palette {
+    // The colors of the window.
+    fn default() -> Palette {
+        Palette { off: [20, 20, 20], on: [200, 200, 200] }
+    }
+}
+
+fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let text = text.trim().trim_start_matches('#');
+    if text.len() != 6 {
+        return None;
+    }
+
+    let value = u32::from_str_radix(text, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+// One palette index per output pixel, 0 for off and 1 for on.
+fn render(display: &Display, scale: u32) -> Vec<u8> {
+    let scale = scale as usize;
+    let width = chip8::DISPLAY_WIDTH * scale;
+    let mut pixels = Vec::with_capacity(width * chip8::DISPLAY_HEIGHT * scale);
+
+    for y in 0..chip8::DISPLAY_HEIGHT * scale {
+        for x in 0..width {
+            pixels.push(display.get(x / scale, y / scale) as u8);
+        }
+    }
+
+    pixels
+}
+
+fn size(scale: u32) -> (u32, u32) {
+    (chip8::DISPLAY_WIDTH as u32 * scale, chip8::DISPLAY_HEIGHT as u32 * scale)
+}
+
+fn png_encoder(path: &Path, scale: u32, palette: Palette) -> io::Result<png::Encoder<'static, BufWriter<File>>> {
+    let (width, height) = size(scale);
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette.bytes());
+    Ok(encoder)
+}
+
+pub fn save_png(path: &Path, display: &Display, scale: u32, palette: Palette) -> io::Result<()> {
+    let mut writer = png_encoder(path, scale, palette)?.write_header()?;
+    writer.write_image_data(&render(display, scale))?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecordingFormat {
+    Gif,
+    Apng
+}
+
+impl RecordingFormat {
+    // Anything that is not a .gif is written as APNG.
+    pub fn from_path(path: &Path) -> RecordingFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gif") => RecordingFormat::Gif,
+            _ => RecordingFormat::Apng
+        }
+    }
+}
+
+// Collects one display per emulated frame and encodes them when finished.
+// Identical consecutive frames are merged into one longer frame.
+pub struct Recorder {
+    path: PathBuf,
+    format: RecordingFormat,
+    scale: u32,
+    palette: Palette,
+    frames: Vec<(Display, u32)>
+}
+
+impl Recorder {
+    pub fn new(path: &Path, scale: u32, palette: Palette) -> Recorder {
+        Recorder {
+            path: path.to_path_buf(),
+            format: RecordingFormat::from_path(path),
+            scale,
+            palette,
+            frames: Vec::new()
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frames.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn record(&mut self, display: &Display) {
+        match self.frames.last_mut() {
+            Some((last, count)) if last == display => *count += 1,
+            _ => self.frames.push((display.clone(), 1))
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self.format {
+            RecordingFormat::Gif => self.write_gif(),
+            RecordingFormat::Apng => self.write_apng()
+        }
+    }
+
+    fn write_gif(&self) -> io::Result<()> {
+        let (width, height) = size(self.scale);
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(io::Error::other(format!("a scale of {} is too large for a GIF, at most {} fits", self.scale, MAX_SCALE)));
+        };
+        let file = BufWriter::new(File::create(&self.path)?);
+        let mut encoder = gif::Encoder::new(file, width, height, &self.palette.bytes()).map_err(io::Error::other)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+
+        // GIF delays are in hundredths of a second, so 60fps is kept on average
+        // by rounding the running total instead of every frame on its own.
+        let mut elapsed_frames = 0;
+        for (display, count) in &self.frames {
+            let start = elapsed_frames * 100 / FRAME_RATE;
+            elapsed_frames += count;
+            let delay = (elapsed_frames * 100 / FRAME_RATE - start).max(1);
+
+            let mut frame = gif::Frame::from_indexed_pixels(width, height, render(display, self.scale), None);
+            frame.delay = delay as u16;
+            encoder.write_frame(&frame).map_err(io::Error::other)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_apng(&self) -> io::Result<()> {
+        let mut encoder = png_encoder(&self.path, self.scale, self.palette)?;
+        encoder.set_animated(self.frames.len().max(1) as u32, 0)?;
+        let mut writer = encoder.write_header()?;
+
+        if self.frames.is_empty() {
+            writer.write_image_data(&render(&Display::new(), self.scale))?;
+        }
+
+        for (display, count) in &self.frames {
+            writer.set_frame_delay((*count).min(u16::MAX as u32) as u16, FRAME_RATE as u16)?;
+            writer.write_image_data(&render(display, self.scale))?;
+        }
+
+        writer.finish()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use rhai::{Engine, Map};
+
+use crate::{chip8::{self, Display}, octo, settings::Manifest};
+
+// Cartridges are GIFs of a label with a JSON payload hidden in the low two bits
+// of every pixel's palette index, in the manner of Octo:
+//
+//   { "options": { "tickrate": 20, ... }, "program": ": main 0x12 0x00 ..." }
+//
+// The program is Octo source, which `pack` writes as bytes after `: main`.
+//
+// The payload is preceded by its length as four big endian bytes and each byte is
+// spread over four pixels, high bits first, continuing over as many frames as needed.
+pub const LABEL_WIDTH: usize = chip8::DISPLAY_WIDTH * 2;
+pub const LABEL_HEIGHT: usize = chip8::DISPLAY_HEIGHT * 2;
+
+const PIXELS_PER_BYTE: usize = 4;
+const FRAME_DELAY: u16 = 10;
+
+// Four shades each for the label's background and ink, close enough to look flat.
+const PALETTE: [u8; 24] = [
+    0x1A, 0x1A, 0x2E, 0x1B, 0x1A, 0x2E, 0x1A, 0x1B, 0x2E, 0x1B, 0x1B, 0x2F,
+    0xF0, 0xC8, 0x3C, 0xF1, 0xC8, 0x3C, 0xF0, 0xC9, 0x3C, 0xF1, 0xC9, 0x3D
+];
+
+pub struct Cartridge {
+    pub program: Vec<u8>,
+    pub manifest: Manifest
+}
+
+pub fn is_cartridge(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a")
+}
+
+fn disassemble_bytes(program: &[u8]) -> String {
+    let mut source = ": main\n".to_string();
+    for line in program.chunks(16) {
+        let bytes: Vec<String> = line.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+        source.push_str(&bytes.join(" "));
+        source.push('\n');
+    }
+    source
+}
+
+// The label pixels, 0 or 1, at twice the size of the display.
+fn label_pixels(label: &Display) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(LABEL_WIDTH * LABEL_HEIGHT);
+    for y in 0..LABEL_HEIGHT {
+        for x in 0..LABEL_WIDTH {
+            pixels.push(label.get(x / 2, y / 2) as u8);
+        }
+    }
+    pixels
+}
+
+pub fn pack(program: &[u8], manifest: &Manifest, label: &Display) -> Result<Vec<u8>, String> {
+    let mut payload = manifest.to_map();
+    payload.insert("program".into(), disassemble_bytes(program).into());
+    let json = rhai::format_map_as_json(&payload);
+
+    let mut data = (json.len() as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(json.as_bytes());
+
+    let label = label_pixels(label);
+    let bytes_per_frame = label.len() / PIXELS_PER_BYTE;
+    let mut gif = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut gif, LABEL_WIDTH as u16, LABEL_HEIGHT as u16, &PALETTE).map_err(|err| err.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|err| err.to_string())?;
+
+        for chunk in data.chunks(bytes_per_frame) {
+            let mut pixels = label.iter().map(|ink| ink << 2).collect::<Vec<u8>>();
+            for (index, byte) in chunk.iter().enumerate() {
+                for part in 0..PIXELS_PER_BYTE {
+                    pixels[index * PIXELS_PER_BYTE + part] |= (byte >> (6 - 2 * part)) & 0b11;
+                }
+            }
+
+            let mut frame = gif::Frame::from_indexed_pixels(LABEL_WIDTH as u16, LABEL_HEIGHT as u16, pixels, None);
+            frame.delay = FRAME_DELAY;
+            encoder.write_frame(&frame).map_err(|err| err.to_string())?;
+        }
+    }
+    Ok(gif)
+}
+
+pub fn unpack(bytes: &[u8]) -> Result<Cartridge, String> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(bytes).map_err(|err| format!("not a readable GIF: {}", err))?;
+
+    let mut data = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(|err| format!("not a readable GIF: {}", err))? {
+        for pixels in frame.buffer.chunks_exact(PIXELS_PER_BYTE) {
+            data.push(pixels.iter().fold(0, |byte, pixel| (byte << 2) | (pixel & 0b11)));
+        }
+    }
+
+    let length = data.get(..4).map(|length| u32::from_be_bytes(length.try_into().unwrap()) as usize).ok_or("no cartridge data in the GIF")?;
+    let json = data.get(4..4 + length).ok_or("the cartridge data is truncated")?;
+    let json = std::str::from_utf8(json).map_err(|_| "the cartridge data is not text, this GIF is probably not a cartridge")?;
+
+    let payload: Map = Engine::new_raw().parse_json(json, true).map_err(|err| format!("invalid cartridge data: {}", err))?;
+    let source = payload.get("program").and_then(|program| program.clone().into_string().ok()).ok_or("the cartridge has no program")?;
+
+    Ok(Cartridge {
+        program: octo::assemble(&source).map_err(|err| format!("the cartridge's program does not assemble: {}", err))?,
+        manifest: Manifest::parse(json)?
+    })
+}
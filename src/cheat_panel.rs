@@ -0,0 +1,136 @@
+use macroquad::{prelude::*, ui::{hash, root_ui, widgets}};
+
+use chip8_rs::{chip8::VM, cheats::{Cheat, CheatList, RamSearch, SearchFilter}};
+
+const SHOWN_CANDIDATES: usize = 16;
+
+pub struct CheatPanel {
+    pub open: bool,
+    search: RamSearch,
+    value: String,
+    name: String
+}
+
+fn save(cheats: &CheatList) {
+    if let Err(err) = cheats.save() {
+        println!("Error saving cheats: {}", err);
+    }
+}
+
+fn parse_value(text: &str) -> Option<u8> {
+    let text = text.trim();
+    match text.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok()
+    }
+}
+
+impl CheatPanel {
+    pub fn new(vm: &VM) -> CheatPanel {
+        CheatPanel {
+            open: false,
+            search: RamSearch::new(&vm.memory),
+            value: String::new(),
+            name: String::new()
+        }
+    }
+
+    fn add_cheat(&mut self, cheats: &mut CheatList, address: usize, value: u8) {
+        let name = if self.name.trim().is_empty() {
+            format!("Cheat {:03X}", address)
+        } else {
+            self.name.trim().to_string()
+        };
+
+        cheats.cheats.push(Cheat { name, address, value, enabled: true });
+        self.name.clear();
+        save(cheats);
+    }
+
+    // The cheats themselves belong to the runner, which writes them every frame.
+    pub fn update(&mut self, vm: &mut VM, cheats: &mut CheatList) {
+        if !self.open {
+            return;
+        }
+
+        let mut filter = None;
+        let mut reset = false;
+        let mut add = None;
+        let mut remove = None;
+        let mut toggled = false;
+
+        widgets::Window::new(hash!(), vec2(10.0, 10.0), vec2(420.0, screen_height() - 20.0))
+            .label("Cheats")
+            .ui(&mut root_ui(), |ui| {
+                ui.label(None, &format!("RAM search: {} candidates", self.search.candidates.len()));
+                ui.input_text(hash!(), "Value (dec or 0x hex)", &mut self.value);
+                ui.input_text(hash!(), "Cheat name", &mut self.name);
+
+                if ui.button(None, "New search") {
+                    reset = true;
+                }
+                ui.same_line(0.0);
+                if ui.button(None, "Equal") {
+                    filter = parse_value(&self.value).map(SearchFilter::Equal);
+                }
+                ui.same_line(0.0);
+                if ui.button(None, "Changed") {
+                    filter = Some(SearchFilter::Changed);
+                }
+                ui.same_line(0.0);
+                if ui.button(None, "Unchanged") {
+                    filter = Some(SearchFilter::Unchanged);
+                }
+                ui.same_line(0.0);
+                if ui.button(None, "Increased") {
+                    filter = Some(SearchFilter::Increased);
+                }
+                ui.same_line(0.0);
+                if ui.button(None, "Decreased") {
+                    filter = Some(SearchFilter::Decreased);
+                }
+
+                ui.separator();
+
+                for address in self.search.candidates.iter().take(SHOWN_CANDIDATES) {
+                    ui.label(None, &format!("{:03X}: {:02X} -> {:02X}", address, self.search.snapshot[*address], vm.memory[*address]));
+                    ui.same_line(0.0);
+                    if ui.button(None, "Freeze") {
+                        add = Some((*address, vm.memory[*address]));
+                    }
+                    ui.same_line(0.0);
+                    if ui.button(None, "Set") {
+                        add = parse_value(&self.value).map(|value| (*address, value));
+                    }
+                }
+
+                ui.separator();
+
+                for (index, cheat) in cheats.cheats.iter_mut().enumerate() {
+                    let enabled = cheat.enabled;
+                    ui.checkbox(hash!("cheat", index), &format!("{} ({:03X} = {:02X})", cheat.name, cheat.address, cheat.value), &mut cheat.enabled);
+                    toggled |= enabled != cheat.enabled;
+                    ui.same_line(0.0);
+                    if ui.button(None, "Remove") {
+                        remove = Some(index);
+                    }
+                }
+            });
+
+        if reset {
+            self.search = RamSearch::new(&vm.memory);
+        }
+        if let Some(filter) = filter {
+            self.search.filter(filter, &vm.memory);
+        }
+        if let Some((address, value)) = add {
+            self.add_cheat(cheats, address, value);
+        }
+        if let Some(index) = remove {
+            cheats.cheats.remove(index);
+        }
+        if toggled || remove.is_some() {
+            save(cheats);
+        }
+    }
+}
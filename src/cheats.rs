@@ -0,0 +1,121 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use crate::chip8::VM;
+
+#[derive(Debug, Clone, Copy)]
+pub enum SearchFilter {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased
+}
+
+impl SearchFilter {
+    fn matches(&self, previous: u8, current: u8) -> bool {
+        match self {
+            SearchFilter::Equal(value) => current == *value,
+            SearchFilter::Changed => current != previous,
+            SearchFilter::Unchanged => current == previous,
+            SearchFilter::Increased => current > previous,
+            SearchFilter::Decreased => current < previous
+        }
+    }
+}
+
+// Narrows down the addresses that could hold a value by comparing
+// memory against the snapshot taken at the previous filter.
+pub struct RamSearch {
+    pub candidates: Vec<usize>,
+    pub snapshot: Vec<u8>
+}
+
+impl RamSearch {
+    pub fn new(memory: &[u8]) -> RamSearch {
+        RamSearch {
+            candidates: (0..memory.len()).collect(),
+            snapshot: memory.to_vec()
+        }
+    }
+
+    pub fn filter(&mut self, filter: SearchFilter, memory: &[u8]) {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|address| filter.matches(snapshot[*address], memory[*address]));
+        self.snapshot.copy_from_slice(memory);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cheat {
+    pub name: String,
+    pub address: usize,
+    pub value: u8,
+    pub enabled: bool
+}
+
+// Cheats for one ROM, stored one per line as "<enabled> <address> <value> <name>", e.g.
+//
+//   1 02F4 09 Infinite lives
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+    path: PathBuf
+}
+
+impl CheatList {
+    pub fn new(path: &Path) -> CheatList {
+        CheatList { cheats: Vec::new(), path: path.to_path_buf() }
+    }
+
+    pub fn load(path: &Path) -> io::Result<CheatList> {
+        let mut list = CheatList::new(path);
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(list),
+            Err(err) => return Err(err)
+        };
+
+        for (number, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            list.cheats.push(parse_cheat(line).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: invalid cheat", path.display(), number + 1))
+            })?);
+        }
+
+        Ok(list)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents: String = self.cheats.iter()
+            .map(|cheat| format!("{} {:04X} {:02X} {}\n", cheat.enabled as u8, cheat.address, cheat.value, cheat.name))
+            .collect();
+
+        fs::write(&self.path, contents)
+    }
+
+    pub fn apply(&self, vm: &mut VM) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            if let Some(byte) = vm.memory.get_mut(cheat.address) {
+                *byte = cheat.value;
+            }
+        }
+    }
+}
+
+pub fn parse_cheat(line: &str) -> Option<Cheat> {
+    let mut parts = line.trim().splitn(4, ' ');
+
+    let enabled = parts.next()? == "1";
+    let address = usize::from_str_radix(parts.next()?, 16).ok()?;
+    let value = u8::from_str_radix(parts.next()?, 16).ok()?;
+    let name = parts.next().unwrap_or("").to_string();
+
+    Some(Cheat { name, address, value, enabled })
+}
@@ -0,0 +1,99 @@
+use std::ops::Range;
+
+use super::instruction;
+
+pub const EXECUTED: u8 = 0b001;
+pub const READ: u8 = 0b010;
+pub const WRITTEN: u8 = 0b100;
+
+pub struct Coverage {
+    pub flags: Vec<u8>
+}
+
+impl Coverage {
+    pub fn new(size: usize) -> Coverage {
+        Coverage { flags: vec![0; size] }
+    }
+
+    pub fn mark(&mut self, address: usize, flag: u8) {
+        if let Some(flags) = self.flags.get_mut(address) {
+            *flags |= flag;
+        }
+    }
+
+    pub fn has(&self, address: usize, flag: u8) -> bool {
+        self.flags.get(address).is_some_and(|flags| flags & flag != 0)
+    }
+
+    fn count(&self, range: Range<usize>, flag: u8) -> usize {
+        range.filter(|address| self.has(*address, flag)).count()
+    }
+
+    // Contiguous runs outside of `program` that were touched with `flag`.
+    fn ranges_outside(&self, program: &Range<usize>, flag: u8) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+
+        for address in (0..self.flags.len()).filter(|a| !program.contains(a) && self.has(*a, flag)) {
+            match ranges.last_mut() {
+                Some(last) if last.end == address => last.end += 1,
+                _ => ranges.push(address..address + 1)
+            }
+        }
+
+        ranges
+    }
+
+    fn flag_string(&self, range: Range<usize>) -> String {
+        let union = range.fold(0, |acc, address| acc | self.flags.get(address).copied().unwrap_or(0));
+
+        [(EXECUTED, 'X'), (READ, 'R'), (WRITTEN, 'W')].iter()
+            .map(|(flag, c)| if union & flag != 0 { *c } else { '-' })
+            .collect()
+    }
+
+    // Annotated disassembly of the program area, X/R/W mark bytes that were
+    // executed, read as data and written. Words never touched show up as "---".
+    pub fn report(&self, memory: &[u8], program: Range<usize>) -> String {
+        let percent = |n: usize| if program.is_empty() { 0.0 } else { n as f32 * 100.0 / program.len() as f32 };
+        let executed = self.count(program.clone(), EXECUTED);
+        let read = self.count(program.clone(), READ);
+        let written = self.count(program.clone(), WRITTEN);
+        let untouched = program.clone().filter(|address| self.flags[*address] == 0).count();
+
+        let mut out = String::new();
+        out.push_str(&format!("Program {:03X}-{:03X} ({} bytes)\n", program.start, program.end.saturating_sub(1), program.len()));
+        out.push_str(&format!("  executed:  {:5} bytes {:5.1}%\n", executed, percent(executed)));
+        out.push_str(&format!("  read:      {:5} bytes {:5.1}%\n", read, percent(read)));
+        out.push_str(&format!("  written:   {:5} bytes {:5.1}%\n", written, percent(written)));
+        out.push_str(&format!("  untouched: {:5} bytes {:5.1}%\n", untouched, percent(untouched)));
+
+        for (name, flag) in [("Read", READ), ("Written", WRITTEN), ("Executed", EXECUTED)] {
+            for range in self.ranges_outside(&program, flag) {
+                out.push_str(&format!("{} outside program: {:03X}-{:03X}\n", name, range.start, range.end - 1));
+            }
+        }
+
+        out.push('\n');
+
+        let mut address = program.start;
+        while address < program.end {
+            let is_word = self.has(address, EXECUTED) || (address + 1 < program.end && !self.has(address + 1, EXECUTED));
+
+            if is_word && address + 1 < program.end {
+                let raw_inst = ((memory[address] as u16) << 8) | memory[address + 1] as u16;
+                let mnemonic = match instruction::decode(raw_inst) {
+                    Ok(instruction) => instruction.to_string(),
+                    Err(_) => String::new()
+                };
+
+                out.push_str(&format!("{:03X}  {:02X} {:02X}  {}  {}\n", address, memory[address], memory[address + 1], self.flag_string(address..address + 2), mnemonic));
+                address += 2;
+            } else {
+                out.push_str(&format!("{:03X}  {:02X}     {}\n", address, memory[address], self.flag_string(address..address + 1)));
+                address += 1;
+            }
+        }
+
+        out
+    }
+}
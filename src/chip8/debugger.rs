@@ -0,0 +1,283 @@
+use std::{collections::VecDeque, ops::Range};
+
+use super::{Expression, Instruction, VM};
+
+// What makes a breakpoint look at its condition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    // Before the instruction at this address runs.
+    Pc(usize),
+    // After an instruction read from or wrote to the range.
+    Read(Range<usize>),
+    Write(Range<usize>),
+    // Before every instruction of this class, by Instruction::name, e.g. "Draw".
+    Opcode(String),
+    // Before every instruction, only useful with a condition.
+    Always
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub trigger: Trigger,
+    pub condition: Option<Expression>,
+    // Logpoints print this instead of stopping, {expr} is replaced by the value in hex.
+    pub log: Option<String>,
+    pub enabled: bool,
+    pub hits: u64,
+    // The text it was parsed from, shown in the window and the REPL.
+    pub source: String
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    Read,
+    Write
+}
+
+fn parse_address(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => usize::from_str_radix(text, 16)
+    };
+    parsed.map_err(|_| format!("invalid address {}", text))
+}
+
+// "300" is one byte, "300..310" a half open range and "300+16" a start and length.
+fn parse_range(text: &str) -> Result<Range<usize>, String> {
+    if let Some((start, end)) = text.split_once("..") {
+        let range = parse_address(start)?..parse_address(end)?;
+        if range.is_empty() {
+            return Err(format!("empty range {}", text));
+        }
+        Ok(range)
+    } else if let Some((start, length)) = text.split_once('+') {
+        let start = parse_address(start)?;
+        let length: usize = length.trim().parse().map_err(|_| format!("invalid length {}", length))?;
+        Ok(start..start + length.max(1))
+    } else {
+        let address = parse_address(text)?;
+        Ok(address..address + 1)
+    }
+}
+
+// Replaces every {expr} in a logpoint message with the value of expr in hex.
+fn format_log(template: &str, vm: &VM, address: Option<usize>) -> String {
+    let mut message = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => {
+                let source = &rest[start + 1..start + end];
+                match Expression::parse(source) {
+                    Ok(expression) => message.push_str(&format!("{:X}", expression.eval(vm, address))),
+                    Err(err) => message.push_str(&format!("<{}>", err))
+                }
+                rest = &rest[start + end + 1..];
+            },
+            None => {
+                message.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    message.push_str(rest);
+    message
+}
+
+impl Breakpoint {
+    // Parses "<trigger> [if <condition>] [log <message>]" where the trigger is one of
+    //
+    //   pc 2A4            read 300..310      write 300+16
+    //   op Draw           when v3 == 0 (only a condition, checked before every instruction)
+    //
+    // Addresses are hex, with or without 0x.
+    pub fn parse(text: &str) -> Result<Breakpoint, String> {
+        let source = text.trim().to_string();
+        let (rest, log) = match source.split_once(" log ") {
+            Some((rest, log)) => (rest, Some(log.trim().to_string())),
+            None => (source.as_str(), None)
+        };
+        let (trigger, mut condition) = match rest.split_once(" if ") {
+            Some((trigger, condition)) => (trigger, Some(Expression::parse(condition)?)),
+            None => (rest, None)
+        };
+
+        let (kind, argument) = trigger.trim().split_once(' ').unwrap_or((trigger.trim(), ""));
+        let trigger = match kind.to_ascii_lowercase().as_str() {
+            "pc" => Trigger::Pc(parse_address(argument)?),
+            "read" => Trigger::Read(parse_range(argument)?),
+            "write" => Trigger::Write(parse_range(argument)?),
+            "op" => {
+                let name = argument.trim();
+                if name.is_empty() {
+                    return Err("op needs an instruction name, e.g. op Draw".to_string());
+                }
+                Trigger::Opcode(name.to_string())
+            },
+            "when" => {
+                if condition.is_none() {
+                    condition = Some(Expression::parse(argument)?);
+                }
+                Trigger::Always
+            },
+            _ => return Err(format!("unknown breakpoint kind {}, expected pc, read, write, op or when", kind))
+        };
+
+        Ok(Breakpoint { trigger, condition, log, enabled: true, hits: 0, source })
+    }
+
+    pub fn at(pc: usize) -> Breakpoint {
+        Breakpoint { trigger: Trigger::Pc(pc), condition: None, log: None, enabled: true, hits: 0, source: format!("pc {:03X}", pc) }
+    }
+}
+
+// Hit and logpoint messages waiting for a frontend, the oldest are dropped beyond this.
+pub const MAX_MESSAGES: usize = 1000;
+
+// Breakpoints, watchpoints and logpoints for one VM. The VM checks them in
+// tick and pauses itself when one stops, the frontends only look at `stopped`
+// and show the messages.
+#[derive(Default)]
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    // The breakpoint that paused the VM last, cleared when it runs again.
+    pub stopped: Option<usize>,
+    messages: VecDeque<String>,
+    dropped: usize,
+    accesses: Vec<(Access, usize)>,
+    // Resuming on a PC breakpoint has to run that instruction once.
+    resume_pc: Option<usize>
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger::default()
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(breakpoint);
+        self.breakpoints.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Breakpoint> {
+        if index >= self.breakpoints.len() {
+            return None;
+        }
+        self.stopped = None;
+        Some(self.breakpoints.remove(index))
+    }
+
+    // Hit messages and logpoint output since the last call, led by a note when some were dropped.
+    pub fn take_messages(&mut self) -> Vec<String> {
+        let mut messages: Vec<String> = std::mem::take(&mut self.messages).into();
+        if self.dropped > 0 {
+            messages.insert(0, format!("{} older messages dropped", self.dropped));
+            self.dropped = 0;
+        }
+        messages
+    }
+
+    fn message(&mut self, message: String) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+            self.dropped += 1;
+        }
+        self.messages.push_back(message);
+    }
+
+    fn watching(&self) -> bool {
+        self.breakpoints.iter().any(|breakpoint| breakpoint.enabled && matches!(breakpoint.trigger, Trigger::Read(_) | Trigger::Write(_)))
+    }
+
+    pub(super) fn read(&mut self, address: usize) {
+        if self.watching() {
+            self.accesses.push((Access::Read, address));
+        }
+    }
+
+    pub(super) fn write(&mut self, address: usize) {
+        if self.watching() {
+            self.accesses.push((Access::Write, address));
+        }
+    }
+
+    // Counts the hit and logs or reports it, true when the VM should stop.
+    fn hit(&mut self, index: usize, vm: &VM, pc: usize, address: Option<usize>) -> bool {
+        let breakpoint = &mut self.breakpoints[index];
+        if let Some(condition) = &breakpoint.condition {
+            if condition.eval(vm, address) == 0 {
+                return false;
+            }
+        }
+        breakpoint.hits += 1;
+
+        let location = match address {
+            Some(address) => format!("PC {:03X} ({:03X})", pc, address),
+            None => format!("PC {:03X}", pc)
+        };
+        match &breakpoint.log {
+            Some(template) => {
+                let message = format!("{}: {}", location, format_log(template, vm, address));
+                self.message(message);
+                false
+            },
+            None => {
+                let message = format!("{}: breakpoint {} \"{}\" hit {} times", location, index, breakpoint.source, breakpoint.hits);
+                self.message(message);
+                self.stopped = Some(index);
+                true
+            }
+        }
+    }
+
+    // Checked before the instruction at the PC runs, true when it should not run yet.
+    pub(super) fn before_instruction(&mut self, vm: &VM, instruction: &Instruction) -> bool {
+        let pc = vm.program_counter;
+        let resuming = self.resume_pc.take() == Some(pc);
+        self.stopped = None;
+        self.accesses.clear();
+
+        let mut stop = false;
+        for index in 0..self.breakpoints.len() {
+            let breakpoint = &self.breakpoints[index];
+            let triggered = breakpoint.enabled && match &breakpoint.trigger {
+                Trigger::Pc(address) => *address == pc,
+                Trigger::Opcode(name) => instruction.name().eq_ignore_ascii_case(name),
+                Trigger::Always => true,
+                Trigger::Read(_) | Trigger::Write(_) => false
+            };
+            if triggered && !resuming && self.hit(index, vm, pc, None) {
+                stop = true;
+            }
+        }
+
+        if stop {
+            self.resume_pc = Some(pc);
+        }
+        stop
+    }
+
+    // Checks the watchpoints against what the instruction just accessed, true when the VM should stop.
+    pub(super) fn after_instruction(&mut self, vm: &VM, pc: usize) -> bool {
+        let accesses = std::mem::take(&mut self.accesses);
+        let mut stop = false;
+        for index in 0..self.breakpoints.len() {
+            let breakpoint = &self.breakpoints[index];
+            if !breakpoint.enabled {
+                continue;
+            }
+            let (watched, range) = match &breakpoint.trigger {
+                Trigger::Read(range) => (Access::Read, range.clone()),
+                Trigger::Write(range) => (Access::Write, range.clone()),
+                _ => continue
+            };
+            // One hit per instruction, at the first address in range.
+            if let Some((_, address)) = accesses.iter().find(|(access, address)| *access == watched && range.contains(address)) {
+                stop |= self.hit(index, vm, pc, Some(*address));
+            }
+        }
+        stop
+    }
+}
@@ -1,10 +1,17 @@
 
 pub const DISPLAY_HEIGHT: usize = 32;
 pub const DISPLAY_WIDTH: usize = 64;
+#[derive(Clone, PartialEq)]
 pub struct Display {
     pixels: [[bool; DISPLAY_WIDTH]; DISPLAY_HEIGHT]
 }
 
+impl Default for Display {
+    fn default() -> Display {
+        Display::new()
+    }
+}
+
 impl Display {
     pub fn new() -> Display {
         Display { pixels: [[false; DISPLAY_WIDTH]; DISPLAY_HEIGHT] }
@@ -0,0 +1,259 @@
+use super::VM;
+
+// A tiny C-like expression language for breakpoint conditions and logpoints, e.g.
+//
+//   v3 == 0x10 && i > 0x300
+//   [i + 2] != 0 || dt == 0
+//
+// Names are v0-vf, i, pc, dt, st, sp (stack depth) and addr, the address a
+// watchpoint fired for. [x] reads the byte at x. Everything is an i64 and
+// comparisons give 1 or 0, like in C.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(i64),
+    Register(usize),
+    Index,
+    ProgramCounter,
+    DelayTimer,
+    SoundTimer,
+    StackDepth,
+    Address,
+    Memory(Box<Expression>),
+    Unary(UnaryOp, Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    Complement
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder
+}
+
+// Binary operators from the loosest to the tightest binding.
+const PRECEDENCE: &[&[(&str, BinaryOp)]] = &[
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("==", BinaryOp::Equal), ("!=", BinaryOp::NotEqual)],
+    &[("<=", BinaryOp::LessEqual), (">=", BinaryOp::GreaterEqual), ("<", BinaryOp::Less), (">", BinaryOp::Greater)],
+    &[("<<", BinaryOp::ShiftLeft), (">>", BinaryOp::ShiftRight)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Subtract)],
+    &[("*", BinaryOp::Multiply), ("/", BinaryOp::Divide), ("%", BinaryOp::Remainder)]
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str)
+}
+
+const SYMBOLS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>",
+    "|", "^", "&", "<", ">", "+", "-", "*", "/", "%", "!", "~", "(", ")", "[", "]"
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            let word = &rest[..end];
+            if c.is_ascii_digit() {
+                let number = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => word.parse()
+                };
+                tokens.push(Token::Number(number.map_err(|_| format!("invalid number {}", word))?));
+            } else {
+                tokens.push(Token::Name(word.to_ascii_lowercase()));
+            }
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)).ok_or(format!("unexpected {}", c))?;
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Symbol(found)) if found == symbol => Ok(()),
+            _ => Err(format!("expected {}", symbol))
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expression, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Symbol(symbol)) = self.peek() {
+            let Some((_, op)) = PRECEDENCE[level].iter().find(|(text, _)| text == symbol) else {
+                break;
+            };
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Expression::Binary(*op, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        let op = match self.peek() {
+            Some(Token::Symbol("-")) => UnaryOp::Negate,
+            Some(Token::Symbol("!")) => UnaryOp::Not,
+            Some(Token::Symbol("~")) => UnaryOp::Complement,
+            _ => return self.primary()
+        };
+        self.position += 1;
+        Ok(Expression::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expression::Number(value)),
+            Some(Token::Symbol("(")) => {
+                let inner = self.binary(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            },
+            Some(Token::Symbol("[")) => {
+                let address = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Expression::Memory(Box::new(address)))
+            },
+            Some(Token::Name(name)) => match name.as_str() {
+                "i" => Ok(Expression::Index),
+                "pc" => Ok(Expression::ProgramCounter),
+                "dt" => Ok(Expression::DelayTimer),
+                "st" => Ok(Expression::SoundTimer),
+                "sp" => Ok(Expression::StackDepth),
+                "addr" => Ok(Expression::Address),
+                _ => name.strip_prefix('v')
+                    .filter(|digit| digit.len() == 1)
+                    .and_then(|digit| usize::from_str_radix(digit, 16).ok())
+                    .map(Expression::Register)
+                    .ok_or(format!("unknown name {}", name))
+            },
+            Some(Token::Symbol(symbol)) => Err(format!("unexpected {}", symbol)),
+            None => Err("unexpected end of expression".to_string())
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, String> {
+        let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+        let expression = parser.binary(0)?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(format!("unexpected {:?} after the expression", token))
+        }
+    }
+
+    // `address` is what `addr` evaluates to, the accessed address for watchpoints.
+    // Reads outside of memory give 0 and division by zero gives 0, a condition should never fail.
+    pub fn eval(&self, vm: &VM, address: Option<usize>) -> i64 {
+        match self {
+            Expression::Number(value) => *value,
+            Expression::Register(index) => vm.variable_registers[*index] as i64,
+            Expression::Index => vm.index_register as i64,
+            Expression::ProgramCounter => vm.program_counter as i64,
+            Expression::DelayTimer => vm.delay_timer as i64,
+            Expression::SoundTimer => vm.sound_timer as i64,
+            Expression::StackDepth => (vm.stack.top + 1) as i64,
+            Expression::Address => address.map_or(0, |address| address as i64),
+            Expression::Memory(inner) => {
+                let address = inner.eval(vm, address);
+                usize::try_from(address).ok().and_then(|address| vm.memory.get(address)).map_or(0, |value| *value as i64)
+            },
+            Expression::Unary(op, inner) => {
+                let value = inner.eval(vm, address);
+                match op {
+                    UnaryOp::Negate => value.wrapping_neg(),
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::Complement => !value
+                }
+            },
+            Expression::Binary(op, left, right) => {
+                let left = left.eval(vm, address);
+                // Short circuit so `[i] && ...` style guards behave as expected.
+                match op {
+                    BinaryOp::Or if left != 0 => return 1,
+                    BinaryOp::And if left == 0 => return 0,
+                    _ => ()
+                }
+                let right = right.eval(vm, address);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (right != 0) as i64,
+                    BinaryOp::Equal => (left == right) as i64,
+                    BinaryOp::NotEqual => (left != right) as i64,
+                    BinaryOp::Less => (left < right) as i64,
+                    BinaryOp::LessEqual => (left <= right) as i64,
+                    BinaryOp::Greater => (left > right) as i64,
+                    BinaryOp::GreaterEqual => (left >= right) as i64,
+                    BinaryOp::BitOr => left | right,
+                    BinaryOp::BitXor => left ^ right,
+                    BinaryOp::BitAnd => left & right,
+                    BinaryOp::ShiftLeft => left.wrapping_shl(right as u32),
+                    BinaryOp::ShiftRight => left.wrapping_shr(right as u32),
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Subtract => left.wrapping_sub(right),
+                    BinaryOp::Multiply => left.wrapping_mul(right),
+                    BinaryOp::Divide => left.checked_div(right).unwrap_or(0),
+                    BinaryOp::Remainder => left.checked_rem(right).unwrap_or(0)
+                }
+            }
+        }
+    }
+}
@@ -5,7 +5,9 @@ pub enum InstructionDecodeError {
 
 impl std::fmt::Display for InstructionDecodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        return write!(f, "invalid instruction");
+        match self {
+            InstructionDecodeError::UnsupportedOpcode { raw_inst } => write!(f, "invalid instruction {:04X}", raw_inst)
+        }
     }
 }
 #[derive(Debug)]
@@ -45,9 +47,103 @@ pub enum Instruction {
     AddVXToIndexRegister { vx: usize },
     GetKeyBlock { vx: usize },
     FontChar { vx: usize },
+    BigFontChar { vx: usize },
     BinaryCodedDecimalConversion { vx: usize },
     SaveVXToMem { vx: usize },
     LoadVXFromMem { vx: usize },
+    SaveVXToFlags { vx: usize },
+    LoadVXFromFlags { vx: usize },
+}
+
+impl Instruction {
+    // The disassembly as the VM will run it, in chip48 mode BNNN jumps relative to VX instead of V0.
+    pub fn disassemble(&self, chip48_mode: bool) -> String {
+        match self {
+            Instruction::JumpOffset { vx, offset } if chip48_mode => format!("JP V{:X}, {:03X}", vx, offset),
+            _ => self.to_string()
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instruction::DisplayClear => "DisplayClear",
+            Instruction::SubReturn => "SubReturn",
+            Instruction::Jump { .. } => "Jump",
+            Instruction::SetVX { .. } => "SetVX",
+            Instruction::AddVX { .. } => "AddVX",
+            Instruction::SetIR { .. } => "SetIR",
+            Instruction::Draw { .. } => "Draw",
+            Instruction::SubCall { .. } => "SubCall",
+            Instruction::SkipEq { .. } => "SkipEq",
+            Instruction::SkipNotEq { .. } => "SkipNotEq",
+            Instruction::SkipVEq { .. } => "SkipVEq",
+            Instruction::SkipVNotEq { .. } => "SkipVNotEq",
+            Instruction::MSetVReg { .. } => "MSetVReg",
+            Instruction::MSetVRegOr { .. } => "MSetVRegOr",
+            Instruction::MSetVRegAnd { .. } => "MSetVRegAnd",
+            Instruction::MSetVRegXor { .. } => "MSetVRegXor",
+            Instruction::MAddWithCarry { .. } => "MAddWithCarry",
+            Instruction::MSubWithBorrow { .. } => "MSubWithBorrow",
+            Instruction::MSubInvWithBorrow { .. } => "MSubInvWithBorrow",
+            Instruction::MShiftRight { .. } => "MShiftRight",
+            Instruction::MShiftLeft { .. } => "MShiftLeft",
+            Instruction::JumpOffset { .. } => "JumpOffset",
+            Instruction::Random { .. } => "Random",
+            Instruction::SkipIfKey { .. } => "SkipIfKey",
+            Instruction::SkipIfNotKey { .. } => "SkipIfNotKey",
+            Instruction::SetVXToDelayTimer { .. } => "SetVXToDelayTimer",
+            Instruction::SetDelayTimerToVX { .. } => "SetDelayTimerToVX",
+            Instruction::SetSoundTimerToVX { .. } => "SetSoundTimerToVX",
+            Instruction::AddVXToIndexRegister { .. } => "AddVXToIndexRegister",
+            Instruction::GetKeyBlock { .. } => "GetKeyBlock",
+            Instruction::FontChar { .. } => "FontChar",
+            Instruction::BigFontChar { .. } => "BigFontChar",
+            Instruction::BinaryCodedDecimalConversion { .. } => "BinaryCodedDecimalConversion",
+            Instruction::SaveVXToMem { .. } => "SaveVXToMem",
+            Instruction::LoadVXFromMem { .. } => "LoadVXFromMem",
+            Instruction::SaveVXToFlags { .. } => "SaveVXToFlags",
+            Instruction::LoadVXFromFlags { .. } => "LoadVXFromFlags",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extension {
+    SuperChip,
+    XoChip
+}
+
+impl Extension {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Extension::SuperChip => "SCHIP",
+            Extension::XoChip => "XO-CHIP"
+        }
+    }
+}
+
+// Opcodes that only exist on SCHIP or XO-CHIP, with their length in bytes.
+// FX30 and DXY0 decode here but are SCHIP all the same.
+pub fn extension(raw_inst: u16) -> Option<(Extension, usize)> {
+    let x = (raw_inst & 0x0F00) >> 8;
+    match raw_inst & 0xF000 {
+        0x0000 => match raw_inst & 0xFFF0 {
+            0x00C0 => Some((Extension::SuperChip, 2)),
+            0x00D0 => Some((Extension::XoChip, 2)),
+            0x00F0 if (0xFB..=0xFF).contains(&(raw_inst & 0xFF)) => Some((Extension::SuperChip, 2)),
+            _ => None
+        },
+        0x5000 if matches!(raw_inst & 0xF, 2 | 3) => Some((Extension::XoChip, 2)),
+        0xD000 if raw_inst & 0xF == 0 => Some((Extension::SuperChip, 2)),
+        0xF000 => match raw_inst & 0xFF {
+            0x00 if x == 0 => Some((Extension::XoChip, 4)),
+            0x01 | 0x3A => Some((Extension::XoChip, 2)),
+            0x02 if x == 0 => Some((Extension::XoChip, 2)),
+            0x30 | 0x75 | 0x85 => Some((Extension::SuperChip, 2)),
+            _ => None
+        },
+        _ => None
+    }
 }
 
 pub fn decode(raw_inst: u16) -> Result<Instruction, InstructionDecodeError> {
@@ -64,7 +160,7 @@ pub fn decode(raw_inst: u16) -> Result<Instruction, InstructionDecodeError> {
         0x0000 => match raw_inst {
             0x00E0 => Ok(Instruction::DisplayClear),
             0x00EE => Ok(Instruction::SubReturn),
-            _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst: raw_inst })
+            _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst })
         },
         0x1000 => Ok(Instruction::Jump { target: nnn }),
         0x6000 => Ok(Instruction::SetVX { index: x.into(), value: nn }),
@@ -86,14 +182,14 @@ pub fn decode(raw_inst: u16) -> Result<Instruction, InstructionDecodeError> {
             0x7 => Ok(Instruction::MSubInvWithBorrow { vx: x.into(), vy: y.into() }),
             0x6 => Ok(Instruction::MShiftRight { vx: x.into(), vy: y.into() }),
             0xE => Ok(Instruction::MShiftLeft { vx: x.into(), vy: y.into() }),
-            _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst: raw_inst })
+            _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst })
         },
-        0xB000 => Ok(Instruction::JumpOffset { vx: x.into(), offset: opcode & 0x0FFF }),
+        0xB000 => Ok(Instruction::JumpOffset { vx: x.into(), offset: nnn }),
         0xC000 => Ok(Instruction::Random { vx: x.into(), nn }),
         0xE000 => match nn {
             0x9E => Ok(Instruction::SkipIfKey { vx: x.into() }),
             0xA1 => Ok(Instruction::SkipIfNotKey { vx: x.into() }),
-            _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst: raw_inst })
+            _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst })
         },
         0xF000 => match nn {
             0x7 => Ok(Instruction::SetVXToDelayTimer { vx: x.into() }),
@@ -102,11 +198,58 @@ pub fn decode(raw_inst: u16) -> Result<Instruction, InstructionDecodeError> {
             0x1E => Ok(Instruction::AddVXToIndexRegister { vx: x.into() }),
             0x0A => Ok(Instruction::GetKeyBlock { vx: x.into() }),
             0x29 => Ok(Instruction::FontChar { vx: x.into() }),
+            0x30 => Ok(Instruction::BigFontChar { vx: x.into() }),
             0x33 => Ok(Instruction::BinaryCodedDecimalConversion { vx: x.into() }),
             0x55 => Ok(Instruction::SaveVXToMem { vx: x.into() }),
             0x65 => Ok(Instruction::LoadVXFromMem { vx: x.into() }),
-            _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst: raw_inst })
+            0x75 => Ok(Instruction::SaveVXToFlags { vx: x.into() }),
+            0x85 => Ok(Instruction::LoadVXFromFlags { vx: x.into() }),
+            _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst })
         }
-        _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst: raw_inst })
+        _ => Err(InstructionDecodeError::UnsupportedOpcode { raw_inst })
     }
-}
\ No newline at end of file
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Instruction::DisplayClear => write!(f, "CLS"),
+            Instruction::SubReturn => write!(f, "RET"),
+            Instruction::Jump { target } => write!(f, "JP {:03X}", target),
+            Instruction::SetVX { index, value } => write!(f, "LD V{:X}, {:02X}", index, value),
+            Instruction::AddVX { index, value } => write!(f, "ADD V{:X}, {:02X}", index, value),
+            Instruction::SetIR { value } => write!(f, "LD I, {:03X}", value),
+            Instruction::Draw { vx, vy, height } => write!(f, "DRW V{:X}, V{:X}, {:X}", vx, vy, height),
+            Instruction::SubCall { target } => write!(f, "CALL {:03X}", target),
+            Instruction::SkipEq { vx, nn } => write!(f, "SE V{:X}, {:02X}", vx, nn),
+            Instruction::SkipNotEq { vx, nn } => write!(f, "SNE V{:X}, {:02X}", vx, nn),
+            Instruction::SkipVEq { vx, vy } => write!(f, "SE V{:X}, V{:X}", vx, vy),
+            Instruction::SkipVNotEq { vx, vy } => write!(f, "SNE V{:X}, V{:X}", vx, vy),
+            Instruction::MSetVReg { vx, vy } => write!(f, "LD V{:X}, V{:X}", vx, vy),
+            Instruction::MSetVRegOr { vx, vy } => write!(f, "OR V{:X}, V{:X}", vx, vy),
+            Instruction::MSetVRegAnd { vx, vy } => write!(f, "AND V{:X}, V{:X}", vx, vy),
+            Instruction::MSetVRegXor { vx, vy } => write!(f, "XOR V{:X}, V{:X}", vx, vy),
+            Instruction::MAddWithCarry { vx, vy } => write!(f, "ADD V{:X}, V{:X}", vx, vy),
+            Instruction::MSubWithBorrow { vx, vy } => write!(f, "SUB V{:X}, V{:X}", vx, vy),
+            Instruction::MSubInvWithBorrow { vx, vy } => write!(f, "SUBN V{:X}, V{:X}", vx, vy),
+            Instruction::MShiftRight { vx, vy } => write!(f, "SHR V{:X}, V{:X}", vx, vy),
+            Instruction::MShiftLeft { vx, vy } => write!(f, "SHL V{:X}, V{:X}", vx, vy),
+            Instruction::JumpOffset { vx: _, offset } => write!(f, "JP V0, {:03X}", offset),
+            Instruction::Random { vx, nn } => write!(f, "RND V{:X}, {:02X}", vx, nn),
+            Instruction::SkipIfKey { vx } => write!(f, "SKP V{:X}", vx),
+            Instruction::SkipIfNotKey { vx } => write!(f, "SKNP V{:X}", vx),
+            Instruction::SetVXToDelayTimer { vx } => write!(f, "LD V{:X}, DT", vx),
+            Instruction::SetDelayTimerToVX { vx } => write!(f, "LD DT, V{:X}", vx),
+            Instruction::SetSoundTimerToVX { vx } => write!(f, "LD ST, V{:X}", vx),
+            Instruction::AddVXToIndexRegister { vx } => write!(f, "ADD I, V{:X}", vx),
+            Instruction::GetKeyBlock { vx } => write!(f, "LD V{:X}, K", vx),
+            Instruction::FontChar { vx } => write!(f, "LD F, V{:X}", vx),
+            Instruction::BigFontChar { vx } => write!(f, "LD HF, V{:X}", vx),
+            Instruction::BinaryCodedDecimalConversion { vx } => write!(f, "LD B, V{:X}", vx),
+            Instruction::SaveVXToMem { vx } => write!(f, "LD [I], V{:X}", vx),
+            Instruction::LoadVXFromMem { vx } => write!(f, "LD V{:X}, [I]", vx),
+            Instruction::SaveVXToFlags { vx } => write!(f, "LD R, V{:X}", vx),
+            Instruction::LoadVXFromFlags { vx } => write!(f, "LD V{:X}, R", vx),
+        }
+    }
+}
@@ -2,6 +2,12 @@ pub struct Keyboard {
     pub keys: [bool; 16]
 }
 
+impl Default for Keyboard {
+    fn default() -> Keyboard {
+        Keyboard::new()
+    }
+}
+
 impl Keyboard {
     pub fn new() -> Keyboard {
         Keyboard {
@@ -4,13 +4,54 @@ mod stack;
 mod display;
 mod keyboard;
 mod font;
+mod coverage;
+mod random;
+mod platform;
+mod sanitizer;
+mod expression;
+mod debugger;
+mod snapshot;
+mod quirks;
+mod patch;
 
 pub use font::FONT_DATA;
+pub use font::BIG_FONT_DATA;
+pub use font::FONT_SIZE;
+pub use font::FontSet;
 pub use display::DISPLAY_HEIGHT;
 pub use display::DISPLAY_WIDTH;
 pub use vm::VM;
+pub use vm::VMError;
+pub use vm::FaultKind;
+pub use vm::LoadError;
+pub use vm::MEMORY_SIZE;
+pub use vm::VREG_COUNT;
+pub use vm::RPL_FLAG_COUNT;
 pub use keyboard::Keyboard;
 pub use display::Display;
 pub use stack::Stack;
+pub use stack::STACK_SIZE;
 pub use instruction::Instruction;
-pub use instruction::InstructionDecodeError;
\ No newline at end of file
+pub use instruction::InstructionDecodeError;
+pub use instruction::decode;
+pub use instruction::Extension;
+pub use instruction::extension;
+pub use coverage::Coverage;
+pub use random::RandomSource;
+pub use random::SeededRandom;
+pub use platform::Platform;
+pub use sanitizer::Sanitizer;
+pub use sanitizer::Warning;
+pub use sanitizer::WarningKind;
+pub use expression::Expression;
+pub use debugger::Debugger;
+pub use debugger::Breakpoint;
+pub use debugger::Trigger;
+pub use debugger::MAX_MESSAGES;
+pub use snapshot::Snapshot;
+pub use quirks::Quirks;
+pub use patch::PatchFormat;
+pub use patch::apply_patch;
+pub use patch::apply_patch_files;
+pub use patch::create_patch;
+pub use patch::crc32;
@@ -0,0 +1,289 @@
+use std::path::{Path, PathBuf};
+
+use super::LoadError;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+// Records can't start at the offset that spells "EOF", so IPS stops short of it.
+const IPS_MAX_SIZE: usize = 0x454F46;
+const IPS_MAX_RECORD: usize = 0xFFFF;
+const BPS_MAGIC: &[u8] = b"BPS1";
+// No platform has more memory, and the patch's word for its output size is not taken on trust.
+const BPS_MAX_SIZE: usize = 0x10000;
+// Matching bytes shorter than this are cheaper to repeat than to switch actions for.
+const MIN_MATCH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Bps
+}
+
+impl PatchFormat {
+    // By extension, BPS unless it is .ips since only BPS can check what it patches.
+    pub fn from_path(path: &Path) -> PatchFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("ips") => PatchFormat::Ips,
+            _ => PatchFormat::Bps
+        }
+    }
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let taken = self.bytes.get(self.offset..self.offset + count).ok_or("the patch is truncated")?;
+        self.offset += count;
+        Ok(taken)
+    }
+
+    fn big_endian(&mut self, count: usize) -> Result<usize, String> {
+        Ok(self.take(count)?.iter().fold(0, |value, byte| value << 8 | *byte as usize))
+    }
+
+    // BPS numbers: seven bits at a time, lowest first, the last byte marked by its top bit.
+    fn number(&mut self) -> Result<usize, String> {
+        let too_large = || "a number in the patch is too large".to_string();
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.take(1)?[0] as usize;
+            value = (byte & 0x7F).checked_mul(shift).and_then(|bits| value.checked_add(bits)).ok_or_else(too_large)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(too_large)?;
+            value = value.checked_add(shift).ok_or_else(too_large)?;
+        }
+    }
+}
+
+fn write_number(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let low = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | low);
+            return;
+        }
+        out.push(low);
+        value -= 1;
+    }
+}
+
+// Applies the patch files in order, each to what the one before made of the ROM.
+pub fn apply_patch_files(mut rom: Vec<u8>, patches: &[PathBuf]) -> Result<Vec<u8>, LoadError> {
+    for path in patches {
+        let patch = std::fs::read(path).map_err(|err| LoadError::Patch { path: path.clone(), reason: err.to_string() })?;
+        rom = apply_patch(&patch, &rom).map_err(|reason| LoadError::Patch { path: path.clone(), reason })?;
+    }
+    Ok(rom)
+}
+
+// Applies an IPS or BPS patch, told apart by their magic.
+pub fn apply_patch(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(patch, rom)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(patch, rom)
+    } else {
+        Err("not an IPS or BPS patch".to_string())
+    }
+}
+
+fn apply_ips(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = Reader { bytes: patch, offset: IPS_MAGIC.len() };
+    let mut target = rom.to_vec();
+
+    loop {
+        if reader.bytes.get(reader.offset..reader.offset + IPS_EOF.len()) == Some(IPS_EOF) {
+            reader.offset += IPS_EOF.len();
+            break;
+        }
+
+        let offset = reader.big_endian(3)?;
+        let size = reader.big_endian(2)?;
+        let data = if size == 0 {
+            let count = reader.big_endian(2)?;
+            vec![reader.take(1)?[0]; count]
+        } else {
+            reader.take(size)?.to_vec()
+        };
+
+        if target.len() < offset + data.len() {
+            target.resize(offset + data.len(), 0);
+        }
+        target[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    // The truncation extension, a new size after the end marker.
+    if reader.bytes.len() - reader.offset == 3 {
+        target.truncate(reader.big_endian(3)?);
+    }
+
+    Ok(target)
+}
+
+fn apply_bps(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < BPS_MAGIC.len() + 12 {
+        return Err("the patch is truncated".to_string());
+    }
+
+    let footer = patch.len() - 12;
+    let checksum = |at: usize| u32::from_le_bytes(patch[at..at + 4].try_into().unwrap());
+    if crc32(&patch[..footer + 8]) != checksum(footer + 8) {
+        return Err("the patch is corrupt, its checksum does not match".to_string());
+    }
+    if crc32(rom) != checksum(footer) {
+        return Err(format!("the patch is for a different ROM (CRC32 {:08X}, this one is {:08X})", checksum(footer), crc32(rom)));
+    }
+
+    let mut reader = Reader { bytes: &patch[..footer], offset: BPS_MAGIC.len() };
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.take(metadata_size)?;
+
+    if source_size != rom.len() {
+        return Err(format!("the patch is for a {} byte ROM, this one is {} bytes", source_size, rom.len()));
+    }
+
+    if target_size > BPS_MAX_SIZE {
+        return Err(format!("the patch makes a {} byte ROM, more than any platform holds", target_size));
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+    let relative = |offset: usize, delta: usize| -> Result<usize, String> {
+        let distance = delta >> 1;
+        let moved = if delta & 1 == 1 { offset.checked_sub(distance) } else { offset.checked_add(distance) };
+        moved.ok_or("a copy in the patch starts outside the data".to_string())
+    };
+
+    while reader.offset < footer {
+        let action = reader.number()?;
+        let length = (action >> 2) + 1;
+        if length > target_size - target.len() {
+            return Err("the patch writes past the end of the patched ROM".to_string());
+        }
+
+        match action & 3 {
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + length).ok_or("a copy in the patch reads past the end of the ROM")?);
+            },
+            1 => target.extend_from_slice(reader.take(length)?),
+            2 => {
+                source_offset = relative(source_offset, reader.number()?)?;
+                let end = source_offset.checked_add(length).ok_or("a copy in the patch starts outside the data")?;
+                target.extend_from_slice(rom.get(source_offset..end).ok_or("a copy in the patch reads past the end of the ROM")?);
+                source_offset += length;
+            },
+            _ => {
+                target_offset = relative(target_offset, reader.number()?)?;
+                // Byte by byte, the copy may overlap what it is writing.
+                for _ in 0..length {
+                    let byte = *target.get(target_offset).ok_or("a copy in the patch reads bytes not written yet")?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(format!("the patch produced {} bytes instead of {}", target.len(), target_size));
+    }
+    if crc32(&target) != checksum(footer + 4) {
+        return Err("the patched ROM does not match the patch's checksum".to_string());
+    }
+
+    Ok(target)
+}
+
+// The ranges where `patched` differs from `original`, joined when only a few equal bytes lie between them.
+fn changes(original: &[u8], patched: &[u8]) -> Vec<(usize, usize)> {
+    let mut changes: Vec<(usize, usize)> = Vec::new();
+    for (offset, byte) in patched.iter().enumerate() {
+        if original.get(offset) == Some(byte) {
+            continue;
+        }
+        match changes.last_mut() {
+            Some((_, end)) if offset - *end < MIN_MATCH => *end = offset + 1,
+            _ => changes.push((offset, offset + 1))
+        }
+    }
+    changes
+}
+
+pub fn create_patch(format: PatchFormat, original: &[u8], patched: &[u8]) -> Result<Vec<u8>, String> {
+    match format {
+        PatchFormat::Ips => create_ips(original, patched),
+        PatchFormat::Bps => Ok(create_bps(original, patched))
+    }
+}
+
+fn create_ips(original: &[u8], patched: &[u8]) -> Result<Vec<u8>, String> {
+    if original.len().max(patched.len()) >= IPS_MAX_SIZE {
+        return Err(format!("IPS patches can't reach past {:#X} bytes, use BPS", IPS_MAX_SIZE));
+    }
+
+    let mut patch = IPS_MAGIC.to_vec();
+    for (start, end) in changes(original, patched) {
+        for offset in (start..end).step_by(IPS_MAX_RECORD) {
+            let data = &patched[offset..end.min(offset + IPS_MAX_RECORD)];
+            patch.extend_from_slice(&(offset as u32).to_be_bytes()[1..]);
+            patch.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            patch.extend_from_slice(data);
+        }
+    }
+    patch.extend_from_slice(IPS_EOF);
+
+    if patched.len() < original.len() {
+        patch.extend_from_slice(&(patched.len() as u32).to_be_bytes()[1..]);
+    }
+    Ok(patch)
+}
+
+// Reads unchanged bytes from the original in place and stores everything else in the patch.
+fn create_bps(original: &[u8], patched: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_MAGIC.to_vec();
+    write_number(&mut patch, original.len());
+    write_number(&mut patch, patched.len());
+    write_number(&mut patch, 0);
+
+    let mut offset = 0;
+    for (start, end) in changes(original, patched) {
+        if start > offset {
+            write_number(&mut patch, (start - offset - 1) << 2);
+        }
+        write_number(&mut patch, ((end - start - 1) << 2) | 1);
+        patch.extend_from_slice(&patched[start..end]);
+        offset = end;
+    }
+    if patched.len() > offset {
+        write_number(&mut patch, (patched.len() - offset - 1) << 2);
+    }
+
+    patch.extend_from_slice(&crc32(original).to_le_bytes());
+    patch.extend_from_slice(&crc32(patched).to_le_bytes());
+    let checksum = crc32(&patch);
+    patch.extend_from_slice(&checksum.to_le_bytes());
+    patch
+}
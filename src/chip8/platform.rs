@@ -0,0 +1,96 @@
+use std::ops::Range;
+
+use super::{MEMORY_SIZE, RPL_FLAG_COUNT};
+
+// The memory map a ROM is written for. The reserved region is where the
+// original interpreter lived and `reserved_top` how many bytes at the end of
+// memory it kept for itself, programs may not be loaded into either.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Platform {
+    pub name: &'static str,
+    pub memory_size: usize,
+    pub load_address: usize,
+    pub entry_point: usize,
+    pub reserved: Range<usize>,
+    pub reserved_top: usize,
+    // How many SCHIP RPL user flags FX75/FX85 reach, 8 as on the HP-48 or 16 on XO-CHIP.
+    pub rpl_flags: usize
+}
+
+impl Platform {
+    pub fn chip8() -> Platform {
+        Platform { name: "chip8", memory_size: MEMORY_SIZE, load_address: 0x200, entry_point: 0x200, reserved: 0x000..0x200, reserved_top: 0, rpl_flags: 8 }
+    }
+
+    // The 2K COSMAC VIP, the top 0x160 bytes hold the stack, variables and the display.
+    pub fn vip_2k() -> Platform {
+        Platform { name: "vip2k", memory_size: 0x800, load_address: 0x200, entry_point: 0x200, reserved: 0x000..0x200, reserved_top: 0x160, rpl_flags: 8 }
+    }
+
+    pub fn eti660() -> Platform {
+        Platform { name: "eti660", memory_size: MEMORY_SIZE, load_address: 0x600, entry_point: 0x600, reserved: 0x000..0x600, reserved_top: 0, rpl_flags: 8 }
+    }
+
+    pub fn xo_chip() -> Platform {
+        Platform { name: "xochip", memory_size: 0x10000, load_address: 0x200, entry_point: 0x200, reserved: 0x000..0x200, reserved_top: 0, rpl_flags: 16 }
+    }
+
+    pub fn all() -> [Platform; 4] {
+        [Platform::chip8(), Platform::vip_2k(), Platform::eti660(), Platform::xo_chip()]
+    }
+
+    pub fn from_name(name: &str) -> Option<Platform> {
+        Platform::all().into_iter().find(|platform| platform.name.eq_ignore_ascii_case(name))
+    }
+
+    // Where the interpreter's area at the top of memory starts, the end of memory without one.
+    pub fn program_end(&self) -> usize {
+        self.memory_size.saturating_sub(self.reserved_top)
+    }
+
+    // Both interpreter areas, the one at the top may be empty.
+    pub fn reserved_regions(&self) -> [Range<usize>; 2] {
+        [self.reserved.clone(), self.program_end()..self.memory_size]
+    }
+
+    pub fn is_reserved(&self, address: usize) -> bool {
+        self.reserved_regions().iter().any(|region| region.contains(&address))
+    }
+
+    // Bytes available to a program, from the load address to the interpreter's area at the top.
+    pub fn program_space(&self) -> usize {
+        self.program_end().saturating_sub(self.load_address)
+    }
+
+    // Catches descriptions that could never run anything, before a VM is built from them.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rpl_flags > RPL_FLAG_COUNT {
+            return Err(format!("{} RPL flags is more than the {} there can be", self.rpl_flags, RPL_FLAG_COUNT));
+        }
+        if self.memory_size == 0 || self.memory_size > 0x10000 {
+            return Err(format!("memory size {:#X} is not between 1 and 64K", self.memory_size));
+        }
+        if self.load_address >= self.memory_size {
+            return Err(format!("load address {:#X} is outside of {:#X} bytes of memory", self.load_address, self.memory_size));
+        }
+        if self.load_address >= self.program_end() {
+            return Err(format!("load address {:#X} is inside the interpreter's area from {:#X}", self.load_address, self.program_end()));
+        }
+        if self.entry_point + 1 >= self.memory_size {
+            return Err(format!("entry point {:#X} is outside of {:#X} bytes of memory", self.entry_point, self.memory_size));
+        }
+        if self.reserved.contains(&self.load_address) {
+            return Err(format!(
+                "load address {:#X} is inside the reserved region {:#X}..{:#X}",
+                self.load_address, self.reserved.start, self.reserved.end
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Platform {
+        Platform::chip8()
+    }
+}
@@ -0,0 +1,140 @@
+use super::{Instruction, VM};
+
+// The behaviours interpreters disagree on, as one value that can be named,
+// detected, stored and applied to a VM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VX in place instead of shifting VY into VX.
+    pub shift_legacy: bool,
+    // BXNN jumps to XNN + VX instead of NNN + V0.
+    pub chip48_mode: bool,
+    // 8XY1/8XY2/8XY3 clear VF.
+    pub vf_reset: bool,
+    // FX55/FX65 advance I past the registers they copy.
+    pub memory_increment: bool,
+    // DXYN clips sprites at the screen edges instead of wrapping them.
+    pub clip_sprites: bool
+}
+
+impl Quirks {
+    pub fn vip() -> Quirks {
+        Quirks { shift_legacy: false, chip48_mode: false, vf_reset: true, memory_increment: true, clip_sprites: true }
+    }
+
+    pub fn schip() -> Quirks {
+        Quirks { shift_legacy: true, chip48_mode: true, vf_reset: false, memory_increment: false, clip_sprites: true }
+    }
+
+    pub fn xo_chip() -> Quirks {
+        Quirks { shift_legacy: false, chip48_mode: false, vf_reset: false, memory_increment: true, clip_sprites: false }
+    }
+
+    pub fn all() -> [(&'static str, Quirks); 3] {
+        [("vip", Quirks::vip()), ("schip", Quirks::schip()), ("xochip", Quirks::xo_chip())]
+    }
+
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        Quirks::all().into_iter().find(|(preset, _)| preset.eq_ignore_ascii_case(name)).map(|(_, quirks)| quirks)
+    }
+
+    // A preset or "default", optionally followed by quirks to turn on or off,
+    // like "schip+vf_reset" or "vip-clip_sprites".
+    pub fn parse(spec: &str) -> Result<Quirks, String> {
+        let end = spec.find(['+', '-']).unwrap_or(spec.len());
+        let (base, mut changes) = spec.split_at(end);
+        let mut quirks = match base {
+            _ if base.eq_ignore_ascii_case("default") => Quirks::default(),
+            _ => Quirks::from_name(base).ok_or(format!("unknown quirks {}", base))?
+        };
+
+        while let Some(sign) = changes.chars().next() {
+            let rest = &changes[1..];
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let name = &rest[..end];
+            let flag = match name {
+                "shift_legacy" => &mut quirks.shift_legacy,
+                "chip48_mode" => &mut quirks.chip48_mode,
+                "vf_reset" => &mut quirks.vf_reset,
+                "memory_increment" => &mut quirks.memory_increment,
+                "clip_sprites" => &mut quirks.clip_sprites,
+                _ => return Err(format!("unknown quirk {}", name))
+            };
+            *flag = sign == '+';
+            changes = &rest[end..];
+        }
+        Ok(quirks)
+    }
+
+    // The first preset with exactly these settings, "custom" for anything else.
+    pub fn name(&self) -> &'static str {
+        Quirks::all().into_iter().find(|(_, quirks)| quirks == self).map_or("custom", |(name, _)| name)
+    }
+
+    // The shortest text `parse` turns back into these settings.
+    pub fn spec(&self) -> String {
+        let name = self.name();
+        if name != "custom" {
+            return name.to_string();
+        }
+
+        let mut spec = "default".to_string();
+        for ((quirk, value), _) in self.flags().into_iter().zip(Quirks::default().flags()).filter(|(a, b)| a.1 != b.1) {
+            spec.push(if value { '+' } else { '-' });
+            spec.push_str(quirk);
+        }
+        spec
+    }
+
+    // Each quirk by field name with its setting.
+    pub fn flags(&self) -> [(&'static str, bool); 5] {
+        [
+            ("shift_legacy", self.shift_legacy),
+            ("chip48_mode", self.chip48_mode),
+            ("vf_reset", self.vf_reset),
+            ("memory_increment", self.memory_increment),
+            ("clip_sprites", self.clip_sprites)
+        ]
+    }
+
+    // The quirks set differently here and in `other`.
+    pub fn differences(&self, other: &Quirks) -> Vec<&'static str> {
+        self.flags().into_iter().zip(other.flags()).filter(|(a, b)| a.1 != b.1).map(|(a, _)| a.0).collect()
+    }
+
+    // The quirks that can change what `instruction` does.
+    pub fn affecting(instruction: &Instruction) -> &'static [&'static str] {
+        match instruction {
+            Instruction::MShiftRight { .. } | Instruction::MShiftLeft { .. } => &["shift_legacy"],
+            Instruction::JumpOffset { .. } => &["chip48_mode"],
+            Instruction::MSetVRegOr { .. } | Instruction::MSetVRegAnd { .. } | Instruction::MSetVRegXor { .. } => &["vf_reset"],
+            Instruction::SaveVXToMem { .. } | Instruction::LoadVXFromMem { .. } => &["memory_increment"],
+            Instruction::Draw { .. } => &["clip_sprites"],
+            _ => &[]
+        }
+    }
+
+    pub fn of(vm: &VM) -> Quirks {
+        Quirks {
+            shift_legacy: vm.shift_legacy,
+            chip48_mode: vm.chip48_mode,
+            vf_reset: vm.vf_reset,
+            memory_increment: vm.memory_increment,
+            clip_sprites: vm.clip_sprites
+        }
+    }
+
+    pub fn apply(&self, vm: &mut VM) {
+        vm.shift_legacy = self.shift_legacy;
+        vm.chip48_mode = self.chip48_mode;
+        vm.vf_reset = self.vf_reset;
+        vm.memory_increment = self.memory_increment;
+        vm.clip_sprites = self.clip_sprites;
+    }
+}
+
+// What a fresh VM does, which is none of the presets.
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks { shift_legacy: false, chip48_mode: true, vf_reset: false, memory_increment: false, clip_sprites: false }
+    }
+}
@@ -0,0 +1,42 @@
+// Source of the bytes returned by CXNN. The whole generator state fits into
+// a u64 so runs can be reproduced by saving and restoring it. Memory is passed
+// along for generators that read it, as the original interpreters did.
+pub trait RandomSource: Send {
+    fn next_byte(&mut self, memory: &[u8]) -> u8;
+    fn state(&self) -> u64;
+    fn set_state(&mut self, state: u64);
+}
+
+// SplitMix64, every state is valid so any seed can be used as is.
+pub struct SeededRandom {
+    state: u64
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> SeededRandom {
+        SeededRandom { state: seed }
+    }
+
+    pub fn from_entropy() -> SeededRandom {
+        SeededRandom::new(rand::random())
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_byte(&mut self, _memory: &[u8]) -> u8 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as u8
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+}
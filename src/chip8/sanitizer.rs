@@ -0,0 +1,165 @@
+use std::{collections::HashSet, ops::Range};
+
+use super::{Instruction, VREG_COUNT};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    UninitialisedRegister,
+    UninitialisedMemory,
+    ReturnWithEmptyStack,
+    SkipIntoOpcode,
+    FlagOperand,
+    SelfModifyingCode
+}
+
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub pc: usize,
+    pub kind: WarningKind,
+    pub message: String
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "PC {:03X}: {}", self.pc, self.message)
+    }
+}
+
+// Watches a running VM for behaviour that is legal but almost always a bug,
+// or that different interpreters disagree on. Every kind of warning is only
+// reported once per PC so loops don't drown the log.
+pub struct Sanitizer {
+    registers_written: [bool; VREG_COUNT],
+    memory_initialised: Vec<bool>,
+    opcode_starts: Vec<bool>,
+    reported: HashSet<(usize, WarningKind)>,
+    pending: Vec<Warning>
+}
+
+fn is_skip(instruction: &Instruction) -> bool {
+    matches!(instruction,
+        Instruction::SkipEq { .. } | Instruction::SkipNotEq { .. } |
+        Instruction::SkipVEq { .. } | Instruction::SkipVNotEq { .. } |
+        Instruction::SkipIfKey { .. } | Instruction::SkipIfNotKey { .. })
+}
+
+fn registers_read(instruction: &Instruction, chip48_mode: bool, shift_legacy: bool) -> Vec<usize> {
+    match *instruction {
+        Instruction::AddVX { index, .. } => vec![index],
+        Instruction::Draw { vx, vy, .. } => vec![vx, vy],
+        Instruction::SkipEq { vx, .. } | Instruction::SkipNotEq { vx, .. } => vec![vx],
+        Instruction::SkipVEq { vx, vy } | Instruction::SkipVNotEq { vx, vy } => vec![vx, vy],
+        Instruction::MSetVReg { vy, .. } => vec![vy],
+        Instruction::MSetVRegOr { vx, vy } | Instruction::MSetVRegAnd { vx, vy } | Instruction::MSetVRegXor { vx, vy } |
+        Instruction::MAddWithCarry { vx, vy } | Instruction::MSubWithBorrow { vx, vy } | Instruction::MSubInvWithBorrow { vx, vy } => vec![vx, vy],
+        Instruction::MShiftRight { vx, vy } | Instruction::MShiftLeft { vx, vy } => {
+            if shift_legacy { vec![vx] } else { vec![vx, vy] }
+        },
+        Instruction::JumpOffset { vx, .. } => vec![if chip48_mode { vx } else { 0 }],
+        Instruction::SkipIfKey { vx } | Instruction::SkipIfNotKey { vx } | Instruction::GetKeyBlock { vx } |
+        Instruction::SetDelayTimerToVX { vx } | Instruction::SetSoundTimerToVX { vx } | Instruction::AddVXToIndexRegister { vx } |
+        Instruction::FontChar { vx } | Instruction::BigFontChar { vx } | Instruction::BinaryCodedDecimalConversion { vx } => vec![vx],
+        Instruction::SaveVXToMem { vx } | Instruction::SaveVXToFlags { vx } => (0..=vx).collect(),
+        _ => Vec::new()
+    }
+}
+
+fn registers_written(instruction: &Instruction) -> Vec<usize> {
+    match *instruction {
+        Instruction::SetVX { index, .. } | Instruction::AddVX { index, .. } => vec![index],
+        Instruction::MSetVReg { vx, .. } | Instruction::MSetVRegOr { vx, .. } | Instruction::MSetVRegAnd { vx, .. } |
+        Instruction::MSetVRegXor { vx, .. } | Instruction::Random { vx, .. } | Instruction::SetVXToDelayTimer { vx } => vec![vx],
+        Instruction::MAddWithCarry { vx, .. } | Instruction::MSubWithBorrow { vx, .. } | Instruction::MSubInvWithBorrow { vx, .. } |
+        Instruction::MShiftRight { vx, .. } | Instruction::MShiftLeft { vx, .. } => vec![vx, 0xF],
+        Instruction::Draw { .. } | Instruction::AddVXToIndexRegister { .. } => vec![0xF],
+        Instruction::LoadVXFromMem { vx } | Instruction::LoadVXFromFlags { vx } => (0..=vx).collect(),
+        _ => Vec::new()
+    }
+}
+
+impl Sanitizer {
+    // Everything in `initialised` counts as written before the program started,
+    // usually the font and the ROM.
+    pub fn new(memory_size: usize, initialised: &[Range<usize>]) -> Sanitizer {
+        let mut memory_initialised = vec![false; memory_size];
+        for range in initialised {
+            for address in range.clone().filter(|address| *address < memory_size) {
+                memory_initialised[address] = true;
+            }
+        }
+
+        Sanitizer {
+            registers_written: [false; VREG_COUNT],
+            memory_initialised,
+            opcode_starts: vec![false; memory_size],
+            reported: HashSet::new(),
+            pending: Vec::new()
+        }
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.pending)
+    }
+
+    fn warn(&mut self, pc: usize, kind: WarningKind, message: String) {
+        if self.reported.insert((pc, kind)) {
+            self.pending.push(Warning { pc, kind, message });
+        }
+    }
+
+    pub(super) fn before_instruction(&mut self, pc: usize, instruction: &Instruction, chip48_mode: bool, shift_legacy: bool, stack_empty: bool) {
+        if let Some(start) = self.opcode_starts.get_mut(pc) {
+            *start = true;
+        }
+
+        for register in registers_read(instruction, chip48_mode, shift_legacy) {
+            if !self.registers_written[register] {
+                self.warn(pc, WarningKind::UninitialisedRegister, format!("{} reads V{:X} before anything was written to it", instruction, register));
+            }
+        }
+
+        if let Instruction::MAddWithCarry { vx, vy } | Instruction::MSubWithBorrow { vx, vy } |
+            Instruction::MSubInvWithBorrow { vx, vy } | Instruction::MShiftRight { vx, vy } |
+            Instruction::MShiftLeft { vx, vy } = *instruction {
+            if vx == 0xF || vy == 0xF {
+                self.warn(pc, WarningKind::FlagOperand, format!("{} uses VF as an operand, the result depends on when the flag is written", instruction));
+            }
+        }
+
+        if matches!(instruction, Instruction::SubReturn) && stack_empty {
+            self.warn(pc, WarningKind::ReturnWithEmptyStack, "RET with an empty stack".to_string());
+        }
+    }
+
+    pub(super) fn after_instruction(&mut self, pc: usize, instruction: &Instruction, next_pc: usize, memory: &[u8]) {
+        for register in registers_written(instruction) {
+            self.registers_written[register] = true;
+        }
+
+        if is_skip(instruction) && next_pc == pc + 4 {
+            let skipped = memory.get(pc + 2..pc + 4);
+            if skipped == Some(&[0xF0, 0x00]) {
+                self.warn(pc, WarningKind::SkipIntoOpcode, format!("{} skips into the middle of the four byte F000 NNNN at {:03X}", instruction, pc + 2));
+            } else if next_pc > 0 && self.opcode_starts.get(next_pc - 1) == Some(&true) {
+                self.warn(pc, WarningKind::SkipIntoOpcode, format!("{} lands at {:03X}, in the middle of the instruction at {:03X}", instruction, next_pc, next_pc - 1));
+            }
+        }
+    }
+
+    pub(super) fn read(&mut self, pc: usize, address: usize) {
+        if self.memory_initialised.get(address) == Some(&false) {
+            self.warn(pc, WarningKind::UninitialisedMemory, format!("reads {:03X} before anything was written to it", address));
+        }
+    }
+
+    pub(super) fn write(&mut self, pc: usize, address: usize) {
+        if let Some(initialised) = self.memory_initialised.get_mut(address) {
+            *initialised = true;
+        }
+
+        let executed = |address: usize| self.opcode_starts.get(address) == Some(&true);
+        if executed(address) || (address > 0 && executed(address - 1)) {
+            self.warn(pc, WarningKind::SelfModifyingCode, format!("writes {:03X}, which was already executed as code", address));
+        }
+    }
+}
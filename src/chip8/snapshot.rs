@@ -0,0 +1,184 @@
+use super::{Display, Quirks, VM, VREG_COUNT, DISPLAY_WIDTH, DISPLAY_HEIGHT, RPL_FLAG_COUNT, stack::STACK_SIZE};
+
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u8 = 1;
+
+// Everything a running program can observe, with the quirks it ran under, enough to rewind a VM to this point.
+// The memory size is part of it, a snapshot only restores into the same platform.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Snapshot {
+    pub memory: Vec<u8>,
+    pub program_counter: usize,
+    pub index_register: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub variable_registers: [u8; VREG_COUNT],
+    pub stack: [u16; STACK_SIZE],
+    pub stack_top: i32,
+    pub pixels: Vec<bool>,
+    pub keys: [bool; 16],
+    pub random_state: u64,
+    pub rpl_flags: [u8; RPL_FLAG_COUNT],
+    pub quirks: Quirks
+}
+
+// Little endian helpers for the file format, which is the fields above in order after a magic and a version.
+struct Reader<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < count {
+            return Err("the snapshot is truncated".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend((self.memory.len() as u32).to_le_bytes());
+        bytes.extend(&self.memory);
+        bytes.extend((self.program_counter as u32).to_le_bytes());
+        bytes.extend(self.index_register.to_le_bytes());
+        bytes.push(self.delay_timer);
+        bytes.push(self.sound_timer);
+        bytes.extend(self.variable_registers);
+        for entry in self.stack {
+            bytes.extend(entry.to_le_bytes());
+        }
+        bytes.extend(self.stack_top.to_le_bytes());
+        bytes.extend(self.pixels.iter().map(|pixel| *pixel as u8));
+        bytes.extend(self.keys.iter().map(|key| *key as u8));
+        bytes.extend(self.random_state.to_le_bytes());
+        bytes.extend(self.rpl_flags);
+        let quirks = &self.quirks;
+        bytes.extend([quirks.shift_legacy, quirks.chip48_mode, quirks.vf_reset, quirks.memory_increment, quirks.clip_sprites].map(u8::from));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, String> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err("not a snapshot".to_string());
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(format!("unsupported snapshot version {}", version));
+        }
+
+        let memory_size = reader.u32()? as usize;
+        let memory = reader.take(memory_size)?.to_vec();
+        let program_counter = reader.u32()? as usize;
+        let index_register = reader.u16()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let variable_registers = reader.take(VREG_COUNT)?.try_into().unwrap();
+        let mut stack = [0; STACK_SIZE];
+        for entry in stack.iter_mut() {
+            *entry = reader.u16()?;
+        }
+        let stack_top = reader.u32()? as i32;
+        if !(-1..STACK_SIZE as i32).contains(&stack_top) {
+            return Err(format!("invalid stack pointer {}", stack_top));
+        }
+        let pixels = reader.take(DISPLAY_WIDTH * DISPLAY_HEIGHT)?.iter().map(|pixel| *pixel != 0).collect();
+        let mut keys = [false; 16];
+        for (key, byte) in keys.iter_mut().zip(reader.take(16)?) {
+            *key = *byte != 0;
+        }
+        let random_state = reader.u64()?;
+        let rpl_flags = reader.take(RPL_FLAG_COUNT)?.try_into().unwrap();
+        let quirks = reader.take(5)?;
+        let quirks = Quirks {
+            shift_legacy: quirks[0] != 0,
+            chip48_mode: quirks[1] != 0,
+            vf_reset: quirks[2] != 0,
+            memory_increment: quirks[3] != 0,
+            clip_sprites: quirks[4] != 0
+        };
+
+        Ok(Snapshot {
+            memory, program_counter, index_register, delay_timer, sound_timer, variable_registers,
+            stack, stack_top, pixels, keys, random_state, rpl_flags, quirks
+        })
+    }
+}
+
+impl VM {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut pixels = Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        for y in 0..DISPLAY_HEIGHT {
+            for x in 0..DISPLAY_WIDTH {
+                pixels.push(self.display.get(x, y));
+            }
+        }
+
+        Snapshot {
+            memory: self.memory.clone(),
+            program_counter: self.program_counter,
+            index_register: self.index_register,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            variable_registers: self.variable_registers,
+            stack: self.stack.data,
+            stack_top: self.stack.top,
+            pixels,
+            keys: self.keyboard.keys,
+            random_state: self.random.state(),
+            rpl_flags: self.rpl_flags,
+            quirks: Quirks::of(self)
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        if snapshot.memory.len() != self.memory.len() {
+            return Err(format!("the snapshot has {:#X} bytes of memory but the VM has {:#X}", snapshot.memory.len(), self.memory.len()));
+        }
+        if snapshot.pixels.len() != DISPLAY_WIDTH * DISPLAY_HEIGHT {
+            return Err(format!("the snapshot has {} pixels instead of {}", snapshot.pixels.len(), DISPLAY_WIDTH * DISPLAY_HEIGHT));
+        }
+
+        self.memory.copy_from_slice(&snapshot.memory);
+        self.program_counter = snapshot.program_counter;
+        self.index_register = snapshot.index_register;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.variable_registers = snapshot.variable_registers;
+        self.stack.data = snapshot.stack;
+        self.stack.top = snapshot.stack_top;
+        self.keyboard.keys = snapshot.keys;
+        self.random.set_state(snapshot.random_state);
+        self.rpl_flags = snapshot.rpl_flags;
+        snapshot.quirks.apply(self);
+
+        self.display = Display::new();
+        for (index, pixel) in snapshot.pixels.iter().enumerate() {
+            if *pixel {
+                self.display.flip(index % DISPLAY_WIDTH, index / DISPLAY_WIDTH);
+            }
+        }
+        Ok(())
+    }
+}
@@ -11,6 +11,12 @@ pub struct Stack {
     pub top: i32
 }
 
+impl Default for Stack {
+    fn default() -> Stack {
+        Stack::new()
+    }
+}
+
 impl Stack {
     pub fn new() -> Stack {
         Stack {
@@ -25,9 +31,9 @@ impl Stack {
             self.top += 1;
             self.data[self.top as usize] = value;
 
-            return Ok(())
+            Ok(())
         } else {
-            return Err(StackError::StackOverflow)
+            Err(StackError::StackOverflow)
         }
 
     }
@@ -38,10 +44,10 @@ impl Stack {
             let r = self.data[self.top as usize];
             self.top -= 1;
             
-            return Ok(r);
+            Ok(r)
 
         } else {
-            return Err(StackError::StackUnderflow);
+            Err(StackError::StackUnderflow)
         }
     }
 }
\ No newline at end of file
@@ -1,17 +1,16 @@
-use core::panic;
+use std::ops::Range;
 
-use macroquad::prelude::debug;
-use rand::Rng;
+use crate::chip8::{display, font};
 
-use crate::chip8::display;
-
-use super::{Instruction, InstructionDecodeError, Stack, Display, instruction, Keyboard};
+use super::{Instruction, InstructionDecodeError, Stack, Display, instruction, Keyboard, Coverage, coverage, RandomSource, SeededRandom, FontSet, Platform, Sanitizer, Debugger, patch};
 
 pub const MEMORY_SIZE: usize = 4096;
 pub const VREG_COUNT: usize = 16;
+pub const RPL_FLAG_COUNT: usize = 16;
 
 pub struct VM {
-    pub memory: [u8; MEMORY_SIZE],
+    pub platform: Platform,
+    pub memory: Vec<u8>,
     pub program_counter: usize,
     pub index_register: u16,
     pub delay_timer: u8,
@@ -21,21 +20,105 @@ pub struct VM {
     pub keyboard: Keyboard,
     pub display: Display,
     pub shift_legacy: bool,
-    pub chip48_mode: bool
+    pub chip48_mode: bool,
+    // 8XY1/8XY2/8XY3 clear VF, as on the VIP.
+    pub vf_reset: bool,
+    // FX55/FX65 leave I pointing past the last register, as on the VIP.
+    pub memory_increment: bool,
+    // Sprites are cut off at the screen edges instead of wrapping around.
+    pub clip_sprites: bool,
+    // The SCHIP RPL user flags, which outlive the program, see Platform::rpl_flags.
+    pub rpl_flags: [u8; RPL_FLAG_COUNT],
+    // Set by FX75 so the flags get saved, cleared by whoever saves them.
+    pub rpl_flags_written: bool,
+    pub font_address: usize,
+    // Raise MemoryFault for writes into the reserved region, fetches outside
+    // of the loaded program and I-relative accesses beyond memory.
+    pub strict: bool,
+    pub program: Range<usize>,
+    pub paused: bool,
+    pub memory_writes: Vec<usize>,
+    pub drawn_sprites: Vec<(usize, u8)>,
+    pub last_frame_sprites: Vec<(usize, u8)>,
+    pub coverage: Option<Coverage>,
+    pub sanitizer: Option<Sanitizer>,
+    pub debugger: Debugger,
+    pub random: Box<dyn RandomSource>
 }
 
 #[derive(Debug)]
 pub enum VMError {
     StackOverflow,
     StackUnderflow,
-    UnsupportedInstruction
+    UnsupportedInstruction,
+    MemoryOutOfBounds { address: usize },
+    MemoryFault { kind: FaultKind, addr: usize, pc: usize }
+}
+
+// What strict mode caught, see VM::strict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    ReservedWrite,
+    FetchOutsideProgram,
+    IndexOutOfBounds
+}
+
+impl std::fmt::Display for VMError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VMError::StackOverflow => write!(f, "stack overflow"),
+            VMError::StackUnderflow => write!(f, "return with an empty stack"),
+            VMError::UnsupportedInstruction => write!(f, "unsupported instruction"),
+            VMError::MemoryOutOfBounds { address } => write!(f, "access to {:#X} outside of memory", address),
+            VMError::MemoryFault { kind, addr, pc } => {
+                let what = match kind {
+                    FaultKind::ReservedWrite => "write to the reserved interpreter area",
+                    FaultKind::FetchOutsideProgram => "fetch from outside the loaded program",
+                    FaultKind::IndexOutOfBounds => "I-relative access beyond memory"
+                };
+                write!(f, "memory fault at PC {:03X}: {} at {:#X}", pc, what, addr)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    TooLarge { size: usize, available: usize, load_address: usize },
+    OverlapsReserved { load_address: usize, end: usize, reserved: Range<usize> },
+    Patch { path: std::path::PathBuf, reason: String }
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "could not read the ROM: {}", err),
+            LoadError::TooLarge { size, available, load_address } => write!(
+                f, "the ROM is {} bytes but only {} fit between the load address {:#X} and the end of memory",
+                size, available, load_address
+            ),
+            LoadError::OverlapsReserved { load_address, end, reserved } => write!(
+                f, "the ROM would occupy {:#X}..{:#X}, which overlaps the reserved region {:#X}..{:#X}",
+                load_address, end, reserved.start, reserved.end
+            ),
+            LoadError::Patch { path, reason } => write!(f, "could not apply the patch {}: {}", path.display(), reason)
+        }
+    }
+}
+
+impl Default for VM {
+    fn default() -> VM {
+        VM::new(Platform::default())
+    }
 }
 
 impl VM {
-    pub fn new() -> VM {
+    pub fn new(platform: Platform) -> VM {
         VM { 
-            memory: [0; MEMORY_SIZE],
-            program_counter: 0,
+            memory: vec![0; platform.memory_size],
+            program_counter: platform.entry_point,
+            platform,
             index_register: 0,
             delay_timer: 0,
             sound_timer: 0,
@@ -44,40 +127,193 @@ impl VM {
             keyboard: Keyboard::new(),
             display: Display::new(),
             shift_legacy: false,
-            chip48_mode: true
+            chip48_mode: true,
+            vf_reset: false,
+            memory_increment: false,
+            clip_sprites: false,
+            rpl_flags: [0; RPL_FLAG_COUNT],
+            rpl_flags_written: false,
+            font_address: 0,
+            strict: false,
+            program: 0..0,
+            paused: false,
+            memory_writes: Vec::new(),
+            drawn_sprites: Vec::new(),
+            last_frame_sprites: Vec::new(),
+            coverage: None,
+            sanitizer: None,
+            debugger: Debugger::new(),
+            random: Box::new(SeededRandom::from_entropy())
         }
     }
     pub fn mem_copy(&mut self, buf: &[u8], offset: usize) {
         let end = offset + buf.len();
         self.memory[offset..end].copy_from_slice(buf);
     }
-    pub fn load_program_from_file(&mut self, file_path: &std::path::Path, load_location: usize) {
-        // Read the contents of the file into a byte vector
-        let mut buffer = Vec::new();
-        if let Ok(mut file) = std::fs::File::open(file_path) {
-            if let Ok(metadata) = file.metadata() {
-                buffer.reserve_exact(metadata.len() as usize);
+    // Places the small font and the big font right after it, FX29 and FX30 follow the address.
+    pub fn load_font(&mut self, font: FontSet, address: usize) -> Result<(), VMError> {
+        let end = address.saturating_add(font::FONT_SIZE);
+        if end > self.memory.len() {
+            return Err(VMError::MemoryOutOfBounds { address: end - 1 });
+        }
+
+        self.mem_copy(font.data(), address);
+        self.mem_copy(&font::BIG_FONT_DATA, address + font::SMALL_FONT_SIZE);
+        self.font_address = address;
+        Ok(())
+    }
+    // The patches, IPS or BPS, are applied in order before anything is copied into memory.
+    pub fn load_program_from_file(&mut self, file_path: &std::path::Path, patches: &[std::path::PathBuf]) -> Result<usize, LoadError> {
+        let buffer = std::fs::read(file_path).map_err(LoadError::Io)?;
+        self.load_rom(&patch::apply_patch_files(buffer, patches)?)
+    }
+
+    // Loads a ROM at the platform load address, checking it fits the memory map first.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<usize, LoadError> {
+        let load_address = self.platform.load_address;
+        let available = self.memory.len().saturating_sub(load_address);
+        if rom.len() > available {
+            return Err(LoadError::TooLarge { size: rom.len(), available, load_address });
+        }
+
+        let end = load_address + rom.len();
+        for reserved in self.platform.reserved_regions() {
+            if load_address < reserved.end && reserved.start < end {
+                return Err(LoadError::OverlapsReserved { load_address, end, reserved });
             }
-            std::io::Read::read_to_end(&mut file, &mut buffer).ok();
-        } else {
-            panic!("Error reading file!");
         }
 
+        Ok(self.load_program(rom, load_address))
+    }
+
+    pub fn load_program(&mut self, program: &[u8], load_location: usize) -> usize {
         // Load the program into memory at the specified location
-        self.mem_copy(&buffer, load_location);
+        self.mem_copy(program, load_location);
+        self.program = load_location..load_location + program.len();
+
+        program.len()
     }
-    
-    pub fn fetch(&self) -> Result<Instruction, InstructionDecodeError> {
-        let raw_inst: u16 = (((self.memory[self.program_counter] as u16) << 8) | (self.memory[self.program_counter + 1] as u16)).into();
-        instruction::decode(raw_inst)
+
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new(self.memory.len()));
+    }
+
+    // Call after loading the font and the ROM, everything else starts out uninitialised.
+    pub fn enable_sanitizer(&mut self) {
+        let font = self.font_address..self.font_address + font::FONT_SIZE;
+        self.sanitizer = Some(Sanitizer::new(self.memory.len(), &[font, self.program.clone()]));
+    }
+
+    fn out_of_bounds(&self, address: usize) -> VMError {
+        if self.strict {
+            VMError::MemoryFault { kind: FaultKind::IndexOutOfBounds, addr: address, pc: self.program_counter }
+        } else {
+            VMError::MemoryOutOfBounds { address }
+        }
+    }
+
+    fn read_memory(&mut self, address: usize) -> Result<u8, VMError> {
+        let value = *self.memory.get(address).ok_or_else(|| self.out_of_bounds(address))?;
+        if let Some(sanitizer) = &mut self.sanitizer {
+            sanitizer.read(self.program_counter, address);
+        }
+        self.debugger.read(address);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark(address, coverage::READ);
+        }
+        Ok(value)
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), VMError> {
+        if self.strict && self.platform.is_reserved(address) {
+            return Err(VMError::MemoryFault { kind: FaultKind::ReservedWrite, addr: address, pc: self.program_counter });
+        }
+
+        if address >= self.memory.len() {
+            return Err(self.out_of_bounds(address));
+        }
+        self.memory[address] = value;
+        if let Some(sanitizer) = &mut self.sanitizer {
+            sanitizer.write(self.program_counter, address);
+        }
+        self.debugger.write(address);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark(address, coverage::WRITTEN);
+        }
+        self.memory_writes.push(address);
+        Ok(())
+    }
+
+    // A write from outside the program, e.g. by a script, seen by everything that sees the
+    // program's writes: coverage, the sanitizer and write watchpoints, which may pause the VM.
+    pub fn poke(&mut self, address: usize, value: u8) -> Result<(), VMError> {
+        self.write_memory(address, value)?;
+
+        let mut debugger = std::mem::take(&mut self.debugger);
+        if debugger.after_instruction(self, self.program_counter) {
+            self.paused = true;
+        }
+        self.debugger = debugger;
+        Ok(())
+    }
+
+    // Called once per 60Hz frame, keeps the sprites drawn during it as (address, height).
+    pub fn end_frame(&mut self) {
+        self.last_frame_sprites = std::mem::take(&mut self.drawn_sprites);
+    }
+
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
     }
     
-    pub fn tick(&mut self) -> Result<(), VMError> {
-        let instruction = self.fetch().map_err(|err| {
+    pub fn fetch_raw(&self) -> Result<u16, VMError> {
+        let high = *self.memory.get(self.program_counter).ok_or(VMError::MemoryOutOfBounds { address: self.program_counter })?;
+        let low = *self.memory.get(self.program_counter + 1).ok_or(VMError::MemoryOutOfBounds { address: self.program_counter + 1 })?;
+        Ok(((high as u16) << 8) | (low as u16))
+    }
+
+    pub fn fetch(&self) -> Result<Instruction, VMError> {
+        instruction::decode(self.fetch_raw()?).map_err(|err| {
             match err {
                 InstructionDecodeError::UnsupportedOpcode { raw_inst: _ } => VMError::UnsupportedInstruction
             }
-        })?;
+        })
+    }
+    
+    pub fn tick(&mut self) -> Result<(), VMError> {
+        self.memory_writes.clear();
+
+        // Strict mode only runs code from the loaded ROM.
+        let pc = self.program_counter;
+        if self.strict && !(self.program.contains(&pc) && self.program.contains(&(pc + 1))) {
+            return Err(VMError::MemoryFault { kind: FaultKind::FetchOutsideProgram, addr: pc, pc });
+        }
+
+        let instruction = self.fetch()?;
+
+        // Breakpoints stop before the instruction runs, the debugger is moved out so it can look at the VM.
+        let mut debugger = std::mem::take(&mut self.debugger);
+        let stop = debugger.before_instruction(self, &instruction);
+        self.debugger = debugger;
+        if stop {
+            self.paused = true;
+            return Ok(());
+        }
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark(self.program_counter, coverage::EXECUTED);
+            coverage.mark(self.program_counter + 1, coverage::EXECUTED);
+        }
+
+        if let Some(sanitizer) = &mut self.sanitizer {
+            sanitizer.before_instruction(pc, &instruction, self.chip48_mode, self.shift_legacy, self.stack.top < 0);
+        }
 
         let mut increment: usize = 2;
         
@@ -110,11 +346,20 @@ impl VM {
                 let mut pixel: i32;
 
                 self.variable_registers[0xF] = 0;
+                self.drawn_sprites.push((self.index_register as usize, height as u8));
 
                 for y in 0..height {
-                    pixel = self.memory[(self.index_register + y) as usize] as i32;
+                    pixel = self.read_memory(self.index_register as usize + y as usize)? as i32;
+
+                    if self.clip_sprites && dy + y as usize >= display::DISPLAY_HEIGHT {
+                        break;
+                    }
 
                     for x in 0..8 {
+                        if self.clip_sprites && dx + x >= display::DISPLAY_WIDTH {
+                            break;
+                        }
+
                         if pixel & (0x80 >> x) > 0 {
                             let draw_x = (dx + x) % display::DISPLAY_WIDTH;
                             let draw_y = (dy + y as usize) % display::DISPLAY_HEIGHT;
@@ -128,7 +373,7 @@ impl VM {
                 }
             },
             Instruction::SubCall { target } => {
-                self.stack.push((self.program_counter + increment).try_into().unwrap()).map_err(|err|  {
+                self.stack.push((self.program_counter + increment) as u16).map_err(|err|  {
                     match err {
                         crate::chip8::stack::StackError::StackOverflow => VMError::StackOverflow,
                         crate::chip8::stack::StackError::StackUnderflow => VMError::StackUnderflow
@@ -158,9 +403,24 @@ impl VM {
                 }
             },
             Instruction::MSetVReg { vx, vy } => self.variable_registers[vx] = self.variable_registers[vy],
-            Instruction::MSetVRegOr { vx, vy } => self.variable_registers[vx] = self.variable_registers[vx] | self.variable_registers[vy],
-            Instruction::MSetVRegAnd { vx, vy } => self.variable_registers[vx] = self.variable_registers[vx] & self.variable_registers[vy],
-            Instruction::MSetVRegXor { vx, vy } => self.variable_registers[vx] = self.variable_registers[vx] ^ self.variable_registers[vy],
+            Instruction::MSetVRegOr { vx, vy } => {
+                self.variable_registers[vx] |= self.variable_registers[vy];
+                if self.vf_reset {
+                    self.variable_registers[0xF] = 0;
+                }
+            },
+            Instruction::MSetVRegAnd { vx, vy } => {
+                self.variable_registers[vx] &= self.variable_registers[vy];
+                if self.vf_reset {
+                    self.variable_registers[0xF] = 0;
+                }
+            },
+            Instruction::MSetVRegXor { vx, vy } => {
+                self.variable_registers[vx] ^= self.variable_registers[vy];
+                if self.vf_reset {
+                    self.variable_registers[0xF] = 0;
+                }
+            },
             Instruction::MAddWithCarry { vx, vy } => {
                 if self.variable_registers[vx] > (u8::MAX - self.variable_registers[vy]) {
                     self.variable_registers[0xF] = 1;
@@ -185,13 +445,12 @@ impl VM {
                     self.variable_registers[0xF] = 0;
                 }
 
-                self.variable_registers[vx] = self.variable_registers[vy] - self.variable_registers[vx];
                 self.variable_registers[vx] = u8::wrapping_sub(self.variable_registers[vy], self.variable_registers[vx])
             },
             Instruction::MShiftRight { vx, vy } => {
                 if self.shift_legacy {
                     self.variable_registers[0xF] = self.variable_registers[vx] & 0x01;
-                    self.variable_registers[vx] = self.variable_registers[vx] >> 1;
+                    self.variable_registers[vx] >>= 1;
                 } else {
                     self.variable_registers[0xF] = self.variable_registers[vx] & 0x01;
                     self.variable_registers[vx] = self.variable_registers[vy] >> 1; 
@@ -200,7 +459,7 @@ impl VM {
             Instruction::MShiftLeft { vx, vy } => {
                 if self.shift_legacy {
                     self.variable_registers[0xF] = self.variable_registers[vx] & 0x01;
-                    self.variable_registers[vx] = self.variable_registers[vx] << 1;
+                    self.variable_registers[vx] <<= 1;
                 } else {
                     self.variable_registers[0xF] = self.variable_registers[vx] & 0x01;
                     self.variable_registers[vx] = self.variable_registers[vy] << 1; 
@@ -208,20 +467,20 @@ impl VM {
             },
             Instruction::JumpOffset { vx, offset } => {
                 if self.chip48_mode {
-                    self.program_counter = (self.variable_registers[vx] + ((offset & 0x0FFF) as u8)) as usize;
+                    self.program_counter = self.variable_registers[vx] as usize + (offset & 0x0FFF) as usize;
                 } else {
-                    self.program_counter = (self.variable_registers[0] + ((offset & 0x0FFF) as u8)) as usize;
+                    self.program_counter = self.variable_registers[0] as usize + (offset & 0x0FFF) as usize;
                 }
                 increment = 0;
             },
-            Instruction::Random { vx, nn } => self.variable_registers[vx] = rand::thread_rng().gen_range(0..=255) & nn,
+            Instruction::Random { vx, nn } => self.variable_registers[vx] = self.random.next_byte(&self.memory) & nn,
             Instruction::SkipIfKey { vx } => {
-                if self.keyboard.keys[self.variable_registers[vx] as usize] {
+                if self.keyboard.keys[(self.variable_registers[vx] & 0x0F) as usize] {
                     increment += 2;
                 }
             },
             Instruction::SkipIfNotKey { vx } => {
-                if !self.keyboard.keys[self.variable_registers[vx] as usize] {
+                if !self.keyboard.keys[(self.variable_registers[vx] & 0x0F) as usize] {
                     increment += 2;
                 }
             },
@@ -229,7 +488,7 @@ impl VM {
             Instruction::SetDelayTimerToVX { vx } => self.delay_timer = self.variable_registers[vx],
             Instruction::SetSoundTimerToVX { vx } => self.sound_timer = self.variable_registers[vx],
             Instruction::AddVXToIndexRegister { vx } => {
-                self.index_register += self.variable_registers[vx] as u16;
+                self.index_register = self.index_register.wrapping_add(self.variable_registers[vx] as u16);
 
                 if self.index_register > 0xFFF {
                     self.variable_registers[0xF] = 1;
@@ -238,13 +497,18 @@ impl VM {
                 }
             },
             Instruction::GetKeyBlock { vx } => {
-                if !self.keyboard.keys[self.variable_registers[vx] as usize] {
+                if !self.keyboard.keys[(self.variable_registers[vx] & 0x0F) as usize] {
                     increment = 0;
                 }
             },
             Instruction::FontChar { vx } => {
-                let char = self.variable_registers[vx] & 0x0F;
-                let address = char * 5;
+                let char = (self.variable_registers[vx] & 0x0F) as usize;
+                let address = self.font_address + char * 5;
+                self.index_register = address as u16;
+            },
+            Instruction::BigFontChar { vx } => {
+                let char = (self.variable_registers[vx] & 0x0F) as usize;
+                let address = self.font_address + font::SMALL_FONT_SIZE + char * 10;
                 self.index_register = address as u16;
             },
             Instruction::BinaryCodedDecimalConversion { vx } => {
@@ -256,25 +520,51 @@ impl VM {
                 value /= 10;
                 let hundreds = value % 10;
 
-                self.memory[self.index_register as usize] = hundreds;
-                self.memory[(self.index_register + 1) as usize] = tens;
-                self.memory[(self.index_register + 2) as usize] = ones;
+                self.write_memory(self.index_register as usize, hundreds)?;
+                self.write_memory(self.index_register as usize + 1, tens)?;
+                self.write_memory(self.index_register as usize + 2, ones)?;
             }
             Instruction::SaveVXToMem { vx } => {
                 for i in 0..=vx {
-                    self.memory[self.index_register as usize + i] = self.variable_registers[i];
+                    self.write_memory(self.index_register as usize + i, self.variable_registers[i])?;
+                }
+                if self.memory_increment {
+                    self.index_register = self.index_register.wrapping_add(vx as u16 + 1);
                 }
             },
             Instruction::LoadVXFromMem { vx } => {
                 for i in 0..=vx {
-                    self.variable_registers[i] = self.memory[self.index_register as usize + i];
+                    self.variable_registers[i] = self.read_memory(self.index_register as usize + i)?;
                 } 
+                if self.memory_increment {
+                    self.index_register = self.index_register.wrapping_add(vx as u16 + 1);
+                }
+            },
+            // Registers past the platform's flags are left alone.
+            Instruction::SaveVXToFlags { vx } => {
+                let count = (vx + 1).min(self.platform.rpl_flags);
+                self.rpl_flags[..count].copy_from_slice(&self.variable_registers[..count]);
+                self.rpl_flags_written = true;
+            },
+            Instruction::LoadVXFromFlags { vx } => {
+                let count = (vx + 1).min(self.platform.rpl_flags);
+                self.variable_registers[..count].copy_from_slice(&self.rpl_flags[..count]);
             }
         }
 
         self.program_counter += increment;
 
-        return Ok(());
+        if let Some(sanitizer) = &mut self.sanitizer {
+            sanitizer.after_instruction(pc, &instruction, self.program_counter, &self.memory);
+        }
+
+        let mut debugger = std::mem::take(&mut self.debugger);
+        if debugger.after_instruction(self, pc) {
+            self.paused = true;
+        }
+        self.debugger = debugger;
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use crate::{
+    chip8::{extension, Extension, Instruction, Platform, Quirks},
+    lint::ControlFlowGraph
+};
+
+const EMBEDDED_DATABASE: &str = include_str!("known_roms.txt");
+
+// Memory the detector lays a ROM out in, big enough for anything an XO-CHIP ROM could be.
+const SCAN_MEMORY_SIZE: usize = 0x10000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownRom {
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub title: String
+}
+
+// Exact settings for known ROMs by SHA-1, from the list built into the binary plus the user's own.
+#[derive(Default)]
+pub struct Database {
+    entries: HashMap<String, KnownRom>
+}
+
+impl Database {
+    pub fn embedded() -> Database {
+        let mut database = Database::default();
+        database.add(EMBEDDED_DATABASE).expect("the embedded ROM database is valid");
+        database
+    }
+
+    // Adds the entries in `text`, later entries win so user lists override the embedded one.
+    pub fn add(&mut self, text: &str) -> Result<(), String> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.splitn(4, char::is_whitespace);
+            let (Some(hash), Some(platform), Some(quirks)) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(format!("line {}: expected <sha1> <platform> <quirks> <title>", number + 1));
+            };
+            let platform = Platform::from_name(platform).ok_or(format!("line {}: unknown platform {}", number + 1, platform))?;
+            let quirks = Quirks::from_name(quirks).ok_or(format!("line {}: unknown quirks {}", number + 1, quirks))?;
+            let title = fields.next().unwrap_or("").trim().to_string();
+
+            self.entries.insert(hash.to_ascii_lowercase(), KnownRom { platform, quirks, title });
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, hash: &str) -> Option<&KnownRom> {
+        self.entries.get(&hash.to_ascii_lowercase())
+    }
+}
+
+// What the launcher should use, `None` where nothing pointed either way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub title: Option<String>,
+    pub reasons: Vec<String>
+}
+
+// Guesses from the opcodes reachable from the load address. Extension opcodes
+// settle it, otherwise an 8XY6/8XYE reading a second register points at the VIP.
+// Shifts naming V0 as the source say nothing, plenty of VIP programs write them too.
+pub fn guess(rom: &[u8], load_address: usize) -> Detection {
+    let mut memory = vec![0; SCAN_MEMORY_SIZE.max(load_address + rom.len())];
+    memory[load_address..load_address + rom.len()].copy_from_slice(rom);
+    let cfg = ControlFlowGraph::build(&memory, load_address);
+
+    let mut detection = Detection { platform: None, quirks: None, title: None, reasons: Vec::new() };
+    let mut found: Option<(Extension, usize, u16)> = None;
+    let mut shifts_from_vy = None;
+    let mut jumps = None;
+
+    for (address, node) in &cfg.nodes {
+        if let Some((extension, _)) = extension(node.raw_inst) {
+            // XO-CHIP is a superset, one of its opcodes outranks any number of SCHIP ones.
+            if found.is_none_or(|(previous, _, _)| previous == Extension::SuperChip && extension == Extension::XoChip) {
+                found = Some((extension, *address, node.raw_inst));
+            }
+        }
+        match node.instruction {
+            Some(Instruction::MShiftRight { vx, vy }) | Some(Instruction::MShiftLeft { vx, vy }) if vx != vy && vy != 0 => {
+                shifts_from_vy.get_or_insert(*address);
+            },
+            Some(Instruction::JumpOffset { offset, .. }) if offset & 0xF00 != 0 => {
+                jumps.get_or_insert(*address);
+            },
+            _ => ()
+        }
+    }
+
+    match found {
+        Some((Extension::XoChip, address, raw_inst)) => {
+            detection.platform = Some(Platform::xo_chip());
+            detection.quirks = Some(Quirks::xo_chip());
+            detection.reasons.push(format!("XO-CHIP opcode {:04X} at {:03X}", raw_inst, address));
+        },
+        Some((Extension::SuperChip, address, raw_inst)) => {
+            detection.quirks = Some(Quirks::schip());
+            detection.reasons.push(format!("SCHIP opcode {:04X} at {:03X}", raw_inst, address));
+        },
+        None => {
+            if let Some(address) = shifts_from_vy {
+                detection.quirks = Some(Quirks::vip());
+                detection.reasons.push(format!("shift at {:03X} reads a second register, as on the VIP", address));
+            }
+        }
+    }
+
+    if let (Some(address), Some(quirks)) = (jumps, detection.quirks) {
+        detection.reasons.push(format!("BXNN at {:03X} follows the {} jump quirk", address, quirks.name()));
+    }
+
+    if detection.platform.is_none() && rom.len() > Platform::chip8().program_space() {
+        detection.platform = Some(Platform::xo_chip());
+        detection.reasons.push(format!("{} bytes do not fit in 4K", rom.len()));
+    }
+
+    detection
+}
+
+// The database when it knows the ROM, the heuristics otherwise.
+pub fn detect(rom: &[u8], hash: &str, load_address: usize, database: &Database) -> Detection {
+    match database.lookup(hash) {
+        Some(known) => Detection {
+            platform: Some(known.platform.clone()),
+            quirks: Some(known.quirks),
+            title: Some(known.title.clone()).filter(|title| !title.is_empty()),
+            reasons: vec!["known ROM".to_string()]
+        },
+        None => guess(rom, load_address)
+    }
+}
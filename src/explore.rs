@@ -0,0 +1,212 @@
+use std::fmt;
+
+use crate::chip8::{Instruction, Quirks, VM};
+
+// One configuration under comparison. The VMs should be set up identically
+// apart from the quirks, including the random seed.
+pub struct Machine {
+    pub name: String,
+    pub quirks: Quirks,
+    pub vm: VM,
+    pub error: Option<String>
+}
+
+impl Machine {
+    pub fn new(name: &str, quirks: Quirks, mut vm: VM) -> Machine {
+        quirks.apply(&mut vm);
+        Machine { name: name.to_string(), quirks, vm, error: None }
+    }
+}
+
+// Where the first machine that disagreed with the first one split off.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub frame: u64,
+    // Instructions executed before the one that diverged, counted from the start.
+    pub instruction: u64,
+    pub pc: usize,
+    pub raw_inst: Option<u16>,
+    pub disassembly: String,
+    // Indices of the machines that differ from the first.
+    pub machines: Vec<usize>,
+    pub fields: Vec<String>,
+    // Quirks that told the machines apart on this instruction, empty when none of them applies to it.
+    pub quirks: Vec<&'static str>
+}
+
+// Runs every machine one instruction at a time on the same input and compares
+// their whole state after each, so the divergence points at a single instruction.
+pub struct Explorer {
+    pub machines: Vec<Machine>,
+    pub frame: u64,
+    pub instructions: u64,
+    pub divergence: Option<Divergence>
+}
+
+// The observable state that differs between two machines, by name.
+fn differences(left: &VM, right: &VM) -> Vec<String> {
+    let mut fields = Vec::new();
+
+    if left.program_counter != right.program_counter {
+        fields.push("PC".to_string());
+    }
+    if left.index_register != right.index_register {
+        fields.push("I".to_string());
+    }
+    for (index, (l, r)) in left.variable_registers.iter().zip(right.variable_registers.iter()).enumerate() {
+        if l != r {
+            fields.push(format!("V{:X}", index));
+        }
+    }
+    if left.stack.top != right.stack.top || left.stack.data != right.stack.data {
+        fields.push("stack".to_string());
+    }
+    if left.delay_timer != right.delay_timer {
+        fields.push("DT".to_string());
+    }
+    if left.sound_timer != right.sound_timer {
+        fields.push("ST".to_string());
+    }
+    if let Some(address) = left.memory.iter().zip(right.memory.iter()).position(|(l, r)| l != r) {
+        fields.push(format!("memory at {:03X}", address));
+    }
+    if left.display != right.display {
+        fields.push("display".to_string());
+    }
+
+    fields
+}
+
+impl Explorer {
+    pub fn new(machines: Vec<Machine>) -> Explorer {
+        Explorer { machines, frame: 0, instructions: 0, divergence: None }
+    }
+
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        for machine in &mut self.machines {
+            machine.vm.keyboard.keys = keys;
+        }
+    }
+
+    // True once every machine stopped on an error.
+    pub fn halted(&self) -> bool {
+        self.machines.iter().all(|machine| machine.error.is_some())
+    }
+
+    // One instruction on every machine that is still running.
+    pub fn step(&mut self) {
+        let pc = self.machines[0].vm.program_counter;
+        let raw_inst = self.machines[0].vm.fetch_raw().ok();
+        let instruction = self.machines[0].vm.fetch().ok();
+
+        for machine in &mut self.machines {
+            if machine.error.is_some() {
+                continue;
+            }
+            if let Err(err) = machine.vm.tick() {
+                machine.error = Some(err.to_string());
+            }
+        }
+
+        if self.divergence.is_none() {
+            self.compare(pc, raw_inst, instruction.as_ref());
+        }
+        self.instructions += 1;
+    }
+
+    fn compare(&mut self, pc: usize, raw_inst: Option<u16>, instruction: Option<&Instruction>) {
+        let (first, others) = self.machines.split_first().unwrap();
+        let mut machines = Vec::new();
+        let mut fields = Vec::new();
+        let mut quirks = Vec::new();
+
+        for (index, other) in others.iter().enumerate() {
+            let mut differing = differences(&first.vm, &other.vm);
+            if first.error != other.error {
+                differing.insert(0, "error".to_string());
+            }
+            if differing.is_empty() {
+                continue;
+            }
+
+            if fields.is_empty() {
+                fields = differing;
+            }
+            machines.push(index + 1);
+
+            let sensitive = instruction.map_or(&[][..], Quirks::affecting);
+            for quirk in first.quirks.differences(&other.quirks) {
+                if sensitive.contains(&quirk) && !quirks.contains(&quirk) {
+                    quirks.push(quirk);
+                }
+            }
+        }
+
+        if !machines.is_empty() {
+            self.divergence = Some(Divergence {
+                frame: self.frame,
+                instruction: self.instructions,
+                pc,
+                raw_inst,
+                disassembly: instruction.map_or("???".to_string(), |instruction| instruction.to_string()),
+                machines,
+                fields,
+                quirks
+            });
+        }
+    }
+
+    // One 60Hz frame: the instruction budget on every machine, then their timers.
+    pub fn run_frame(&mut self, instructions_per_frame: u32) {
+        for _ in 0..instructions_per_frame {
+            if self.halted() {
+                break;
+            }
+            self.step();
+        }
+
+        for machine in &mut self.machines {
+            if machine.error.is_none() {
+                machine.vm.tick_timers();
+                machine.vm.end_frame();
+            }
+        }
+        self.frame += 1;
+    }
+
+    pub fn report(&self) -> String {
+        let names: Vec<String> = self.machines.iter().map(|machine| format!("{} ({})", machine.name, machine.quirks.name())).collect();
+        let mut report = format!("Compared {} over {} frames, {} instructions\n", names.join(", "), self.frame, self.instructions);
+
+        match &self.divergence {
+            Some(divergence) => report.push_str(&divergence.describe(&self.machines)),
+            None => report.push_str("No divergence\n")
+        }
+        report
+    }
+}
+
+impl Divergence {
+    pub fn describe(&self, machines: &[Machine]) -> String {
+        let opcode = self.raw_inst.map_or("????".to_string(), |raw_inst| format!("{:04X}", raw_inst));
+        let names: Vec<&str> = self.machines.iter().map(|&index| machines[index].name.as_str()).collect();
+        let mut text = format!(
+            "Diverged in frame {} at instruction {}: {:03X} {} {}\n{} differs from {} in {}\n",
+            self.frame, self.instruction, self.pc, opcode, self.disassembly,
+            names.join(", "), machines[0].name, self.fields.join(", ")
+        );
+
+        if self.quirks.is_empty() {
+            text.push_str("No quirk applies to this instruction\n");
+        } else {
+            text.push_str(&format!("Caused by {}\n", self.quirks.join(", ")));
+        }
+        text
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "frame {} instruction {} at {:03X}: {}", self.frame, self.instruction, self.pc, self.fields.join(", "))
+    }
+}
@@ -0,0 +1,21 @@
+use crate::chip8::{Display, Keyboard};
+
+use super::{VideoSink, AudioSink, InputSource};
+
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn present(&mut self, _display: &Display) {}
+}
+
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn set_tone(&mut self, _playing: bool) {}
+}
+
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn poll(&mut self, _keyboard: &mut Keyboard) {}
+}
@@ -0,0 +1,169 @@
+use macroquad::{prelude::*, audio::{self, Sound, PlaySoundParams}};
+
+use crate::{chip8::{self, Display, Keyboard}, settings::Keymap};
+
+use super::{VideoSink, AudioSink, InputSource};
+
+const SCREEN_MARGIN: usize = 15;
+const PIXEL_MARGIN: usize = 0;
+
+const TONE_SAMPLE_RATE: u32 = 44100;
+const TONE_FREQUENCY: u32 = 440;
+
+pub struct MacroquadVideo {
+    pub on: Color,
+    pub off: Color
+}
+
+impl MacroquadVideo {
+    pub fn new() -> MacroquadVideo {
+        MacroquadVideo { on: color_u8!(200,200,200,255), off: color_u8!(20,20,20,255) }
+    }
+
+    pub fn with_palette(off: [u8; 3], on: [u8; 3]) -> MacroquadVideo {
+        MacroquadVideo { on: color_u8!(on[0], on[1], on[2], 255), off: color_u8!(off[0], off[1], off[2], 255) }
+    }
+}
+
+impl Default for MacroquadVideo {
+    fn default() -> MacroquadVideo {
+        MacroquadVideo::new()
+    }
+}
+
+impl MacroquadVideo {
+    // Draws the display scaled into the given rectangle, for layouts with more than one screen.
+    pub fn draw(&self, display: &Display, left: f32, top: f32, width: f32, height: f32) {
+        for y in 0..chip8::DISPLAY_HEIGHT {
+            for x in 0..chip8::DISPLAY_WIDTH {
+                let color = if display.get(x, y) { self.on } else { self.off };
+
+                let pixel_x = left + PIXEL_MARGIN as f32 + (width / chip8::DISPLAY_WIDTH as f32) * x as f32;
+                let pixel_y = top + PIXEL_MARGIN as f32 + (height / chip8::DISPLAY_HEIGHT as f32) * y as f32;
+                let pixel_w = width / chip8::DISPLAY_WIDTH as f32 - 2f32 * PIXEL_MARGIN as f32;
+                let pixel_h = height / chip8::DISPLAY_HEIGHT as f32 - 2f32 * PIXEL_MARGIN as f32;
+                draw_rectangle(pixel_x, pixel_y, pixel_w, pixel_h, color)
+            }
+        }
+    }
+}
+
+impl VideoSink for MacroquadVideo {
+    fn present(&mut self, display: &Display) {
+        let display_width: f32;
+        let display_height: f32;
+
+        let aspect_ratio: f32 = chip8::DISPLAY_WIDTH as f32 / chip8::DISPLAY_HEIGHT as f32;
+
+        if screen_width() / screen_height() > aspect_ratio {
+            display_width = screen_height() * aspect_ratio;
+            display_height = screen_height();
+        } else {
+            display_width = screen_width();
+            display_height = screen_width() / aspect_ratio;
+        }
+
+        let left_margin = (screen_width() - display_width) / 2.0f32 - SCREEN_MARGIN as f32;
+        let top_margin = (screen_height() - display_height) / 2.0f32 - SCREEN_MARGIN as f32;
+
+        self.draw(display, left_margin + SCREEN_MARGIN as f32, top_margin + SCREEN_MARGIN as f32, display_width, display_height);
+    }
+}
+
+pub struct MacroquadAudio {
+    sound: Option<Sound>,
+    playing: bool
+}
+
+// One second of an 8-bit square wave, looped while the sound timer runs.
+fn square_wave() -> Vec<u8> {
+    let samples = TONE_SAMPLE_RATE;
+    let mut wav = Vec::with_capacity(44 + samples as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + samples).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&TONE_SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&TONE_SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&8u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&samples.to_le_bytes());
+
+    let period = TONE_SAMPLE_RATE / TONE_FREQUENCY;
+    for sample in 0..samples {
+        wav.push(if sample % period < period / 2 { 0xA0 } else { 0x60 });
+    }
+
+    wav
+}
+
+impl MacroquadAudio {
+    pub async fn load() -> MacroquadAudio {
+        let sound = audio::load_sound_from_bytes(&square_wave()).await.ok();
+        MacroquadAudio { sound, playing: false }
+    }
+}
+
+impl AudioSink for MacroquadAudio {
+    fn set_tone(&mut self, playing: bool) {
+        if playing == self.playing {
+            return;
+        }
+        self.playing = playing;
+
+        if let Some(sound) = self.sound {
+            if playing {
+                audio::play_sound(sound, PlaySoundParams { looped: true, volume: 0.3 });
+            } else {
+                audio::stop_sound(sound);
+            }
+        }
+    }
+}
+
+fn key_code(key: char) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I,
+        KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+        KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+        KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9
+    ];
+
+    match key.to_ascii_uppercase() {
+        letter @ 'A'..='Z' => Some(LETTERS[letter as usize - 'A' as usize]),
+        digit @ '0'..='9' => Some(DIGITS[digit as usize - '0' as usize]),
+        _ => None
+    }
+}
+
+// Reads the keys of a Keymap, by default the hex keypad layout on the left of a QWERTY keyboard.
+pub struct MacroquadInput {
+    keys: [Option<KeyCode>; 16]
+}
+
+impl MacroquadInput {
+    pub fn new(keymap: &Keymap) -> MacroquadInput {
+        MacroquadInput { keys: keymap.0.map(key_code) }
+    }
+}
+
+impl Default for MacroquadInput {
+    fn default() -> MacroquadInput {
+        MacroquadInput::new(&Keymap::default())
+    }
+}
+
+impl InputSource for MacroquadInput {
+    fn poll(&mut self, keyboard: &mut Keyboard) {
+        for (key, code) in self.keys.iter().enumerate() {
+            keyboard.keys[key] = code.is_some_and(is_key_down);
+        }
+    }
+}
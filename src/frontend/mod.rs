@@ -0,0 +1,81 @@
+pub mod headless;
+pub mod terminal;
+pub mod macroquad;
+pub mod test;
+
+use std::time::{Duration, Instant};
+
+use crate::chip8::{Display, Keyboard};
+
+pub const FRAME_RATE: u32 = 60;
+
+// The pieces a frontend plugs into the Runner. The run loop itself stays in
+// Runner::update, frontends only decide when to call it.
+pub trait VideoSink {
+    fn present(&mut self, display: &Display);
+}
+
+pub trait AudioSink {
+    fn set_tone(&mut self, playing: bool);
+}
+
+pub trait InputSource {
+    fn poll(&mut self, keyboard: &mut Keyboard);
+}
+
+pub trait Clock {
+    // How many 60Hz frames should be emulated since the last call.
+    fn frames_due(&mut self) -> u32;
+}
+
+// Emulates exactly one frame per update, for headless runs and tests.
+pub struct FixedClock;
+
+impl Clock for FixedClock {
+    fn frames_due(&mut self) -> u32 {
+        1
+    }
+}
+
+// Keeps emulation at 60Hz regardless of how often the frontend updates,
+// catching up at most MAX_CATCH_UP frames after a stall.
+pub struct RealTimeClock {
+    last: Instant,
+    pending: Duration
+}
+
+const MAX_CATCH_UP: u32 = 4;
+
+impl RealTimeClock {
+    pub fn new() -> RealTimeClock {
+        RealTimeClock { last: Instant::now(), pending: Duration::ZERO }
+    }
+}
+
+impl Default for RealTimeClock {
+    fn default() -> RealTimeClock {
+        RealTimeClock::new()
+    }
+}
+
+impl Clock for RealTimeClock {
+    fn frames_due(&mut self) -> u32 {
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        let now = Instant::now();
+        self.pending += now - self.last;
+        self.last = now;
+
+        let mut frames = 0;
+        while self.pending >= frame {
+            self.pending -= frame;
+            frames += 1;
+        }
+
+        if frames > MAX_CATCH_UP {
+            self.pending = Duration::ZERO;
+            frames = MAX_CATCH_UP;
+        }
+
+        frames
+    }
+}
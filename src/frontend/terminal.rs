@@ -0,0 +1,145 @@
+use std::{io::{self, Write, Stdout}, time::Duration};
+
+use crossterm::{
+    cursor, execute, queue, terminal,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers}
+};
+
+use crate::{chip8::{self, Display, Keyboard}, runner::Runner, settings::Keymap};
+
+use super::{VideoSink, AudioSink, InputSource, RealTimeClock, FRAME_RATE};
+
+// Terminals only report presses, so a key counts as held for a few frames
+// after each press. Auto-repeat keeps it held for as long as it is down.
+const KEY_HOLD_FRAMES: u8 = 6;
+
+// Two pixel rows per line using half block characters.
+pub struct TerminalVideo {
+    out: Stdout
+}
+
+impl VideoSink for TerminalVideo {
+    fn present(&mut self, display: &Display) {
+        let mut screen = String::with_capacity(chip8::DISPLAY_WIDTH * chip8::DISPLAY_HEIGHT);
+
+        for y in (0..chip8::DISPLAY_HEIGHT).step_by(2) {
+            for x in 0..chip8::DISPLAY_WIDTH {
+                screen.push(match (display.get(x, y), display.get(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' '
+                });
+            }
+            screen.push_str("\r\n");
+        }
+
+        // A closed terminal is noticed by the input side, nothing to do here.
+        let _ = queue!(self.out, cursor::MoveTo(0, 0));
+        let _ = self.out.write_all(screen.as_bytes());
+        let _ = self.out.flush();
+    }
+}
+
+// Rings the terminal bell whenever the sound timer starts.
+pub struct TerminalAudio {
+    playing: bool
+}
+
+impl AudioSink for TerminalAudio {
+    fn set_tone(&mut self, playing: bool) {
+        if playing && !self.playing {
+            let mut out = io::stdout();
+            let _ = out.write_all(b"\x07");
+            let _ = out.flush();
+        }
+        self.playing = playing;
+    }
+}
+
+pub struct TerminalInput {
+    keys: [char; 16],
+    held: [u8; 16],
+    quit: bool
+}
+
+impl TerminalInput {
+    pub fn new(keymap: &Keymap) -> TerminalInput {
+        TerminalInput { keys: keymap.0, held: [0; 16], quit: false }
+    }
+
+    // Holds the CHIP-8 key the character is mapped to, letters in either case.
+    pub fn press(&mut self, c: char) {
+        let c = c.to_ascii_uppercase();
+        if let Some(key) = self.keys.iter().position(|k| *k == c) {
+            self.held[key] = KEY_HOLD_FRAMES;
+        }
+    }
+
+    // Drains pending terminal events, Esc or Ctrl-C ask to quit.
+    fn pump(&mut self) -> io::Result<()> {
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? {
+                if kind == KeyEventKind::Release {
+                    continue;
+                }
+
+                match code {
+                    KeyCode::Esc => self.quit = true,
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+                    KeyCode::Char(c) => self.press(c),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InputSource for TerminalInput {
+    fn poll(&mut self, keyboard: &mut Keyboard) {
+        for (key, held) in self.held.iter_mut().enumerate() {
+            keyboard.keys[key] = *held > 0;
+            *held = held.saturating_sub(1);
+        }
+    }
+}
+
+// Runs until Esc or Ctrl-C, restoring the terminal on the way out.
+pub fn run(runner: &mut Runner, keymap: &Keymap) -> io::Result<()> {
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+
+    let result = run_loop(runner, keymap);
+
+    execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn run_loop(runner: &mut Runner, keymap: &Keymap) -> io::Result<()> {
+    let mut video = TerminalVideo { out: io::stdout() };
+    let mut audio = TerminalAudio { playing: false };
+    let mut input = TerminalInput::new(keymap);
+    let mut clock = RealTimeClock::new();
+
+    loop {
+        input.pump()?;
+        if input.quit {
+            return Ok(());
+        }
+
+        // Errors pause the VM and were already reported, keep showing the screen.
+        let _ = runner.update(&mut video, &mut audio, &mut input, &mut clock);
+
+        // The latest breakpoint or logpoint message goes on the line under the screen.
+        if let Some(message) = runner.vm.lock().unwrap().debugger.take_messages().pop() {
+            queue!(video.out, cursor::MoveTo(0, (chip8::DISPLAY_HEIGHT / 2) as u16), terminal::Clear(terminal::ClearType::CurrentLine))?;
+            video.out.write_all(message.as_bytes())?;
+            video.out.flush()?;
+        }
+
+        std::thread::sleep(Duration::from_secs(1) / FRAME_RATE);
+    }
+}
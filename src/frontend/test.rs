@@ -0,0 +1,46 @@
+use crate::chip8::{Display, Keyboard};
+
+use super::{VideoSink, AudioSink, InputSource};
+
+// Keeps every presented frame so tests can compare the screen.
+#[derive(Default)]
+pub struct RecordingVideo {
+    pub frames: Vec<Display>
+}
+
+impl VideoSink for RecordingVideo {
+    fn present(&mut self, display: &Display) {
+        self.frames.push(display.clone());
+    }
+}
+
+#[derive(Default)]
+pub struct RecordingAudio {
+    pub tones: Vec<bool>
+}
+
+impl AudioSink for RecordingAudio {
+    fn set_tone(&mut self, playing: bool) {
+        self.tones.push(playing);
+    }
+}
+
+// Feeds a fixed key state per frame, then releases everything once the script runs out.
+#[derive(Default)]
+pub struct ScriptedInput {
+    pub frames: Vec<[bool; 16]>,
+    position: usize
+}
+
+impl ScriptedInput {
+    pub fn new(frames: Vec<[bool; 16]>) -> ScriptedInput {
+        ScriptedInput { frames, position: 0 }
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self, keyboard: &mut Keyboard) {
+        keyboard.keys = self.frames.get(self.position).copied().unwrap_or([false; 16]);
+        self.position += 1;
+    }
+}
@@ -0,0 +1,80 @@
+// The state behind the memory view: which bytes changed between frames and
+// the byte being typed over in hex, kept apart from the drawing.
+pub const BYTES_PER_ROW: usize = 16;
+
+// Rows needed for the whole memory, the last one may be short.
+pub fn row_count(memory: &[u8]) -> usize {
+    memory.len().div_ceil(BYTES_PER_ROW)
+}
+
+// The bytes on the row starting at `base`, fewer at the end of memory and none past it.
+pub fn row(memory: &[u8], base: usize) -> &[u8] {
+    let start = base.min(memory.len());
+    &memory[start..(base + BYTES_PER_ROW).min(memory.len())]
+}
+
+#[derive(Default)]
+pub struct Changes {
+    previous: Vec<u8>,
+    changed: Vec<bool>
+}
+
+impl Changes {
+    // Compares with the memory at the end of the previous frame.
+    pub fn end_frame(&mut self, memory: &[u8]) {
+        if self.previous.len() != memory.len() {
+            self.previous = memory.to_vec();
+            self.changed = vec![false; memory.len()];
+        }
+
+        for (address, value) in memory.iter().enumerate() {
+            self.changed[address] = self.previous[address] != *value;
+        }
+        self.previous.copy_from_slice(memory);
+    }
+
+    pub fn changed(&self, address: usize) -> bool {
+        self.changed.get(address).copied().unwrap_or(false)
+    }
+
+    // An edit by hand is not a change the program made.
+    pub fn edited(&mut self, address: usize, value: u8) {
+        if let Some(previous) = self.previous.get_mut(address) {
+            *previous = value;
+        }
+    }
+}
+
+// Two hex digits typed over the selected byte, which then moves on to the next one.
+#[derive(Default)]
+pub struct HexEditor {
+    pub selected: Option<usize>,
+    pub pending_nibble: Option<u8>
+}
+
+impl HexEditor {
+    pub fn select(&mut self, address: Option<usize>) {
+        self.selected = address;
+        self.pending_nibble = None;
+    }
+
+    // Returns the address written once the second digit is in.
+    pub fn type_char(&mut self, c: char, memory: &mut [u8]) -> Option<usize> {
+        let selected = self.selected.filter(|selected| *selected < memory.len())?;
+        let nibble = c.to_digit(16)? as u8;
+
+        match self.pending_nibble.take() {
+            None => {
+                self.pending_nibble = Some(nibble);
+                None
+            },
+            Some(high) => {
+                memory[selected] = (high << 4) | nibble;
+                if selected + 1 < memory.len() {
+                    self.selected = Some(selected + 1);
+                }
+                Some(selected)
+            }
+        }
+    }
+}
@@ -0,0 +1,18 @@
+pub mod chip8;
+pub mod frontend;
+pub mod runner;
+pub mod scripting;
+pub mod trace;
+pub mod profile;
+pub mod capture;
+pub mod speed;
+pub mod repl;
+pub mod lint;
+pub mod detect;
+pub mod explore;
+pub mod settings;
+pub mod cartridge;
+pub mod octo;
+pub mod storage;
+pub mod hex_edit;
+pub mod cheats;
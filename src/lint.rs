@@ -0,0 +1,342 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, ops::Range};
+
+use crate::chip8::{self, Instruction, STACK_SIZE};
+
+// How far the FX55/FX65 check follows the code looking for the next use of I.
+const INDEX_USE_DISTANCE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    InvalidOpcode,
+    TargetOutsideMemory,
+    OutsideProgram,
+    OddTarget,
+    UnbalancedReturn,
+    MissingReturn,
+    Recursion,
+    StackDepth,
+    IndirectJump,
+    Quirk
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub address: usize,
+    pub severity: Severity,
+    pub kind: FindingKind,
+    pub message: String
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info"
+        };
+        write!(f, "{:03X} {}: {}", self.address, severity, self.message)
+    }
+}
+
+pub struct Node {
+    pub raw_inst: u16,
+    pub instruction: Option<Instruction>,
+    // Where execution continues in the same routine, calls fall through to the return address.
+    pub successors: Vec<usize>,
+    pub call: Option<usize>
+}
+
+// Every instruction reachable from the entry point by following jumps, skips
+// and calls. BNNN targets depend on a register and are not followed.
+pub struct ControlFlowGraph {
+    pub entry_point: usize,
+    pub nodes: BTreeMap<usize, Node>
+}
+
+fn fetch(memory: &[u8], address: usize) -> Option<u16> {
+    match (memory.get(address), memory.get(address + 1)) {
+        (Some(high), Some(low)) => Some(((*high as u16) << 8) | *low as u16),
+        _ => None
+    }
+}
+
+fn successors(address: usize, instruction: &Instruction) -> (Vec<usize>, Option<usize>) {
+    match *instruction {
+        Instruction::Jump { target } => (vec![target as usize], None),
+        Instruction::SubCall { target } => (vec![address + 2], Some(target as usize)),
+        Instruction::SubReturn | Instruction::JumpOffset { .. } => (Vec::new(), None),
+        Instruction::SkipEq { .. } | Instruction::SkipNotEq { .. } | Instruction::SkipVEq { .. } |
+        Instruction::SkipVNotEq { .. } | Instruction::SkipIfKey { .. } | Instruction::SkipIfNotKey { .. } => (vec![address + 2, address + 4], None),
+        _ => (vec![address + 2], None)
+    }
+}
+
+impl ControlFlowGraph {
+    pub fn build(memory: &[u8], entry_point: usize) -> ControlFlowGraph {
+        let mut nodes = BTreeMap::new();
+        let mut pending = vec![entry_point];
+
+        while let Some(address) = pending.pop() {
+            if nodes.contains_key(&address) {
+                continue;
+            }
+            let Some(raw_inst) = fetch(memory, address) else {
+                continue;
+            };
+
+            let instruction = chip8::decode(raw_inst).ok();
+            // SCHIP and XO-CHIP opcodes don't run here, but following them keeps the rest of the code in the graph.
+            let (successors, call) = match (&instruction, chip8::extension(raw_inst)) {
+                (Some(instruction), _) => successors(address, instruction),
+                (None, Some(_)) if raw_inst == 0x00FD => (Vec::new(), None),
+                (None, Some((_, length))) => (vec![address + length], None),
+                (None, None) => (Vec::new(), None)
+            };
+            pending.extend(successors.iter().chain(call.iter()));
+            nodes.insert(address, Node { raw_inst, instruction, successors, call });
+        }
+
+        ControlFlowGraph { entry_point, nodes }
+    }
+
+    // The entry point and every call target, each with the addresses reachable without returning.
+    pub fn routines(&self) -> BTreeMap<usize, BTreeSet<usize>> {
+        let mut starts: BTreeSet<usize> = self.nodes.values().filter_map(|node| node.call).collect();
+        starts.insert(self.entry_point);
+
+        starts.into_iter().map(|start| {
+            let mut body = BTreeSet::new();
+            let mut pending = vec![start];
+            while let Some(address) = pending.pop() {
+                if let Some(node) = self.nodes.get(&address) {
+                    if body.insert(address) {
+                        pending.extend(&node.successors);
+                    }
+                }
+            }
+            (start, body)
+        }).collect()
+    }
+}
+
+fn routine_name(routine: usize, entry_point: usize) -> String {
+    if routine == entry_point {
+        "main".to_string()
+    } else {
+        format!("sub {:03X}", routine)
+    }
+}
+
+fn uses_index(instruction: &Instruction) -> Option<bool> {
+    match instruction {
+        Instruction::Draw { .. } | Instruction::SaveVXToMem { .. } | Instruction::LoadVXFromMem { .. } |
+        Instruction::BinaryCodedDecimalConversion { .. } | Instruction::AddVXToIndexRegister { .. } => Some(true),
+        Instruction::SetIR { .. } | Instruction::FontChar { .. } | Instruction::BigFontChar { .. } => Some(false),
+        _ => None
+    }
+}
+
+// Finds an instruction that uses I before anything sets it again, following the code after `address`.
+fn next_index_use(cfg: &ControlFlowGraph, address: usize) -> Option<usize> {
+    let mut pending: Vec<(usize, usize)> = cfg.nodes[&address].successors.iter().map(|next| (*next, 1)).collect();
+    let mut seen = BTreeSet::new();
+
+    while let Some((address, distance)) = pending.pop() {
+        if distance > INDEX_USE_DISTANCE || !seen.insert(address) {
+            continue;
+        }
+        let Some(node) = cfg.nodes.get(&address) else {
+            continue;
+        };
+        match node.instruction.as_ref().and_then(uses_index) {
+            Some(true) => return Some(address),
+            Some(false) => continue,
+            None => pending.extend(node.successors.iter().map(|next| (*next, distance + 1)))
+        }
+    }
+    None
+}
+
+struct Linter<'a> {
+    cfg: &'a ControlFlowGraph,
+    memory_size: usize,
+    program: Range<usize>,
+    findings: Vec<Finding>
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, address: usize, severity: Severity, kind: FindingKind, message: String) {
+        self.findings.push(Finding { address, severity, kind, message });
+    }
+
+    fn in_program(&self, address: usize) -> bool {
+        self.program.contains(&address) && self.program.contains(&(address + 1))
+    }
+
+    fn targets(&mut self, address: usize, node: &Node) {
+        let explicit = match node.instruction {
+            Some(Instruction::Jump { target }) | Some(Instruction::SubCall { target }) => Some(target as usize),
+            _ => None
+        };
+
+        if let Some(target) = explicit {
+            let what = if node.call.is_some() { "call" } else { "jump" };
+            if target + 1 >= self.memory_size {
+                self.report(address, Severity::Error, FindingKind::TargetOutsideMemory, format!("{} to {:03X} is outside of memory", what, target));
+            } else if !self.in_program(target) {
+                self.report(address, Severity::Warning, FindingKind::OutsideProgram, format!("{} to {:03X} is outside of the loaded ROM", what, target));
+            }
+            if target % 2 != 0 {
+                self.report(address, Severity::Warning, FindingKind::OddTarget, format!("{} to the odd address {:03X}", what, target));
+            }
+            if node.call.is_none() {
+                return;
+            }
+        }
+
+        for next in &node.successors {
+            if *next + 1 >= self.memory_size {
+                self.report(address, Severity::Error, FindingKind::TargetOutsideMemory, "execution continues past the end of memory".to_string());
+            } else if !self.in_program(*next) {
+                self.report(address, Severity::Warning, FindingKind::OutsideProgram, format!("execution continues at {:03X}, past the end of the loaded ROM", next));
+            }
+        }
+    }
+
+    fn quirks(&mut self, address: usize, instruction: &Instruction) {
+        match *instruction {
+            Instruction::MShiftRight { vx, vy } | Instruction::MShiftLeft { vx, vy } if vx != vy => self.report(
+                address, Severity::Info, FindingKind::Quirk,
+                format!("{} depends on the shift quirk: the VIP shifts V{:X} into V{:X}, CHIP-48 and SCHIP shift V{:X} in place", instruction, vy, vx, vx)
+            ),
+            Instruction::JumpOffset { offset, .. } if offset & 0xF00 != 0 => self.report(
+                address, Severity::Info, FindingKind::Quirk,
+                format!("{} depends on the jump quirk: the VIP adds V0, CHIP-48 and SCHIP add V{:X}", instruction, offset >> 8)
+            ),
+            Instruction::SaveVXToMem { .. } | Instruction::LoadVXFromMem { .. } => {
+                if let Some(user) = next_index_use(self.cfg, address) {
+                    self.report(
+                        address, Severity::Info, FindingKind::Quirk,
+                        format!("{} is followed by a use of I at {:03X}: the VIP advances I, CHIP-48 and SCHIP leave it", instruction, user)
+                    );
+                }
+            },
+            _ => ()
+        }
+    }
+
+    fn calls(&mut self) {
+        let entry_point = self.cfg.entry_point;
+        let routines = self.cfg.routines();
+        let mut callees: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+
+        for (start, body) in &routines {
+            let returns: Vec<usize> = body.iter().copied()
+                .filter(|address| matches!(self.cfg.nodes[address].instruction, Some(Instruction::SubReturn)))
+                .collect();
+
+            if *start == entry_point {
+                for address in returns {
+                    self.report(address, Severity::Error, FindingKind::UnbalancedReturn, "RET is reachable from main without a call, the stack is empty".to_string());
+                }
+            } else if returns.is_empty() {
+                self.report(*start, Severity::Warning, FindingKind::MissingReturn, format!("{} never returns, every call leaves an entry on the stack", routine_name(*start, entry_point)));
+            }
+
+            let calls = body.iter().filter_map(|address| self.cfg.nodes[address].call.map(|target| (*address, target))).collect();
+            callees.insert(*start, calls);
+        }
+
+        // Depth first over the call graph: cycles are recursion, the longest chain is the deepest the stack gets.
+        let mut depths: HashMap<usize, usize> = HashMap::new();
+        let mut path = Vec::new();
+        let depth = self.call_depth(entry_point, &callees, &mut depths, &mut path);
+        if depth > STACK_SIZE {
+            self.report(entry_point, Severity::Error, FindingKind::StackDepth, format!("calls nest {} deep, the stack only holds {}", depth, STACK_SIZE));
+        }
+    }
+
+    fn call_depth(&mut self, routine: usize, callees: &BTreeMap<usize, Vec<(usize, usize)>>, depths: &mut HashMap<usize, usize>, path: &mut Vec<usize>) -> usize {
+        if let Some(depth) = depths.get(&routine) {
+            return *depth;
+        }
+
+        path.push(routine);
+        let mut deepest = 0;
+        for (site, target) in callees.get(&routine).cloned().unwrap_or_default() {
+            if let Some(position) = path.iter().position(|routine| *routine == target) {
+                let cycle: Vec<String> = path[position..].iter().chain(std::iter::once(&target)).map(|routine| routine_name(*routine, self.cfg.entry_point)).collect();
+                self.report(
+                    site, Severity::Warning, FindingKind::Recursion,
+                    format!("recursive call {}, each level uses one of the {} stack entries", cycle.join(" -> "), STACK_SIZE)
+                );
+                continue;
+            }
+            deepest = deepest.max(1 + self.call_depth(target, callees, depths, path));
+        }
+        path.pop();
+
+        depths.insert(routine, deepest);
+        deepest
+    }
+}
+
+// Static checks over everything reachable from the entry point. Findings are
+// sorted by address, errors first where several land on the same one.
+pub fn lint(cfg: &ControlFlowGraph, memory_size: usize, program: Range<usize>) -> Vec<Finding> {
+    let mut linter = Linter { cfg, memory_size, program, findings: Vec::new() };
+
+    if cfg.entry_point + 1 >= memory_size {
+        linter.report(cfg.entry_point, Severity::Error, FindingKind::TargetOutsideMemory, "the entry point is outside of memory".to_string());
+    }
+
+    for (address, node) in &cfg.nodes {
+        match &node.instruction {
+            None => {
+                let message = match chip8::extension(node.raw_inst) {
+                    Some((extension, _)) => format!("{} instruction {:04X} is reachable but not supported", extension.name(), node.raw_inst),
+                    None => format!("invalid instruction {:04X} is reachable", node.raw_inst)
+                };
+                linter.report(*address, Severity::Error, FindingKind::InvalidOpcode, message);
+            },
+            Some(instruction) => {
+                linter.targets(*address, node);
+                linter.quirks(*address, instruction);
+                if let Instruction::JumpOffset { .. } = instruction {
+                    linter.report(*address, Severity::Info, FindingKind::IndirectJump, format!("{} jumps to a computed address, the code it reaches is not checked", instruction));
+                }
+            }
+        }
+    }
+
+    linter.calls();
+
+    let mut findings = linter.findings;
+    findings.sort_by(|a, b| a.address.cmp(&b.address).then(a.severity.cmp(&b.severity)));
+    findings
+}
+
+pub fn report(findings: &[Finding], cfg: &ControlFlowGraph) -> String {
+    let mut out = String::new();
+    for finding in findings {
+        out.push_str(&format!("{}\n", finding));
+    }
+
+    let count = |severity: Severity| findings.iter().filter(|finding| finding.severity == severity).count();
+    out.push_str(&format!(
+        "{} reachable instructions, {} errors, {} warnings, {} notes\n",
+        cfg.nodes.len(), count(Severity::Error), count(Severity::Warning), count(Severity::Info)
+    ));
+
+    if findings.iter().any(|finding| finding.kind == FindingKind::Quirk) {
+        out.push_str("The ROM behaves differently on the VIP and on CHIP-48/SCHIP, compare it under both quirk profiles\n");
+    }
+    out
+}
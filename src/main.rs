@@ -1,109 +1,986 @@
-mod chip8;
+mod memory_view;
+mod sprite_view;
+mod cheat_panel;
+mod breakpoint_panel;
+mod settings_panel;
 
-use std::{sync::{Mutex, Arc}, time::Duration, env, process::ExitCode};
+use std::{sync::{Mutex, Arc}, env, io::{self, IsTerminal}, path::{Path, PathBuf}, process::ExitCode, ops::Range, time::SystemTime};
 
+use chip8_rs::{
+    chip8,
+    frontend::{self, AudioSink, InputSource, FixedClock, RealTimeClock, headless::{NullVideo, NullAudio, NullInput}, macroquad::{MacroquadVideo, MacroquadAudio, MacroquadInput}},
+    runner::{self, Runner},
+    speed::{self, SpeedClock},
+    scripting::ScriptHost,
+    trace::{self, Tracer},
+    profile::Profiler,
+    capture::{self, Palette, Recorder},
+    repl::Repl,
+    lint::{self, ControlFlowGraph, Severity},
+    detect::{self, Database},
+    explore::{Explorer, Machine},
+    settings::{Keymap, Manifest, RomSettings},
+    cartridge,
+    storage,
+    cheats::CheatList
+};
 use macroquad::prelude::*;
+use memory_view::MemoryView;
+use sprite_view::SpriteView;
+use cheat_panel::CheatPanel;
+use breakpoint_panel::BreakpointPanel;
+use settings_panel::SettingsPanel;
 
-const SCREEN_MARGIN: usize = 15;
-const PIXEL_MARGIN: usize = 0;
+const HEADLESS_DEFAULT_FRAMES: u32 = 3600;
+const PROFILE_DEFAULT_TOP: usize = 10;
+// How long a ROM runs before its screen becomes the cartridge label.
+const CARTRIDGE_LABEL_FRAMES: u32 = 120;
 
-#[macroquad::main("BasicShapes")]
-async fn main() {
+const USAGE: &str = "Usage: chip8 ./path/to/rom [--script ./path/to/script.rhai] [--trace ./path/to/trace.log]
+                          [--coverage ./path/to/report.txt] [--headless] [--terminal] [--frames N]
+                          [--seed N] [--screenshot ./path/to/shot.png]
+                          [--record ./path/to/clip.gif|.png] [--scale N] [--palette RRGGBB,RRGGBB]
+                          [--ipf N] [--fast-forward N] [--font vip|dream6800|eti660|chip48|fishnchips]
+                          [--font-address N] [--platform chip8|vip2k|eti660|xochip] [--memory N]
+                          [--load-address N] [--entry N] [--strict] [--sanitize]
+                          [--quirks vip|schip|xochip] [--no-detect] [--break SPEC]...
+                          [--keymap KEYS] [--patch ./path/to/patch.ips|.bps]...
+       chip8 trace-diff ./path/to/a.log ./path/to/b.log
+       chip8 profile ./path/to/rom [--frames N] [--top N]
+       chip8 debug ./path/to/rom [options] < commands.txt
+       chip8 lint ./path/to/rom [options]
+       chip8 explore ./path/to/rom --compare QUIRKS,QUIRKS[,...] [options]
+       chip8 cartridge ./path/to/rom ./path/to/cart.gif [options]
+       chip8 patch create ./path/to/original ./path/to/patched [./path/to/patch.ips|.bps]";
+
+struct Options {
+    rom: String,
+    script: Option<String>,
+    trace: Option<String>,
+    coverage: Option<String>,
+    headless: bool,
+    terminal: bool,
+    frames: u32,
+    profile: bool,
+    top: usize,
+    seed: Option<u64>,
+    screenshot: Option<String>,
+    record: Option<String>,
+    scale: u32,
+    palette: Palette,
+    instructions_per_frame: u32,
+    fast_forward: u32,
+    font: chip8::FontSet,
+    font_address: usize,
+    platform: chip8::Platform,
+    // Set when any of the memory map options was given, detection leaves the platform alone then.
+    platform_given: bool,
+    quirks: Option<chip8::Quirks>,
+    detect: bool,
+    strict: bool,
+    sanitize: bool,
+    breakpoints: Vec<chip8::Breakpoint>,
+    compare: Vec<(String, chip8::Quirks)>,
+    keymap: Keymap,
+    manifest: Option<Manifest>,
+    patches: Vec<PathBuf>
+}
+
+struct Session {
+    runner: Runner,
+    program: Range<usize>,
+    rom_hash: String
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok()
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut args = args.iter();
+    let mut options = Options {
+        rom: args.next().ok_or("Missing ROM path")?.clone(),
+        script: None,
+        trace: None,
+        coverage: None,
+        headless: false,
+        terminal: false,
+        frames: HEADLESS_DEFAULT_FRAMES,
+        profile: false,
+        top: PROFILE_DEFAULT_TOP,
+        seed: None,
+        screenshot: None,
+        record: None,
+        scale: capture::DEFAULT_SCALE,
+        palette: Palette::default(),
+        instructions_per_frame: runner::DEFAULT_INSTRUCTIONS_PER_FRAME,
+        fast_forward: speed::DEFAULT_FAST_FORWARD,
+        font: chip8::FontSet::default(),
+        font_address: 0,
+        platform: chip8::Platform::default(),
+        platform_given: false,
+        quirks: None,
+        detect: true,
+        strict: false,
+        sanitize: false,
+        breakpoints: Vec::new(),
+        compare: Vec::new(),
+        keymap: Keymap::default(),
+        manifest: None,
+        patches: Vec::new()
+    };
+    let mut memory_size = None;
+    let mut load_address = None;
+    let mut entry_point = None;
+    let mut given = Vec::new();
+
+    while let Some(arg) = args.next() {
+        given.push(arg.as_str());
+        if matches!(arg.as_str(), "--platform" | "--memory" | "--load-address" | "--entry") {
+            options.platform_given = true;
+        }
+
+        let mut value = || args.next().cloned().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--script" => options.script = Some(value()?),
+            "--trace" => options.trace = Some(value()?),
+            "--coverage" => options.coverage = Some(value()?),
+            "--headless" => options.headless = true,
+            "--terminal" => options.terminal = true,
+            "--frames" => options.frames = value()?.parse().map_err(|_| "Invalid frame count")?,
+            "--top" => options.top = value()?.parse().map_err(|_| "Invalid hotspot count")?,
+            "--seed" => options.seed = Some(parse_number(&value()?).ok_or("Invalid seed")?),
+            "--screenshot" => options.screenshot = Some(value()?),
+            "--record" => options.record = Some(value()?),
+            "--scale" => options.scale = value()?.parse().ok().filter(|scale| (1..=capture::MAX_SCALE).contains(scale)).ok_or(format!("Invalid scale, expected 1 to {}", capture::MAX_SCALE))?,
+            "--ipf" => options.instructions_per_frame = value()?.parse().map_err(|_| "Invalid instructions per frame")?,
+            "--fast-forward" => options.fast_forward = value()?.parse().ok().filter(|factor| (1..=speed::MAX_FAST_FORWARD).contains(factor)).ok_or(format!("Invalid fast-forward factor, expected 1 to {}", speed::MAX_FAST_FORWARD))?,
+            "--font" => options.font = chip8::FontSet::from_name(&value()?).ok_or("Unknown font")?,
+            "--font-address" => options.font_address = parse_number(&value()?).ok_or("Invalid font address")? as usize,
+            "--platform" => options.platform = chip8::Platform::from_name(&value()?).ok_or("Unknown platform")?,
+            "--memory" => memory_size = Some(parse_number(&value()?).ok_or("Invalid memory size")? as usize),
+            "--load-address" => load_address = Some(parse_number(&value()?).ok_or("Invalid load address")? as usize),
+            "--entry" => entry_point = Some(parse_number(&value()?).ok_or("Invalid entry point")? as usize),
+            "--quirks" => options.quirks = Some(chip8::Quirks::from_name(&value()?).ok_or("Unknown quirks")?),
+            "--no-detect" => options.detect = false,
+            "--strict" => options.strict = true,
+            "--sanitize" => options.sanitize = true,
+            "--break" => options.breakpoints.push(chip8::Breakpoint::parse(&value()?).map_err(|err| format!("Invalid breakpoint: {}", err))?),
+            "--compare" => options.compare = value()?.split(',')
+                .map(|spec| chip8::Quirks::parse(spec).map(|quirks| (spec.to_string(), quirks)))
+                .collect::<Result<_, _>>()
+                .map_err(|err| format!("Invalid comparison: {}", err))?,
+            "--patch" => options.patches.push(PathBuf::from(value()?)),
+            "--keymap" => options.keymap = Keymap::parse(&value()?).ok_or("Invalid keymap, expected 16 different letters or digits for keys 0-F")?,
+            "--palette" => options.palette = Palette::parse(&value()?).ok_or("Invalid palette, expected RRGGBB,RRGGBB")?,
+            _ => return Err(format!("Unknown argument: {}", arg))
+        }
+    }
+
+    // The overrides apply to whichever platform was picked, wherever they were given.
+    options.platform.memory_size = memory_size.unwrap_or(options.platform.memory_size);
+    options.platform.load_address = load_address.unwrap_or(options.platform.load_address);
+    // Programs start where they are loaded unless told otherwise.
+    options.platform.entry_point = entry_point.unwrap_or(options.platform.load_address);
+    options.platform.validate().map_err(|err| format!("Invalid platform: {}", err))?;
+
+    let settings = load_rom_settings(&mut options);
+    if options.quirks.is_none() {
+        options.quirks = settings.quirks;
+    }
+    if let (false, Some(instructions_per_frame)) = (given.contains(&"--ipf"), settings.instructions_per_frame) {
+        options.instructions_per_frame = instructions_per_frame;
+    }
+    if let (false, Some(palette)) = (given.contains(&"--palette"), settings.palette) {
+        options.palette = palette;
+    }
+    if let (false, Some(keymap)) = (given.contains(&"--keymap"), settings.keymap) {
+        options.keymap = keymap;
+    }
+    if let (false, Some(font)) = (given.contains(&"--font"), settings.font) {
+        options.font = font;
+    }
+
+    Ok(options)
+}
+
+// The program in a ROM file, which can also be a cartridge GIF carrying its own options,
+// with the patches applied.
+fn read_rom(options: &Options) -> Result<(Vec<u8>, Option<Manifest>), String> {
+    let bytes = std::fs::read(&options.rom).map_err(|err| format!("Error loading {}: {}", options.rom, err))?;
+    let (rom, manifest) = if cartridge::is_cartridge(&bytes) {
+        let cartridge = cartridge::unpack(&bytes).map_err(|err| format!("Error loading cartridge {}: {}", options.rom, err))?;
+        (cartridge.program, Some(cartridge.manifest))
+    } else {
+        (bytes, None)
+    };
+
+    let rom = chip8::apply_patch_files(rom, &options.patches).map_err(|err| format!("Error loading {}: {}", options.rom, err))?;
+    Ok((rom, manifest))
+}
+
+fn rom_settings_path(rom: &[u8]) -> PathBuf {
+    storage::rom_file("settings", &storage::rom_hash(rom), "txt")
+}
+
+// The settings saved for the ROM's hash, with gaps filled from the options file next to the ROM.
+// Neither has to exist, broken ones are reported and skipped.
+fn load_rom_settings(options: &mut Options) -> RomSettings {
+    let Ok((rom, cartridge_manifest)) = read_rom(options) else {
+        return RomSettings::default();
+    };
+
+    let path = rom_settings_path(&rom);
+    let stored = settings_panel::load(&path).unwrap_or_else(|err| {
+        println!("Error in {}: {}", path.display(), err);
+        RomSettings::default()
+    });
+
+    let path = Path::new(&options.rom).with_extension("json");
+    options.manifest = match std::fs::read_to_string(&path) {
+        Ok(json) => match Manifest::parse(&json) {
+            Ok(manifest) => Some(manifest),
+            Err(err) => {
+                println!("Error in {}: {}", path.display(), err);
+                cartridge_manifest
+            }
+        },
+        Err(_) => cartridge_manifest
+    };
+
+    if let Some(manifest) = &options.manifest {
+        match (&manifest.title, &manifest.author) {
+            (Some(title), Some(author)) => println!("{} by {}", title, author),
+            (Some(title), None) => println!("{}", title),
+            _ => ()
+        }
+    }
+
+    match &options.manifest {
+        Some(manifest) => stored.or(manifest.settings.clone()),
+        None => stored
+    }
+}
+
+fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
-        println!("Usage: chip8 ./path/to/rom");
+        println!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    match args[1].as_str() {
+        "trace-diff" => trace_diff(&args[2..]),
+        "patch" => create_patch(&args[2..]),
+        "profile" => match parse_options(&args[2..]) {
+            Ok(options) => run_headless(Options { headless: true, profile: true, ..options }),
+            Err(err) => {
+                println!("{}\n{}", err, USAGE);
+                ExitCode::FAILURE
+            }
+        },
+        "lint" => match parse_options(&args[2..]) {
+            Ok(options) => run_lint(options),
+            Err(err) => {
+                println!("{}\n{}", err, USAGE);
+                ExitCode::FAILURE
+            }
+        },
+        "debug" => match parse_options(&args[2..]) {
+            Ok(options) => run_debugger(options),
+            Err(err) => {
+                println!("{}\n{}", err, USAGE);
+                ExitCode::FAILURE
+            }
+        },
+        "cartridge" if args.len() >= 4 => {
+            let rom_args: Vec<String> = args[2..3].iter().chain(&args[4..]).cloned().collect();
+            match parse_options(&rom_args) {
+                Ok(options) => pack_cartridge(options, &args[3]),
+                Err(err) => {
+                    println!("{}\n{}", err, USAGE);
+                    ExitCode::FAILURE
+                }
+            }
+        },
+        "explore" => match parse_options(&args[2..]) {
+            Ok(options) if options.compare.len() < 2 => {
+                println!("explore needs at least two quirk settings to --compare\n{}", USAGE);
+                ExitCode::FAILURE
+            },
+            Ok(options) => match explore_unsupported(&options) {
+                Some(name) => {
+                    println!("{} does not apply to explore\n{}", name, USAGE);
+                    ExitCode::FAILURE
+                },
+                None => run_explorer(options)
+            },
+            Err(err) => {
+                println!("{}\n{}", err, USAGE);
+                ExitCode::FAILURE
+            }
+        },
+        _ => match parse_options(&args[1..]) {
+            Ok(options) if options.headless => run_headless(options),
+            Ok(options) if options.terminal => run_terminal(options),
+            Ok(options) => {
+                macroquad::Window::new("BasicShapes", run(options));
+                ExitCode::SUCCESS
+            },
+            Err(err) => {
+                println!("{}\n{}", err, USAGE);
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+fn trace_diff(args: &[String]) -> ExitCode {
+    if args.len() != 2 {
+        println!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    match trace::diff(Path::new(&args[0]), Path::new(&args[1])) {
+        Ok(None) => {
+            println!("Traces are identical");
+            ExitCode::SUCCESS
+        },
+        Ok(Some(divergence)) => {
+            trace::print_divergence(&divergence);
+            ExitCode::FAILURE
+        },
+        Err(err) => {
+            println!("Error reading traces: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Writes next to the patched ROM with a .bps extension unless told otherwise.
+fn create_patch(args: &[String]) -> ExitCode {
+    if args.first().map(String::as_str) != Some("create") || !(3..=4).contains(&args.len()) {
+        println!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let output = match args.get(3) {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(&args[2]).with_extension("bps")
+    };
+    let read = |path: &String| std::fs::read(path).map_err(|err| format!("Error loading {}: {}", path, err));
+    let patch = read(&args[1])
+        .and_then(|original| Ok((original, read(&args[2])?)))
+        .and_then(|(original, patched)| chip8::create_patch(chip8::PatchFormat::from_path(&output), &original, &patched))
+        .and_then(|patch| std::fs::write(&output, patch).map_err(|err| format!("Error writing {}: {}", output.display(), err)));
+
+    match patch {
+        Ok(()) => {
+            println!("Saved patch {}", output.display());
+            ExitCode::SUCCESS
+        },
+        Err(err) => {
+            println!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Fills in whatever the command line left open from the ROM database or the ROM's opcodes.
+fn detect_settings(options: &Options, rom: &[u8]) -> Result<(chip8::Platform, Option<chip8::Quirks>), String> {
+    if !options.detect || (options.platform_given && options.quirks.is_some()) {
+        return Ok((options.platform.clone(), options.quirks));
+    }
+
+    let mut database = Database::embedded();
+    let user_database = storage::data_dir().join("known_roms.txt");
+    if let Ok(text) = std::fs::read_to_string(&user_database) {
+        database.add(&text).map_err(|err| format!("Error in {}: {}", user_database.display(), err))?;
+    }
+
+    let detection = detect::detect(rom, &storage::rom_hash(rom), options.platform.load_address, &database);
+    let platform = match detection.platform {
+        Some(platform) if !options.platform_given => platform,
+        _ => options.platform.clone()
+    };
+    let quirks = options.quirks.or(detection.quirks);
+
+    if let Some(title) = &detection.title {
+        println!("Known ROM: {}", title);
+    }
+    if !detection.reasons.is_empty() {
+        let quirks = quirks.map_or("default", |quirks| quirks.name());
+        println!("Using platform {} and {} quirks ({})", platform.name, quirks, detection.reasons.join(", "));
+    }
+    Ok((platform, quirks))
+}
+
+// The machine alone, loaded and configured, without the tools that watch it run.
+fn setup_vm(options: &Options) -> Result<(chip8::VM, Range<usize>, String), String> {
+    let (rom, _) = read_rom(options)?;
+    let (platform, quirks) = detect_settings(options, &rom)?;
+    let mut vm = chip8::VM::new(platform.clone());
+    if let Some(quirks) = quirks {
+        quirks.apply(&mut vm);
+    }
+
+    if let Err(err) = vm.load_font(options.font, options.font_address) {
+        return Err(format!("Font does not fit at {:#X}: {:?}", options.font_address, err));
+    }
+
+    let program_size = match vm.load_rom(&rom) {
+        Ok(size) => size,
+        Err(err) => return Err(format!("Error loading {}: {}", options.rom, err))
+    };
+    let load_address = platform.load_address;
+    let program = load_address..load_address + program_size;
+    let rom_hash = storage::rom_hash(&vm.memory[program.clone()]);
+
+    if let Ok(flags) = storage::load_rpl_flags(&storage::rom_file("flags", &rom_hash, "bin"), platform.rpl_flags) {
+        vm.rpl_flags = flags;
+    }
+
+    vm.strict = options.strict;
+
+    if options.coverage.is_some() {
+        vm.enable_coverage();
+    }
+
+    if options.sanitize {
+        vm.enable_sanitizer();
+    }
+
+    for breakpoint in &options.breakpoints {
+        vm.debugger.add(breakpoint.clone());
+    }
+
+    if let Some(seed) = options.seed {
+        vm.random = Box::new(chip8::SeededRandom::new(seed));
+    }
+
+    Ok((vm, program, rom_hash))
+}
+
+fn setup(options: &Options) -> Result<Session, String> {
+    let (vm, program, rom_hash) = setup_vm(options)?;
+    let entry_point = vm.platform.entry_point;
+    let vm = Arc::new(Mutex::new(vm));
+
+    let scripts = match &options.script {
+        Some(path) => match ScriptHost::load(Path::new(path), vm.clone()) {
+            Ok(host) => Some(host),
+            Err(err) => return Err(format!("Error loading script {}: {}", path, err))
+        },
+        None => None
+    };
+
+    let tracer = match &options.trace {
+        Some(path) => match Tracer::create(Path::new(path)) {
+            Ok(tracer) => Some(tracer),
+            Err(err) => return Err(format!("Error creating trace {}: {}", path, err))
+        },
+        None => None
+    };
+
+    let profiler = if options.profile {
+        Some(Profiler::new(entry_point))
+    } else {
+        None
+    };
+
+    let recorder = options.record.as_ref().map(|path| Recorder::new(Path::new(path), options.scale, options.palette));
+
+    let path = storage::rom_file("cheats", &rom_hash, "cht");
+    let cheats = CheatList::load(&path).unwrap_or_else(|err| {
+        println!("Error in {}: {}", path.display(), err);
+        CheatList::new(&path)
+    });
+
+    let mut runner = Runner { scripts, tracer, profiler, recorder, cheats: Some(cheats), ..Runner::new(vm) };
+    runner.set_instructions_per_frame(options.instructions_per_frame);
+
+    Ok(Session { runner, program, rom_hash })
+}
+
+// Hotkey captures go to the working directory, named after the ROM and the time.
+fn capture_path(session: &Session, extension: &str) -> PathBuf {
+    let seconds = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    PathBuf::from(format!("chip8-{}-{}.{}", &session.rom_hash[..8], seconds, extension))
+}
+
+fn save_screenshot(session: &Session, path: &Path, options: &Options) {
+    let display = session.runner.vm.lock().unwrap().display.clone();
+    match capture::save_png(path, &display, options.scale, options.palette) {
+        Ok(()) => println!("Saved screenshot {}", path.display()),
+        Err(err) => println!("Error writing screenshot {}: {}", path.display(), err)
+    }
+}
+
+fn stop_recording(session: &mut Session) {
+    if let Some(recorder) = session.runner.recorder.take() {
+        let path = recorder.path().to_path_buf();
+        let frames = recorder.frame_count();
+        match recorder.finish() {
+            Ok(()) => println!("Saved recording {} ({} frames)", path.display(), frames),
+            Err(err) => println!("Error writing recording {}: {}", path.display(), err)
+        }
+    }
+}
+
+// Writes the RPL flags once a program stored them, so high scores survive a crash too.
+fn save_rpl_flags(session: &Session) {
+    let mut vm_lock = session.runner.vm.lock().unwrap();
+    if !vm_lock.rpl_flags_written {
         return;
     }
+    vm_lock.rpl_flags_written = false;
+
+    let path = storage::rom_file("flags", &session.rom_hash, "bin");
+    let count = vm_lock.platform.rpl_flags;
+    if let Err(err) = storage::save_rpl_flags(&path, &vm_lock.rpl_flags[..count]) {
+        println!("Error saving RPL flags {}: {}", path.display(), err);
+    }
+}
+
+fn finish(session: &mut Session, options: &Options) {
+    save_rpl_flags(session);
+
+    if let Some(path) = &options.screenshot {
+        save_screenshot(session, Path::new(path), options);
+    }
+
+    stop_recording(session);
+
+    if let Some(path) = &options.coverage {
+        let vm_lock = session.runner.vm.lock().unwrap();
+        if let Some(coverage) = &vm_lock.coverage {
+            let report = coverage.report(&vm_lock.memory, session.program.clone());
+            if let Err(err) = std::fs::write(path, report) {
+                println!("Error writing coverage report {}: {}", path, err);
+            }
+        }
+    }
+
+    if let Some(profiler) = &session.runner.profiler {
+        print!("{}", profiler.report(&session.runner.vm.lock().unwrap().memory, options.top));
+    }
+}
+
+// Breakpoint hits and logpoint output, for the frontends without a place of their own to show them.
+fn print_debugger_messages(session: &Session) {
+    for message in session.runner.vm.lock().unwrap().debugger.take_messages() {
+        println!("Debugger: {}", message);
+    }
+}
+
+fn run_headless(options: Options) -> ExitCode {
+    let mut session = match setup(&options) {
+        Ok(session) => session,
+        Err(err) => {
+            println!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for _ in 0..options.frames {
+        let _ = session.runner.update(&mut NullVideo, &mut NullAudio, &mut NullInput, &mut FixedClock);
+        print_debugger_messages(&session);
+    }
+
+    finish(&mut session, &options);
+    ExitCode::SUCCESS
+}
+
+// Fails when anything is certainly broken, warnings and notes only inform.
+// Nothing runs, so only the VM is set up.
+fn run_lint(options: Options) -> ExitCode {
+    let (vm, program, _) = match setup_vm(&options) {
+        Ok(setup) => setup,
+        Err(err) => {
+            println!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let cfg = ControlFlowGraph::build(&vm.memory, vm.platform.entry_point);
+    let findings = lint::lint(&cfg, vm.memory.len(), program);
+    print!("{}", lint::report(&findings, &cfg));
+
+    if findings.iter().any(|finding| finding.severity == Severity::Error) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
 
-    let vm = Arc::new(Mutex::new(chip8::VM::new()));
+// Commands come from stdin, piped command files get echoed so the output reads like a session.
+fn run_debugger(options: Options) -> ExitCode {
+    let mut session = match setup(&options) {
+        Ok(session) => session,
+        Err(err) => {
+            println!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let stdin = io::stdin();
+    let echo = !stdin.is_terminal();
+    if let Err(err) = Repl::new(&mut session.runner).run(stdin.lock(), io::stdout(), echo) {
+        println!("Debugger error: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    finish(&mut session, &options);
+    ExitCode::SUCCESS
+}
+
+// Packs the program with the settings it would run with here and the screen after a
+// couple of seconds as the label.
+fn pack_cartridge(options: Options, path: &str) -> ExitCode {
+    let mut session = match setup(&options) {
+        Ok(session) => session,
+        Err(err) => {
+            println!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    // Before running, the program may rewrite itself.
+    let rom = session.runner.vm.lock().unwrap().memory[session.program.clone()].to_vec();
+
+    for _ in 0..CARTRIDGE_LABEL_FRAMES {
+        let _ = session.runner.update(&mut NullVideo, &mut NullAudio, &mut NullInput, &mut FixedClock);
+        print_debugger_messages(&session);
+    }
+
+    let vm = session.runner.vm.lock().unwrap();
+    let manifest = Manifest {
+        settings: RomSettings {
+            quirks: Some(chip8::Quirks::of(&vm)),
+            instructions_per_frame: Some(session.runner.instructions_per_frame),
+            palette: Some(options.palette),
+            keymap: Some(options.keymap),
+            font: Some(options.font)
+        },
+        ..options.manifest.clone().unwrap_or_default()
+    };
+
+    let result = cartridge::pack(&rom, &manifest, &vm.display)
+        .and_then(|gif| std::fs::write(path, gif).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => {
+            println!("Saved cartridge {}", path);
+            ExitCode::SUCCESS
+        },
+        Err(err) => {
+            println!("Error writing cartridge {}: {}", path, err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Every machine comes out of the usual setup with only the quirks changed. Detection
+// runs for the first one and the rest reuse its platform, and they share one seed.
+// The explorer steps the VMs itself, so nothing that hooks into the runner or waits on a pause applies.
+fn explore_unsupported(options: &Options) -> Option<&'static str> {
+    let given = [
+        (options.script.is_some(), "--script"),
+        (options.trace.is_some(), "--trace"),
+        (options.coverage.is_some(), "--coverage"),
+        (options.record.is_some(), "--record"),
+        (options.screenshot.is_some(), "--screenshot"),
+        (options.sanitize, "--sanitize"),
+        (!options.breakpoints.is_empty(), "--break")
+    ];
+    given.iter().find(|(given, _)| *given).map(|(_, name)| *name)
+}
+
+fn setup_explorer(mut options: Options) -> Result<(Explorer, Options), String> {
+    options.seed = Some(options.seed.unwrap_or_else(::rand::random));
+    let mut machines = Vec::new();
+
+    for (name, quirks) in options.compare.clone() {
+        options.quirks = Some(quirks);
+        let (vm, _, _) = setup_vm(&options)?;
+        options.platform = vm.platform.clone();
+        options.platform_given = true;
+        machines.push(Machine::new(&name, quirks, vm));
+    }
 
-    vm.lock().unwrap().mem_copy(&chip8::FONT_DATA, 0);
+    Ok((Explorer::new(machines), options))
+}
+
+fn run_explorer(options: Options) -> ExitCode {
+    let (mut explorer, options) = match setup_explorer(options) {
+        Ok(setup) => setup,
+        Err(err) => {
+            println!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if !options.headless {
+        macroquad::Window::new("BasicShapes", explore(explorer, options));
+        return ExitCode::SUCCESS;
+    }
+
+    for _ in 0..options.frames {
+        if explorer.halted() {
+            break;
+        }
+        explorer.run_frame(options.instructions_per_frame);
+    }
+
+    print!("{}", explorer.report());
+    for machine in &explorer.machines {
+        if let Some(err) = &machine.error {
+            println!("{} stopped: {}", machine.name, err);
+        }
+    }
+
+    if explorer.divergence.is_some() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// The displays side by side in one row, each labelled, with the divergence underneath.
+async fn explore(mut explorer: Explorer, options: Options) {
+    let video = MacroquadVideo::with_palette(options.palette.off, options.palette.on);
+    let mut input = MacroquadInput::new(&options.keymap);
+    let mut keyboard = chip8::Keyboard::new();
+    let mut paused = false;
+    let mut reported = false;
+
+    loop {
+        clear_background(BLACK);
+
+        if is_key_pressed(KeyCode::P) {
+            paused = !paused;
+        }
+
+        input.poll(&mut keyboard);
+        explorer.set_keys(keyboard.keys);
+        if !paused || is_key_pressed(KeyCode::N) {
+            explorer.run_frame(options.instructions_per_frame);
+        }
+
+        if let (Some(divergence), false) = (&explorer.divergence, reported) {
+            print!("{}", divergence.describe(&explorer.machines));
+            reported = true;
+        }
+
+        let count = explorer.machines.len() as f32;
+        let width = (screen_width() - 10.0 * (count + 1.0)) / count;
+        let height = width * chip8::DISPLAY_HEIGHT as f32 / chip8::DISPLAY_WIDTH as f32;
+        for (index, machine) in explorer.machines.iter().enumerate() {
+            let left = 10.0 + index as f32 * (width + 10.0);
+            let differs = explorer.divergence.as_ref().is_some_and(|divergence| divergence.machines.contains(&index));
+            let label = match &machine.error {
+                Some(err) => format!("{} ({}), stopped: {}", machine.name, machine.quirks.name(), err),
+                None => format!("{} ({})", machine.name, machine.quirks.name())
+            };
+            draw_text(&label, left, 30.0, 20.0, if differs { RED } else { GREEN });
+            video.draw(&machine.vm.display, left, 40.0, width, height);
+        }
+
+        let status = match &explorer.divergence {
+            Some(divergence) => {
+                let quirks = if divergence.quirks.is_empty() { "no quirk".to_string() } else { divergence.quirks.join(", ") };
+                format!("Diverged at {} ({})", divergence, quirks)
+            },
+            None => format!("Frame {}, no divergence", explorer.frame)
+        };
+        draw_text(&status, 10.0, 60.0 + height, 20.0, YELLOW);
+
+        next_frame().await
+    }
+}
 
-    vm.lock().unwrap().load_program_from_file(&std::path::Path::new(&args[1]), 0x200);
+fn run_terminal(options: Options) -> ExitCode {
+    let mut session = match setup(&options) {
+        Ok(session) => session,
+        Err(err) => {
+            println!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
 
-    vm.lock().unwrap().program_counter = 0x200;
+    if let Err(err) = frontend::terminal::run(&mut session.runner, &options.keymap) {
+        println!("Terminal error: {}", err);
+        return ExitCode::FAILURE;
+    }
 
+    finish(&mut session, &options);
+    ExitCode::SUCCESS
+}
 
-    {
-        let vm_clone = vm.clone();
-        std::thread::spawn(move || {
-            tick(vm_clone);
-        });
+// Switches the running session over to settings from the settings panel.
+fn apply_settings(settings: &RomSettings, vm: &mut chip8::VM, options: &mut Options) {
+    if let Some(quirks) = settings.quirks {
+        quirks.apply(vm);
+    }
+    if let Some(instructions_per_frame) = settings.instructions_per_frame {
+        options.instructions_per_frame = instructions_per_frame;
+    }
+    if let Some(palette) = settings.palette {
+        options.palette = palette;
+    }
+    if let Some(keymap) = settings.keymap {
+        options.keymap = keymap;
+    }
+    if let Some(font) = settings.font {
+        if let Err(err) = vm.load_font(font, vm.font_address) {
+            println!("Font does not fit at {:#X}: {:?}", vm.font_address, err);
+        } else {
+            options.font = font;
+        }
     }
+}
+
+async fn run(mut options: Options) {
+    let mut session = match setup(&options) {
+        Ok(session) => session,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    let vm = session.runner.vm.clone();
+
+    let mut memory_view = MemoryView::new();
+    let mut sprite_view = SpriteView::new();
 
+    let mut cheat_panel = CheatPanel::new(&vm.lock().unwrap());
+    let mut breakpoint_panel = BreakpointPanel::new();
+    let current = RomSettings {
+        quirks: Some(chip8::Quirks::of(&vm.lock().unwrap())),
+        instructions_per_frame: Some(session.runner.instructions_per_frame),
+        palette: Some(options.palette),
+        keymap: Some(options.keymap),
+        font: Some(options.font)
+    };
+    let path = rom_settings_path(&vm.lock().unwrap().memory[session.program.clone()]);
+    let mut settings_panel = SettingsPanel::new(path, &current, options.manifest.clone());
+
+    let mut video = MacroquadVideo::with_palette(options.palette.off, options.palette.on);
+    let mut audio = MacroquadAudio::load().await;
+    let mut input = MacroquadInput::new(&options.keymap);
+    let mut clock = SpeedClock::new(RealTimeClock::new());
+    clock.fast_forward_factor = options.fast_forward;
+
+    prevent_quit();
 
     loop {
+        if is_quit_requested() {
+            audio.set_tone(false);
+            finish(&mut session, &options);
+            return;
+        }
+
+        clear_background(BLACK);
+
+        clock.fast_forward = is_key_down(KeyCode::Tab);
+
+        if is_key_pressed(KeyCode::RightBracket) {
+            clock.faster();
+        }
+
+        if is_key_pressed(KeyCode::LeftBracket) {
+            clock.slower();
+        }
+
+        let step = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) { 10 } else { 1 };
+        if is_key_pressed(KeyCode::Equal) {
+            let instructions_per_frame = session.runner.instructions_per_frame.saturating_add(step);
+            session.runner.set_instructions_per_frame(instructions_per_frame);
+        }
+
+        if is_key_pressed(KeyCode::Minus) {
+            let instructions_per_frame = session.runner.instructions_per_frame.saturating_sub(step);
+            session.runner.set_instructions_per_frame(instructions_per_frame);
+        }
+
+        let _ = session.runner.update(&mut video, &mut audio, &mut input, &mut clock);
+        save_rpl_flags(&session);
+
+        if is_key_pressed(KeyCode::N) && vm.lock().unwrap().paused {
+            let _ = session.runner.advance_frame();
+        }
+
+        if is_key_pressed(KeyCode::F12) {
+            save_screenshot(&session, &capture_path(&session, "png"), &options);
+        }
+
+        if is_key_pressed(KeyCode::F11) {
+            if session.runner.recorder.is_some() {
+                stop_recording(&mut session);
+            } else {
+                let path = capture_path(&session, "gif");
+                println!("Recording to {}", path.display());
+                session.runner.recorder = Some(Recorder::new(&path, options.scale, options.palette));
+            }
+        }
+
         {
             let mut vm_lock = vm.lock().unwrap();
-            clear_background(BLACK);
-    
-            let display_width: f32;
-            let display_height: f32;
-    
-            let aspect_ratio: f32 = chip8::DISPLAY_HEIGHT as f32 / chip8::DISPLAY_HEIGHT as f32;
-    
-            if screen_width() as f32 / screen_height() as f32 > aspect_ratio {
-                display_width = screen_width() as f32 * aspect_ratio;
-                display_height = screen_height() as f32;
-            } else {
-                display_width = screen_width() as f32;
-                display_height = screen_width() as f32 / aspect_ratio;
-            }
-    
-            let left_margin = (screen_width() - display_width) / 2.0f32 - SCREEN_MARGIN as f32;
-            let top_margin = (screen_height() - display_height) / 2.0f32 - SCREEN_MARGIN as f32;
-    
-            for y in 0..chip8::DISPLAY_HEIGHT {
-                for x in 0..chip8::DISPLAY_WIDTH {
-                    let color: Color;
-                    if vm_lock.display.get(x, y) {
-                        color = color_u8!(200,200,200,255);
-                    } else {
-                        color = color_u8!(20,20,20,255);
-                    }
-    
-                    let pixel_x = left_margin + SCREEN_MARGIN as f32 + PIXEL_MARGIN as f32 + (display_width / chip8::DISPLAY_WIDTH as f32) * x as f32;
-                    let pixel_y = top_margin + SCREEN_MARGIN as f32 + PIXEL_MARGIN as f32 + (display_height / chip8::DISPLAY_HEIGHT as f32) * y as f32;
-                    let pixel_w = display_width / chip8::DISPLAY_WIDTH as f32 - 2f32 * PIXEL_MARGIN as f32;
-                    let pixel_h = display_height / chip8::DISPLAY_HEIGHT as f32 - 2f32 * PIXEL_MARGIN as f32;
-                    draw_rectangle(pixel_x, pixel_y, pixel_w, pixel_h, color)
-                }
+
+            memory_view.draw(&vm_lock);
+            sprite_view.draw(&vm_lock);
+
+            if is_key_pressed(KeyCode::P) {
+                vm_lock.paused = !vm_lock.paused;
+            }
+
+            if is_key_pressed(KeyCode::F2) {
+                memory_view.open = !memory_view.open;
             }
-            vm_lock.tick().unwrap();
 
-            vm_lock.keyboard.keys[0x1] = is_key_down(KeyCode::Key1);
-            vm_lock.keyboard.keys[0x2] = is_key_down(KeyCode::Key2);
-            vm_lock.keyboard.keys[0x3] = is_key_down(KeyCode::Key3);
-            vm_lock.keyboard.keys[0xC] = is_key_down(KeyCode::Key4);
-            vm_lock.keyboard.keys[0x4] = is_key_down(KeyCode::Q);
-            vm_lock.keyboard.keys[0x5] = is_key_down(KeyCode::W);
-            vm_lock.keyboard.keys[0x6] = is_key_down(KeyCode::E);
-            vm_lock.keyboard.keys[0xD] = is_key_down(KeyCode::R);
-            vm_lock.keyboard.keys[0x7] = is_key_down(KeyCode::A);
-            vm_lock.keyboard.keys[0x8] = is_key_down(KeyCode::S);
-            vm_lock.keyboard.keys[0x9] = is_key_down(KeyCode::D);
-            vm_lock.keyboard.keys[0xE] = is_key_down(KeyCode::F);
-            vm_lock.keyboard.keys[0xA] = is_key_down(KeyCode::Z);
-            vm_lock.keyboard.keys[0x0] = is_key_down(KeyCode::X);
-            vm_lock.keyboard.keys[0xB] = is_key_down(KeyCode::C);
-            vm_lock.keyboard.keys[0xF] = is_key_down(KeyCode::V);
+            if is_key_pressed(KeyCode::F3) {
+                cheat_panel.open = !cheat_panel.open;
+            }
+
+            if is_key_pressed(KeyCode::F4) {
+                sprite_view.open = !sprite_view.open;
+            }
+
+            if is_key_pressed(KeyCode::F5) {
+                breakpoint_panel.open = !breakpoint_panel.open;
+            }
+
+            if is_key_pressed(KeyCode::F6) {
+                settings_panel.open = !settings_panel.open;
+            }
 
-            if vm_lock.delay_timer > 0 {
-                vm_lock.delay_timer -= 1;
+            memory_view.update(&mut vm_lock);
+            sprite_view.update(&vm_lock);
+            if let Some(cheats) = &mut session.runner.cheats {
+                cheat_panel.update(&mut vm_lock, cheats);
             }
+            breakpoint_panel.update(&mut vm_lock);
+            memory_view.end_frame(&vm_lock);
 
-            if vm_lock.sound_timer > 0 {
-                vm_lock.sound_timer -= 1;
+            if let Some(settings) = settings_panel.update(&mut vm_lock) {
+                apply_settings(&settings, &mut vm_lock, &mut options);
+                session.runner.set_instructions_per_frame(options.instructions_per_frame);
+                video = MacroquadVideo::with_palette(options.palette.off, options.palette.on);
+                input = MacroquadInput::new(&options.keymap);
             }
         }
-        next_frame().await
-    }
 
-    fn tick(vm: Arc<Mutex<chip8::VM>>) {
-        loop {
-            std::thread::sleep(Duration::from_millis(2));
-            vm.lock().unwrap().tick().unwrap();
+        if let Some(scripts) = &session.runner.scripts {
+            for text in scripts.overlay().iter() {
+                draw_text(&text.text, text.x, text.y, 20.0, YELLOW);
+            }
         }
+
+        let status = clock.status(session.runner.instructions_per_frame, vm.lock().unwrap().paused);
+        draw_text(&format!("{}  {} fps", status, get_fps()), 10.0, screen_height() - 10.0, 20.0, GREEN);
+
+        next_frame().await
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,218 @@
+use macroquad::prelude::*;
+
+use chip8_rs::{chip8::{self, VM}, hex_edit::{self, BYTES_PER_ROW, Changes, HexEditor}};
+
+const FONT_SIZE: u16 = 16;
+const ROW_HEIGHT: f32 = 16.0;
+const BITMAP_PIXEL_WIDTH: f32 = 3.0;
+const HEADER_ROWS: usize = 2;
+
+// Hex view of the VM memory with ASCII and bitmap columns. The bitmap column
+// draws every byte as one row of 8 pixels, so sprites show up as they would on screen.
+pub struct MemoryView {
+    pub open: bool,
+    scroll: usize,
+    editor: HexEditor,
+    changes: Changes
+}
+
+struct Layout {
+    left: f32,
+    top: f32,
+    char_width: f32,
+    rows: usize
+}
+
+impl Layout {
+    fn new() -> Layout {
+        let char_width = measure_text("0", None, FONT_SIZE, 1.0).width;
+        let width = char_width * (6 + BYTES_PER_ROW * 3 + 1 + BYTES_PER_ROW + 1) as f32 + 8.0 * BITMAP_PIXEL_WIDTH + 10.0;
+        let rows = ((screen_height() / ROW_HEIGHT) as usize).saturating_sub(HEADER_ROWS + 1);
+
+        Layout { left: (screen_width() - width).max(0.0), top: ROW_HEIGHT * HEADER_ROWS as f32, char_width, rows }
+    }
+
+    fn hex_x(&self, column: usize) -> f32 {
+        self.left + 5.0 + self.char_width * (6 + column * 3) as f32
+    }
+
+    fn ascii_x(&self, column: usize) -> f32 {
+        self.left + 5.0 + self.char_width * (6 + BYTES_PER_ROW * 3 + 1 + column) as f32
+    }
+
+    fn bitmap_x(&self) -> f32 {
+        self.ascii_x(BYTES_PER_ROW + 1)
+    }
+
+    fn row_y(&self, row: usize) -> f32 {
+        self.top + ROW_HEIGHT * row as f32
+    }
+}
+
+impl MemoryView {
+    pub fn new() -> MemoryView {
+        MemoryView {
+            open: false,
+            scroll: 0x200 / BYTES_PER_ROW,
+            editor: HexEditor::default(),
+            changes: Changes::default()
+        }
+    }
+
+    fn scroll_to(&mut self, address: usize, rows: usize) {
+        let row = address / BYTES_PER_ROW;
+        if row < self.scroll || row >= self.scroll + rows {
+            self.scroll = row.saturating_sub(rows / 2);
+        }
+    }
+
+    // Tracks changes between frames. While paused the last highlights are kept around.
+    pub fn end_frame(&mut self, vm: &VM) {
+        if !vm.paused {
+            self.changes.end_frame(&vm.memory);
+        }
+    }
+
+    pub fn update(&mut self, vm: &mut VM) {
+        if !self.open {
+            return;
+        }
+
+        let layout = Layout::new();
+        let max_scroll = hex_edit::row_count(&vm.memory).saturating_sub(layout.rows);
+
+        let wheel = if mouse_position().0 >= layout.left { mouse_wheel().1 } else { 0.0 };
+        if wheel > 0.0 || is_key_pressed(KeyCode::PageUp) {
+            self.scroll = self.scroll.saturating_sub(if wheel > 0.0 { 3 } else { layout.rows });
+        }
+        if wheel < 0.0 || is_key_pressed(KeyCode::PageDown) {
+            self.scroll += if wheel < 0.0 { 3 } else { layout.rows };
+        }
+        if is_key_pressed(KeyCode::Home) {
+            self.scroll_to(vm.program_counter, layout.rows);
+        }
+        if is_key_pressed(KeyCode::End) {
+            self.scroll_to(vm.index_register as usize, layout.rows);
+        }
+        self.scroll = self.scroll.min(max_scroll);
+
+        if !vm.paused {
+            self.editor.select(None);
+            return;
+        }
+
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let (x, y) = mouse_position();
+            let row = ((y - layout.top) / ROW_HEIGHT).floor();
+            let column = ((x - layout.hex_x(0)) / (layout.char_width * 3.0)).floor();
+
+            if row >= 0.0 && (row as usize) < layout.rows && column >= 0.0 && (column as usize) < BYTES_PER_ROW {
+                let address = (self.scroll + row as usize) * BYTES_PER_ROW + column as usize;
+                if address < vm.memory.len() {
+                    self.editor.select(Some(address));
+                }
+            }
+        }
+
+        if let Some(selected) = self.editor.selected {
+            let moved = if is_key_pressed(KeyCode::Left) {
+                selected.checked_sub(1)
+            } else if is_key_pressed(KeyCode::Right) {
+                Some(selected + 1)
+            } else if is_key_pressed(KeyCode::Up) {
+                selected.checked_sub(BYTES_PER_ROW)
+            } else if is_key_pressed(KeyCode::Down) {
+                Some(selected + BYTES_PER_ROW)
+            } else {
+                None
+            };
+
+            if let Some(address) = moved.filter(|address| *address < vm.memory.len()) {
+                self.editor.select(Some(address));
+                self.scroll_to(address, layout.rows);
+            }
+
+            if is_key_pressed(KeyCode::Escape) {
+                self.editor.select(None);
+            }
+        }
+
+        while let Some(c) = get_char_pressed() {
+            if let Some(address) = self.editor.type_char(c, &mut vm.memory) {
+                self.changes.edited(address, vm.memory[address]);
+            }
+        }
+    }
+
+    pub fn draw(&self, vm: &VM) {
+        if !self.open {
+            return;
+        }
+
+        let layout = Layout::new();
+        let pc = vm.program_counter;
+        let index = vm.index_register as usize;
+
+        draw_rectangle(layout.left, 0.0, screen_width() - layout.left, screen_height(), color_u8!(10, 10, 30, 230));
+
+        let status = if vm.paused { "paused, click a byte and type hex to edit" } else { "running, P to pause" };
+        draw_text(&format!("PC {:03X}  I {:03X}  {}", pc, index, status), layout.left + 5.0, ROW_HEIGHT, FONT_SIZE as f32, WHITE);
+
+        for row in 0..layout.rows {
+            let base = (self.scroll + row) * BYTES_PER_ROW;
+            let bytes = hex_edit::row(&vm.memory, base);
+            if bytes.is_empty() {
+                break;
+            }
+
+            let y = layout.row_y(row);
+            let baseline = y + ROW_HEIGHT - 4.0;
+            draw_text(&format!("{:03X}", base), layout.left + 5.0, baseline, FONT_SIZE as f32, GRAY);
+
+            for (column, &value) in bytes.iter().enumerate() {
+                let address = base + column;
+                let x = layout.hex_x(column);
+
+                let background = if self.editor.selected == Some(address) {
+                    Some(YELLOW)
+                } else if address == pc || address == pc + 1 {
+                    Some(DARKGREEN)
+                } else if address >= index && address < index + 2 {
+                    Some(color_u8!(140, 80, 0, 255))
+                } else {
+                    None
+                };
+
+                if let Some(background) = background {
+                    draw_rectangle(x - 1.0, y + 1.0, layout.char_width * 2.0 + 2.0, ROW_HEIGHT - 2.0, background);
+                }
+
+                let color = if self.editor.selected == Some(address) {
+                    BLACK
+                } else if self.changes.changed(address) {
+                    RED
+                } else if (vm.font_address..vm.font_address + chip8::FONT_SIZE).contains(&address) {
+                    SKYBLUE
+                } else {
+                    LIGHTGRAY
+                };
+
+                let text = match (self.editor.selected == Some(address), self.editor.pending_nibble) {
+                    (true, Some(high)) => format!("{:X}_", high),
+                    _ => format!("{:02X}", value)
+                };
+                draw_text(&text, x, baseline, FONT_SIZE as f32, color);
+
+                let c = if value.is_ascii_graphic() { value as char } else { '.' };
+                draw_text(&c.to_string(), layout.ascii_x(column), baseline, FONT_SIZE as f32, color);
+
+                let bitmap_row_height = ROW_HEIGHT / BYTES_PER_ROW as f32;
+                for bit in 0..8 {
+                    if value & (0x80 >> bit) != 0 {
+                        draw_rectangle(layout.bitmap_x() + bit as f32 * BITMAP_PIXEL_WIDTH, y + column as f32 * bitmap_row_height, BITMAP_PIXEL_WIDTH, bitmap_row_height, color);
+                    }
+                }
+            }
+        }
+    }
+}
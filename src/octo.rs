@@ -0,0 +1,878 @@
+use std::collections::HashMap;
+
+// An assembler for Octo, the language Octo cartridges carry their programs in:
+//
+//   : main
+//     i := smile
+//     loop
+//       sprite v0 v1 5
+//       v0 += 1
+//       if v0 == 60 then v0 := 0
+//     again
+//   : smile 0x24 0x24 0x00 0x81 0x7E
+//
+// It knows the instructions, labels, `if ... then`, `if ... begin ... else ... end`,
+// `loop ... while ... again`, `:alias`, `:const`, `:calc`, `:macro`, `:org`, `:next`,
+// `:unpack`, `:byte` and `:call`. `:stringmode` and `:assert` are refused. The ROM
+// starts at 0x200 with `main`, or with a jump to it when something comes first.
+
+const START: usize = 0x200;
+const MEMORY_END: usize = 0x10000;
+// A macro that expands into itself would never stop.
+const MAX_EXPANSIONS: usize = 10000;
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        for text in line.split_whitespace() {
+            tokens.push(Token { text: text.to_string(), line: number + 1 });
+        }
+    }
+    tokens
+}
+
+fn number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text)
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn register_number(text: &str) -> Option<u16> {
+    let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u16::from_str_radix(digit, 16).ok()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Register(u16),
+    Value(u8)
+}
+
+// What an `if` or `while` checks, always against a register.
+#[derive(Debug, Clone, Copy)]
+enum Test {
+    Equal(u16, Operand),
+    NotEqual(u16, Operand),
+    Pressed(u16),
+    NotPressed(u16)
+}
+
+impl Test {
+    fn negate(self) -> Test {
+        match self {
+            Test::Equal(x, operand) => Test::NotEqual(x, operand),
+            Test::NotEqual(x, operand) => Test::Equal(x, operand),
+            Test::Pressed(x) => Test::NotPressed(x),
+            Test::NotPressed(x) => Test::Pressed(x)
+        }
+    }
+}
+
+enum Value {
+    Known(i64),
+    // A label that comes later, filled in at the end.
+    Label(String)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FixupKind {
+    // The low 12 bits of an instruction.
+    Address,
+    // Both bytes, for `i := long`.
+    Long,
+    // The byte `:unpack` loads the high part into, after the nibble it was given.
+    High(Option<u8>),
+    Low
+}
+
+struct Fixup {
+    at: usize,
+    label: String,
+    kind: FixupKind,
+    line: usize
+}
+
+// Blocks waiting for their end, with the jumps to point there.
+enum Block {
+    If(usize),
+    Else(usize),
+    Loop { start: usize, breaks: Vec<usize> }
+}
+
+struct Assembler {
+    tokens: Vec<Token>,
+    position: usize,
+    line: usize,
+    rom: Vec<u8>,
+    // Which bytes of the ROM were emitted, :org can move back over them.
+    written: Vec<bool>,
+    here: usize,
+    started: bool,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u16>,
+    macros: HashMap<String, (Vec<String>, Vec<Token>)>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+    expansions: usize
+}
+
+impl Assembler {
+    fn next(&mut self) -> Result<String, String> {
+        let token = self.tokens.get(self.position).ok_or("unexpected end of the program")?;
+        self.line = token.line;
+        self.position += 1;
+        Ok(token.text.clone())
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != text {
+            return Err(format!("expected {} but found {}", text, token));
+        }
+        Ok(())
+    }
+
+    // Programs that do not open with main open with a jump to it.
+    fn start(&mut self) {
+        if !self.started {
+            self.started = true;
+            self.fixups.push(Fixup { at: START, label: "main".to_string(), kind: FixupKind::Address, line: self.line });
+            self.rom.extend([0x10, 0x00]);
+            self.written.extend([true, true]);
+            self.here = START + 2;
+        }
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), String> {
+        self.start();
+        if self.here >= MEMORY_END {
+            return Err("the program does not fit in 64K".to_string());
+        }
+        let offset = self.here - START;
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+            self.written.resize(offset + 1, false);
+        }
+        if self.written[offset] {
+            return Err(format!("{:#X} already holds an earlier byte", self.here));
+        }
+        self.rom[offset] = byte;
+        self.written[offset] = true;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn instruction(&mut self, raw_inst: u16) -> Result<(), String> {
+        self.emit((raw_inst >> 8) as u8)?;
+        self.emit(raw_inst as u8)
+    }
+
+    // Remembers to fill in the label's address `offset` bytes after here.
+    fn reference(&mut self, offset: usize, kind: FixupKind, label: String) {
+        self.start();
+        self.fixups.push(Fixup { at: self.here + offset, label, kind, line: self.line });
+    }
+
+    fn define(&mut self, name: String, address: usize) -> Result<(), String> {
+        if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+            return Err(format!("{} is defined twice", name));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn register(&self, text: &str) -> Option<u16> {
+        self.aliases.get(text).copied().or_else(|| register_number(text))
+    }
+
+    fn expect_register(&mut self) -> Result<u16, String> {
+        let token = self.next()?;
+        self.register(&token).ok_or(format!("expected a register but found {}", token))
+    }
+
+    // A number, constant or label, or the name of a label further down.
+    fn value(&mut self) -> Result<Value, String> {
+        let token = self.next()?;
+        if let Some(number) = number(&token) {
+            return Ok(Value::Known(number));
+        }
+        if let Some(constant) = self.constants.get(&token) {
+            return Ok(Value::Known(constant.floor() as i64));
+        }
+        if let Some(address) = self.labels.get(&token) {
+            return Ok(Value::Known(*address as i64));
+        }
+        if self.register(&token).is_some() || token.starts_with(':') {
+            return Err(format!("expected a value but found {}", token));
+        }
+        Ok(Value::Label(token))
+    }
+
+    fn known(&mut self) -> Result<i64, String> {
+        match self.value()? {
+            Value::Known(value) => Ok(value),
+            Value::Label(name) => Err(format!("{} is not defined yet", name))
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let value = self.known()?;
+        if !(-128..=255).contains(&value) {
+            return Err(format!("{} does not fit in a byte", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u16, String> {
+        let value = self.known()?;
+        if !(0..=15).contains(&value) {
+            return Err(format!("{} does not fit in a nibble", value));
+        }
+        Ok(value as u16)
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        if let Some(y) = self.peek().and_then(|token| self.register(token)) {
+            self.next()?;
+            return Ok(Operand::Register(y));
+        }
+        Ok(Operand::Value(self.byte()?))
+    }
+
+    // An instruction with a 12 bit address, which may be a label further down.
+    fn address_instruction(&mut self, opcode: u16) -> Result<(), String> {
+        match self.value()? {
+            Value::Known(address) => {
+                if !(0..=0xFFF).contains(&address) {
+                    return Err(format!("{:#X} does not fit in 12 bits", address));
+                }
+                self.instruction(opcode | address as u16)
+            },
+            Value::Label(name) => {
+                self.reference(0, FixupKind::Address, name);
+                self.instruction(opcode)
+            }
+        }
+    }
+
+    fn jump_to(&mut self, target: usize) -> Result<(), String> {
+        if target > 0xFFF {
+            return Err(format!("{:#X} is out of reach of a jump", target));
+        }
+        self.instruction(0x1000 | target as u16)
+    }
+
+    // Points the jump placeholder at `at` here.
+    fn patch(&mut self, at: usize) -> Result<(), String> {
+        if self.here > 0xFFF {
+            return Err(format!("{:#X} is out of reach of a jump", self.here));
+        }
+        let offset = at - START;
+        self.rom[offset] = 0x10 | (self.here >> 8) as u8;
+        self.rom[offset + 1] = self.here as u8;
+        Ok(())
+    }
+
+    fn test(&mut self) -> Result<Test, String> {
+        let x = self.expect_register()?;
+        let comparison = self.next()?;
+        match comparison.as_str() {
+            "key" => Ok(Test::Pressed(x)),
+            "-key" => Ok(Test::NotPressed(x)),
+            "==" => Ok(Test::Equal(x, self.operand()?)),
+            "!=" => Ok(Test::NotEqual(x, self.operand()?)),
+            "<" | ">" | "<=" | ">=" => {
+                // Nothing compares directly, so the other side goes into vF and the
+                // borrow of a subtraction decides: 8FX7 sets vF when vX >= it, 8FX5 when vX <= it.
+                match self.operand()? {
+                    Operand::Register(y) => self.instruction(0x8F00 | y << 4)?,
+                    Operand::Value(value) => self.instruction(0x6F00 | value as u16)?
+                }
+                let (subtract, holds) = match comparison.as_str() {
+                    ">=" => (0x7, 1),
+                    "<" => (0x7, 0),
+                    "<=" => (0x5, 1),
+                    _ => (0x5, 0)
+                };
+                self.instruction(0x8F00 | x << 4 | subtract)?;
+                Ok(Test::Equal(0xF, Operand::Value(holds)))
+            },
+            _ => Err(format!("unknown comparison {}", comparison))
+        }
+    }
+
+    // The skip that passes over the next instruction unless the test holds.
+    fn skip_unless(&mut self, test: Test) -> Result<(), String> {
+        let raw_inst = match test {
+            Test::Equal(x, Operand::Value(value)) => 0x4000 | x << 8 | value as u16,
+            Test::Equal(x, Operand::Register(y)) => 0x9000 | x << 8 | y << 4,
+            Test::NotEqual(x, Operand::Value(value)) => 0x3000 | x << 8 | value as u16,
+            Test::NotEqual(x, Operand::Register(y)) => 0x5000 | x << 8 | y << 4,
+            Test::Pressed(x) => 0xE0A1 | x << 8,
+            Test::NotPressed(x) => 0xE09E | x << 8
+        };
+        self.instruction(raw_inst)
+    }
+
+    // A jump to be pointed at the end of a block, returns where it is.
+    fn placeholder(&mut self) -> Result<usize, String> {
+        self.start();
+        let at = self.here;
+        self.instruction(0x1000)?;
+        Ok(at)
+    }
+
+    fn register_statement(&mut self, x: u16) -> Result<(), String> {
+        let operator = self.next()?;
+        let x = x << 8;
+        let register = |assembler: &mut Assembler| assembler.expect_register().map(|y| y << 4);
+        match operator.as_str() {
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    let mask = self.byte()?;
+                    self.instruction(0xC000 | x | mask as u16)
+                },
+                Some("delay") => {
+                    self.next()?;
+                    self.instruction(0xF007 | x)
+                },
+                Some("key") => {
+                    self.next()?;
+                    self.instruction(0xF00A | x)
+                },
+                _ => match self.operand()? {
+                    Operand::Register(y) => self.instruction(0x8000 | x | y << 4),
+                    Operand::Value(value) => self.instruction(0x6000 | x | value as u16)
+                }
+            },
+            "+=" => match self.operand()? {
+                Operand::Register(y) => self.instruction(0x8004 | x | y << 4),
+                Operand::Value(value) => self.instruction(0x7000 | x | value as u16)
+            },
+            "-=" => match self.operand()? {
+                Operand::Register(y) => self.instruction(0x8005 | x | y << 4),
+                Operand::Value(value) => self.instruction(0x7000 | x | value.wrapping_neg() as u16)
+            },
+            "=-" => {
+                let y = register(self)?;
+                self.instruction(0x8007 | x | y)
+            },
+            "|=" | "&=" | "^=" | ">>=" | "<<=" => {
+                let y = register(self)?;
+                let operation = match operator.as_str() {
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    ">>=" => 0x6,
+                    _ => 0xE
+                };
+                self.instruction(0x8000 | x | y | operation)
+            },
+            _ => Err(format!("unknown operator {}", operator))
+        }
+    }
+
+    fn index_statement(&mut self) -> Result<(), String> {
+        let operator = self.next()?;
+        match operator.as_str() {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next()?;
+                    let x = self.expect_register()?;
+                    self.instruction(0xF029 | x << 8)
+                },
+                Some("bighex") => {
+                    self.next()?;
+                    let x = self.expect_register()?;
+                    self.instruction(0xF030 | x << 8)
+                },
+                Some("long") => {
+                    self.next()?;
+                    match self.value()? {
+                        Value::Known(address) => {
+                            if !(0..=0xFFFF).contains(&address) {
+                                return Err(format!("{:#X} does not fit in 16 bits", address));
+                            }
+                            self.instruction(0xF000)?;
+                            self.instruction(address as u16)
+                        },
+                        Value::Label(name) => {
+                            self.instruction(0xF000)?;
+                            self.reference(0, FixupKind::Long, name);
+                            self.instruction(0)
+                        }
+                    }
+                },
+                _ => self.address_instruction(0xA000)
+            },
+            "+=" => {
+                let x = self.expect_register()?;
+                self.instruction(0xF01E | x << 8)
+            },
+            _ => Err(format!("unknown operator i {}", operator))
+        }
+    }
+
+    // `:calc` evaluates right to left without precedence, as Octo does: `2 * 3 + 1` is 8.
+    fn calc(&mut self) -> Result<f64, String> {
+        let left = self.calc_term()?;
+        let operator = match self.peek() {
+            None | Some(")") | Some("}") => return Ok(left),
+            Some(_) => self.next()?
+        };
+        let right = self.calc()?;
+        let (a, b) = (left.floor() as i64, right.floor() as i64);
+        Ok(match operator.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => a.checked_shl(b as u32).unwrap_or(0) as f64,
+            ">>" => a.checked_shr(b as u32).unwrap_or(0) as f64,
+            "<" => (left < right) as i64 as f64,
+            "<=" => (left <= right) as i64 as f64,
+            ">" => (left > right) as i64 as f64,
+            ">=" => (left >= right) as i64 as f64,
+            "==" => (left == right) as i64 as f64,
+            "!=" => (left != right) as i64 as f64,
+            _ => return Err(format!("unknown operator {}", operator))
+        })
+    }
+
+    fn calc_term(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        let unary: Option<fn(f64) -> f64> = match token.as_str() {
+            "-" => Some(|value| -value),
+            "~" => Some(|value| !(value.floor() as i64) as f64),
+            "!" => Some(|value| (value == 0.0) as i64 as f64),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            _ => None
+        };
+        if let Some(unary) = unary {
+            return Ok(unary(self.calc_term()?));
+        }
+
+        match token.as_str() {
+            "(" => {
+                let value = self.calc()?;
+                self.expect(")")?;
+                Ok(value)
+            },
+            "@" => {
+                let address = self.calc_term()?.floor() as usize;
+                Ok(address.checked_sub(START).and_then(|offset| self.rom.get(offset)).copied().unwrap_or(0) as f64)
+            },
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => {
+                if let Some(number) = number(&token) {
+                    Ok(number as f64)
+                } else if let Some(constant) = self.constants.get(&token) {
+                    Ok(*constant)
+                } else if let Some(address) = self.labels.get(&token) {
+                    Ok(*address as f64)
+                } else {
+                    Err(format!("{} is not defined yet", token))
+                }
+            }
+        }
+    }
+
+    fn macro_definition(&mut self) -> Result<(), String> {
+        let name = self.next()?;
+        let mut parameters = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token == "{" {
+                break;
+            }
+            parameters.push(token);
+        }
+
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.tokens.get(self.position).cloned().ok_or(format!("the macro {} is never closed", name))?;
+            self.position += 1;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                },
+                _ => ()
+            }
+            body.push(token);
+        }
+
+        self.macros.insert(name, (parameters, body));
+        Ok(())
+    }
+
+    fn expand(&mut self, parameters: Vec<String>, body: Vec<Token>) -> Result<(), String> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err("the macros expand without end".to_string());
+        }
+
+        let mut arguments = HashMap::new();
+        for parameter in parameters {
+            let argument = self.next()?;
+            arguments.insert(parameter, argument);
+        }
+        let line = self.line;
+        let expanded: Vec<Token> = body.into_iter().map(|token| Token {
+            text: arguments.get(&token.text).cloned().unwrap_or(token.text),
+            line
+        }).collect();
+        self.tokens.splice(self.position..self.position, expanded);
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &str) -> Result<(), String> {
+        match directive {
+            ":" => {
+                let name = self.next()?;
+                if name != "main" || self.started {
+                    self.start();
+                }
+                self.started = true;
+                self.define(name, self.here)
+            },
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.expect_register()?;
+                self.aliases.insert(name, register);
+                Ok(())
+            },
+            ":const" => {
+                let name = self.next()?;
+                let value = self.known()?;
+                if self.labels.contains_key(&name) {
+                    return Err(format!("{} is defined twice", name));
+                }
+                self.constants.insert(name, value as f64);
+                Ok(())
+            },
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.expect("}")?;
+                if self.labels.contains_key(&name) {
+                    return Err(format!("{} is defined twice", name));
+                }
+                self.constants.insert(name, value);
+                Ok(())
+            },
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    self.next()?;
+                    let value = self.calc()?.floor() as i64;
+                    self.expect("}")?;
+                    value
+                } else {
+                    self.known()?
+                };
+                if !(-128..=255).contains(&value) {
+                    return Err(format!("{} does not fit in a byte", value));
+                }
+                self.emit(value as u8)
+            },
+            ":org" => {
+                let address = self.known()?;
+                if !(START as i64..MEMORY_END as i64).contains(&address) {
+                    return Err(format!("{:#X} is outside the program", address));
+                }
+                self.start();
+                self.here = address as usize;
+                Ok(())
+            },
+            ":next" => {
+                let name = self.next()?;
+                self.start();
+                self.define(name, self.here + 1)
+            },
+            ":unpack" => {
+                let prefix = if self.peek() == Some("long") {
+                    self.next()?;
+                    None
+                } else {
+                    Some(self.nibble()? as u8)
+                };
+                let high = self.aliases.get("unpack-hi").copied().unwrap_or(0);
+                let low = self.aliases.get("unpack-lo").copied().unwrap_or(1);
+                match self.value()? {
+                    Value::Known(address) => {
+                        let high_byte = match prefix {
+                            Some(nibble) => nibble << 4 | (address >> 8) as u8 & 0xF,
+                            None => (address >> 8) as u8
+                        };
+                        self.instruction(0x6000 | high << 8 | high_byte as u16)?;
+                        self.instruction(0x6000 | low << 8 | (address & 0xFF) as u16)
+                    },
+                    Value::Label(name) => {
+                        self.reference(1, FixupKind::High(prefix), name.clone());
+                        self.instruction(0x6000 | high << 8)?;
+                        self.reference(1, FixupKind::Low, name);
+                        self.instruction(0x6000 | low << 8)
+                    }
+                }
+            },
+            ":call" => self.address_instruction(0x2000),
+            ":macro" => self.macro_definition(),
+            // Debugger hints for Octo's own interpreter.
+            ":proto" | ":breakpoint" => self.next().map(|_| ()),
+            ":monitor" => {
+                self.next()?;
+                self.next().map(|_| ())
+            },
+            _ => Err(format!("{} is not supported", directive))
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next()?;
+        match token.as_str() {
+            "clear" => self.instruction(0x00E0),
+            "return" | ";" => self.instruction(0x00EE),
+            "hires" => self.instruction(0x00FF),
+            "lores" => self.instruction(0x00FE),
+            "exit" => self.instruction(0x00FD),
+            "scroll-right" => self.instruction(0x00FB),
+            "scroll-left" => self.instruction(0x00FC),
+            "audio" => self.instruction(0xF002),
+            "scroll-down" => {
+                let rows = self.nibble()?;
+                self.instruction(0x00C0 | rows)
+            },
+            "scroll-up" => {
+                let rows = self.nibble()?;
+                self.instruction(0x00D0 | rows)
+            },
+            "plane" => {
+                let planes = self.nibble()?;
+                self.instruction(0xF001 | planes << 8)
+            },
+            "jump" => self.address_instruction(0x1000),
+            "jump0" => self.address_instruction(0xB000),
+            "native" => self.address_instruction(0x0000),
+            "sprite" => {
+                let x = self.expect_register()?;
+                let y = self.expect_register()?;
+                let rows = self.nibble()?;
+                self.instruction(0xD000 | x << 8 | y << 4 | rows)
+            },
+            "save" | "load" => {
+                let x = self.expect_register()?;
+                let load = token == "load";
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.expect_register()?;
+                    self.instruction(0x5002 | x << 8 | y << 4 | load as u16)
+                } else {
+                    self.instruction(if load { 0xF065 } else { 0xF055 } | x << 8)
+                }
+            },
+            "saveflags" | "loadflags" | "bcd" => {
+                let x = self.expect_register()?;
+                let opcode = match token.as_str() {
+                    "saveflags" => 0xF075,
+                    "loadflags" => 0xF085,
+                    _ => 0xF033
+                };
+                self.instruction(opcode | x << 8)
+            },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.expect_register()?;
+                let opcode = match token.as_str() {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A
+                };
+                self.instruction(opcode | x << 8)
+            },
+            "i" => self.index_statement(),
+            "if" => {
+                let test = self.test()?;
+                match self.next()?.as_str() {
+                    "then" => self.skip_unless(test),
+                    "begin" => {
+                        self.skip_unless(test.negate())?;
+                        let jump = self.placeholder()?;
+                        self.blocks.push(Block::If(jump));
+                        Ok(())
+                    },
+                    other => Err(format!("expected then or begin but found {}", other))
+                }
+            },
+            "else" => match self.blocks.pop() {
+                Some(Block::If(jump)) => {
+                    let skip = self.placeholder()?;
+                    self.patch(jump)?;
+                    self.blocks.push(Block::Else(skip));
+                    Ok(())
+                },
+                _ => Err("else without if ... begin".to_string())
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If(jump)) | Some(Block::Else(jump)) => self.patch(jump),
+                _ => Err("end without if ... begin".to_string())
+            },
+            "loop" => {
+                self.start();
+                self.blocks.push(Block::Loop { start: self.here, breaks: Vec::new() });
+                Ok(())
+            },
+            "while" => {
+                let test = self.test()?;
+                self.skip_unless(test.negate())?;
+                let jump = self.placeholder()?;
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { breaks, .. } => Some(breaks),
+                    _ => None
+                }) {
+                    Some(breaks) => {
+                        breaks.push(jump);
+                        Ok(())
+                    },
+                    None => Err("while outside a loop".to_string())
+                }
+            },
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, breaks }) => {
+                    self.jump_to(start)?;
+                    for jump in breaks {
+                        self.patch(jump)?;
+                    }
+                    Ok(())
+                },
+                _ => Err("again without loop".to_string())
+            },
+            _ if token.starts_with(':') => self.directive(&token),
+            _ => {
+                if let Some(x) = self.register(&token) {
+                    return self.register_statement(x);
+                }
+                if let Some((parameters, body)) = self.macros.get(&token).cloned() {
+                    return self.expand(parameters, body);
+                }
+
+                // Bare numbers and constants are data, anything else calls a label.
+                let value = number(&token).or_else(|| self.constants.get(&token).map(|constant| constant.floor() as i64));
+                match (value, self.labels.get(&token).copied()) {
+                    (Some(value), _) if (-128..=255).contains(&value) => self.emit(value as u8),
+                    (Some(value), _) => Err(format!("{} does not fit in a byte", value)),
+                    (None, Some(address)) if address <= 0xFFF => self.instruction(0x2000 | address as u16),
+                    (None, Some(address)) => Err(format!("{:#X} does not fit in 12 bits", address)),
+                    (None, None) => {
+                        self.reference(0, FixupKind::Address, token);
+                        self.instruction(0x2000)
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, String> {
+        if !self.blocks.is_empty() {
+            return Err("a block is never closed with end or again".to_string());
+        }
+        if !self.labels.contains_key("main") {
+            return Err("the program has no main label".to_string());
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let address = *self.labels.get(&fixup.label).ok_or(format!("line {}: {} is never defined", fixup.line, fixup.label))?;
+            let offset = fixup.at - START;
+            match fixup.kind {
+                FixupKind::Address => {
+                    if address > 0xFFF {
+                        return Err(format!("line {}: {} at {:#X} does not fit in 12 bits", fixup.line, fixup.label, address));
+                    }
+                    self.rom[offset] |= (address >> 8) as u8;
+                    self.rom[offset + 1] = address as u8;
+                },
+                FixupKind::Long => {
+                    self.rom[offset] = (address >> 8) as u8;
+                    self.rom[offset + 1] = address as u8;
+                },
+                FixupKind::High(Some(nibble)) => self.rom[offset] = nibble << 4 | (address >> 8) as u8 & 0xF,
+                FixupKind::High(None) => self.rom[offset] = (address >> 8) as u8,
+                FixupKind::Low => self.rom[offset] = address as u8
+            }
+        }
+        Ok(self.rom)
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler {
+        tokens: tokenize(source),
+        position: 0,
+        line: 1,
+        rom: Vec::new(),
+        written: Vec::new(),
+        here: START,
+        started: false,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+        expansions: 0
+    };
+
+    while assembler.position < assembler.tokens.len() {
+        assembler.statement().map_err(|err| format!("line {}: {}", assembler.line, err))?;
+    }
+    assembler.finish()
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::chip8::{self, Instruction};
+
+// Exact execution counters. Every instruction is attributed to the subroutine
+// it ran in, found by shadowing the call stack with the SubCall targets.
+pub struct Profiler {
+    entry_point: usize,
+    pc_counts: HashMap<usize, u64>,
+    class_counts: HashMap<&'static str, u64>,
+    routine_counts: HashMap<usize, u64>,
+    routine_of_pc: HashMap<usize, usize>,
+    backward_jumps: HashMap<(usize, usize), u64>,
+    call_stack: Vec<usize>,
+    frame_instructions: u64,
+    frames: Vec<u64>
+}
+
+impl Profiler {
+    pub fn new(entry_point: usize) -> Profiler {
+        Profiler {
+            entry_point,
+            pc_counts: HashMap::new(),
+            class_counts: HashMap::new(),
+            routine_counts: HashMap::new(),
+            routine_of_pc: HashMap::new(),
+            backward_jumps: HashMap::new(),
+            call_stack: Vec::new(),
+            frame_instructions: 0,
+            frames: Vec::new()
+        }
+    }
+
+    pub fn record(&mut self, pc: usize, instruction: &Instruction, next_pc: usize) {
+        let routine = self.call_stack.last().copied().unwrap_or(self.entry_point);
+
+        *self.pc_counts.entry(pc).or_insert(0) += 1;
+        *self.class_counts.entry(instruction.name()).or_insert(0) += 1;
+        *self.routine_counts.entry(routine).or_insert(0) += 1;
+        self.routine_of_pc.entry(pc).or_insert(routine);
+        self.frame_instructions += 1;
+
+        // Returning to a caller further up is not a loop.
+        let returned = matches!(instruction, Instruction::SubCall { .. } | Instruction::SubReturn);
+        if next_pc <= pc && !returned {
+            *self.backward_jumps.entry((pc, next_pc)).or_insert(0) += 1;
+        }
+
+        match instruction {
+            Instruction::SubCall { target } => self.call_stack.push(*target as usize),
+            Instruction::SubReturn => { self.call_stack.pop(); },
+            _ => {}
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frames.push(self.frame_instructions);
+        self.frame_instructions = 0;
+    }
+
+    fn routine_name(&self, routine: usize) -> String {
+        if routine == self.entry_point {
+            format!("main ({:03X})", routine)
+        } else {
+            format!("sub {:03X}", routine)
+        }
+    }
+
+    pub fn report(&self, memory: &[u8], top: usize) -> String {
+        let total: u64 = self.pc_counts.values().sum();
+        let percent = |n: u64| if total == 0 { 0.0 } else { n as f64 * 100.0 / total as f64 };
+        let mut out = String::new();
+
+        out.push_str(&format!("{} instructions over {} frames\n", total, self.frames.len()));
+        if let (Some(min), Some(max)) = (self.frames.iter().min(), self.frames.iter().max()) {
+            let average = self.frames.iter().sum::<u64>() as f64 / self.frames.len() as f64;
+            out.push_str(&format!("Instructions per frame: min {} avg {:.1} max {}\n", min, average, max));
+        }
+
+        let mut routines: Vec<(&usize, &u64)> = self.routine_counts.iter().collect();
+        routines.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        out.push_str("\nHotspots by subroutine\n");
+        for (routine, count) in routines {
+            out.push_str(&format!("{:<14} {:>10} {:5.1}%\n", self.routine_name(*routine), count, percent(*count)));
+
+            let mut pcs: Vec<(&usize, &u64)> = self.pc_counts.iter()
+                .filter(|(pc, _)| self.routine_of_pc.get(pc) == Some(routine))
+                .collect();
+            pcs.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+            for (pc, count) in pcs.into_iter().take(top) {
+                out.push_str(&format!("  {:03X}  {:>10} {:5.1}%  {}\n", pc, count, percent(*count), disassemble(memory, *pc)));
+            }
+        }
+
+        let mut loops: Vec<(&(usize, usize), &u64)> = self.backward_jumps.iter().collect();
+        loops.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        out.push_str("\nHot loops\n");
+        for ((from, to), count) in loops.into_iter().take(top) {
+            let body: u64 = (*to..=*from).step_by(2).filter_map(|pc| self.pc_counts.get(&pc)).sum();
+            out.push_str(&format!("  {:03X}-{:03X}  taken {:>8}  {:5.1}% of instructions\n", to, from, count, percent(body)));
+        }
+
+        let mut classes: Vec<(&&str, &u64)> = self.class_counts.iter().collect();
+        classes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        out.push_str("\nInstructions by class\n");
+        for (name, count) in classes {
+            out.push_str(&format!("  {:<30} {:>10} {:5.1}%\n", name, count, percent(*count)));
+        }
+
+        out
+    }
+}
+
+fn disassemble(memory: &[u8], pc: usize) -> String {
+    if pc + 1 >= memory.len() {
+        return String::new();
+    }
+
+    let raw_inst = ((memory[pc] as u16) << 8) | memory[pc + 1] as u16;
+    match chip8::decode(raw_inst) {
+        Ok(instruction) => instruction.to_string(),
+        Err(err) => err.to_string()
+    }
+}
@@ -0,0 +1,333 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    chip8::{self, Breakpoint, Expression, Snapshot, VM},
+    runner::Runner
+};
+
+// Stops `continue` on ROMs that never hit a breakpoint, so piped scripts always finish.
+pub const CONTINUE_FRAME_LIMIT: u32 = 60 * 60;
+const DISASSEMBLY_LINES: usize = 10;
+const HEXDUMP_WIDTH: usize = 16;
+
+const HELP: &str = "Commands:
+  step [n]                run n instructions (1)
+  continue                run frames until a breakpoint stops the VM
+  frames <n>              run n frames, timers included
+  break [spec]            list breakpoints, or add one: 2A4, read 300..310, op Draw, when v0 == 1, ... if <cond>, ... log <msg>
+  delete <n>              remove breakpoint n
+  regs                    registers, timers and the stack
+  mem <addr> [len]        hex dump (16 bytes)
+  set <name> <value>      set v0-vf, i, pc, dt, st or [addr]
+  disasm [addr] [n]       disassemble n instructions (10) from addr (pc)
+  screen                  the display as text
+  key <0-F> down|up       press or release a keypad key
+  save [file] / load [file]  snapshot the machine, in memory or to a file
+  quit";
+
+// A gdb-like prompt over any reader and writer. Addresses and values are
+// expressions, so `mem i 8` and `set v0 v1 + 1` work; breakpoints use the
+// breakpoint syntax, where bare addresses are hex.
+pub struct Repl<'a> {
+    runner: &'a mut Runner,
+    saved: Option<Snapshot>
+}
+
+fn evaluate(text: &str, vm: &VM) -> Result<i64, String> {
+    Ok(Expression::parse(text)?.eval(vm, None))
+}
+
+fn in_memory(value: i64, vm: &VM) -> Result<usize, String> {
+    usize::try_from(value).ok().filter(|address| *address < vm.memory.len()).ok_or(format!("{:#X} is outside of memory", value))
+}
+
+fn address(text: &str, vm: &VM) -> Result<usize, String> {
+    in_memory(evaluate(text, vm)?, vm)
+}
+
+fn disassemble(vm: &VM, address: usize) -> String {
+    match (vm.memory.get(address), vm.memory.get(address + 1)) {
+        (Some(high), Some(low)) => {
+            let raw_inst = ((*high as u16) << 8) | *low as u16;
+            let text = chip8::decode(raw_inst).map(|instruction| instruction.disassemble(vm.chip48_mode)).unwrap_or_else(|_| "???".to_string());
+            format!("{:03X}: {:04X}  {}", address, raw_inst, text)
+        },
+        _ => format!("{:03X}: outside of memory", address)
+    }
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(runner: &'a mut Runner) -> Repl<'a> {
+        runner.vm.lock().unwrap().paused = true;
+        Repl { runner, saved: None }
+    }
+
+    // Reads commands until quit or the end of input. With `echo` every command
+    // is written after the prompt, so piped scripts produce a readable transcript.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write, echo: bool) -> io::Result<()> {
+        write!(output, "(chip8) ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            if echo {
+                writeln!(output, "{}", line)?;
+            }
+
+            let command = line.trim();
+            if command == "quit" || command == "q" {
+                return Ok(());
+            }
+            if !command.is_empty() && !command.starts_with('#') {
+                let result = self.execute(command);
+                // Hits and logpoint output from the instructions the command ran
+                for message in self.runner.vm.lock().unwrap().debugger.take_messages() {
+                    writeln!(output, "Debugger: {}", message)?;
+                }
+                match result {
+                    Ok(text) => write!(output, "{}", text)?,
+                    Err(err) => writeln!(output, "Error: {}", err)?
+                }
+            }
+
+            write!(output, "(chip8) ")?;
+            output.flush()?;
+        }
+
+        writeln!(output)
+    }
+
+    // Runs one command and returns what it prints.
+    pub fn execute(&mut self, command: &str) -> Result<String, String> {
+        let (name, arguments) = command.split_once(' ').unwrap_or((command, ""));
+        let arguments = arguments.trim();
+        let words: Vec<&str> = arguments.split_whitespace().collect();
+
+        match name {
+            "step" | "s" => {
+                let count = if arguments.is_empty() { 1 } else { evaluate(arguments, &self.runner.vm.lock().unwrap())? };
+                self.step(count.max(0) as u64)
+            },
+            "continue" | "c" => self.frames(CONTINUE_FRAME_LIMIT, true),
+            "frames" => {
+                let count = evaluate(arguments, &self.runner.vm.lock().unwrap())?;
+                self.frames(count.clamp(0, u32::MAX as i64) as u32, false)
+            },
+            "break" | "b" => self.breakpoint(arguments),
+            "delete" => {
+                let index: usize = arguments.parse().map_err(|_| format!("invalid breakpoint number {}", arguments))?;
+                match self.runner.vm.lock().unwrap().debugger.remove(index) {
+                    Some(breakpoint) => Ok(format!("Deleted {}\n", breakpoint.source)),
+                    None => Err(format!("no breakpoint {}", index))
+                }
+            },
+            "regs" | "r" => Ok(self.registers()),
+            "mem" | "m" => {
+                let vm = self.runner.vm.lock().unwrap();
+                let start = address(words.first().ok_or("mem needs an address")?, &vm)?;
+                let length = match words.get(1) {
+                    Some(length) => evaluate(length, &vm)?.max(0) as usize,
+                    None => HEXDUMP_WIDTH
+                };
+                Ok(Self::hexdump(&vm, start, length))
+            },
+            "set" => {
+                let (target, value) = arguments.split_once(' ').ok_or("set needs a name and a value")?;
+                self.set(target, value)
+            },
+            "disasm" | "d" => {
+                let vm = self.runner.vm.lock().unwrap();
+                let start = match words.first() {
+                    Some(start) => address(start, &vm)?,
+                    None => vm.program_counter
+                };
+                let count = match words.get(1) {
+                    Some(count) => evaluate(count, &vm)?.max(0) as usize,
+                    None => DISASSEMBLY_LINES
+                };
+                Ok((0..count).map(|line| disassemble(&vm, start + line * 2) + "\n").collect())
+            },
+            "screen" => Ok(self.screen()),
+            "key" | "k" => {
+                let key = words.first().and_then(|key| usize::from_str_radix(key, 16).ok()).filter(|key| *key < 16).ok_or("key needs a key from 0 to F")?;
+                let down = match words.get(1).copied() {
+                    Some("down") => true,
+                    Some("up") => false,
+                    _ => return Err("key needs down or up".to_string())
+                };
+                self.runner.vm.lock().unwrap().keyboard.keys[key] = down;
+                Ok(String::new())
+            },
+            "save" => self.save(arguments),
+            "load" => self.load(arguments),
+            "help" | "h" => Ok(format!("{}\n", HELP)),
+            _ => Err(format!("unknown command {}, try help", name))
+        }
+    }
+
+    fn location(vm: &VM) -> String {
+        format!("{}\n", disassemble(vm, vm.program_counter))
+    }
+
+    // Reports why running stopped early, if it did.
+    fn stop_reason(vm: &VM) -> Option<String> {
+        vm.debugger.stopped.map(|index| format!("Breakpoint {} ({}), hit {} times\n", index, vm.debugger.breakpoints[index].source, vm.debugger.breakpoints[index].hits))
+    }
+
+    fn step(&mut self, count: u64) -> Result<String, String> {
+        self.runner.vm.lock().unwrap().paused = false;
+
+        let mut result = Ok(());
+        for _ in 0..count {
+            result = self.runner.step();
+            if result.is_err() || self.runner.vm.lock().unwrap().paused {
+                break;
+            }
+        }
+
+        let mut vm = self.runner.vm.lock().unwrap();
+        vm.paused = true;
+        let mut text = Self::stop_reason(&vm).unwrap_or_default();
+        if let Err(err) = result {
+            text.push_str(&format!("VM error: {}\n", err));
+        }
+        text.push_str(&Self::location(&vm));
+        Ok(text)
+    }
+
+    fn frames(&mut self, count: u32, until_break: bool) -> Result<String, String> {
+        self.runner.vm.lock().unwrap().paused = false;
+
+        let mut result = Ok(());
+        let mut ran = 0;
+        while ran < count {
+            result = self.runner.run_frame();
+            ran += 1;
+            if result.is_err() || self.runner.vm.lock().unwrap().paused {
+                break;
+            }
+        }
+
+        let mut vm = self.runner.vm.lock().unwrap();
+        let stopped = vm.paused;
+        vm.paused = true;
+
+        let mut text = match Self::stop_reason(&vm) {
+            Some(reason) => reason,
+            None if until_break && !stopped => format!("No breakpoint hit after {} frames\n", ran),
+            None => String::new()
+        };
+        if let Err(err) = result {
+            text.push_str(&format!("VM error: {}\n", err));
+        }
+        text.push_str(&Self::location(&vm));
+        Ok(text)
+    }
+
+    fn breakpoint(&mut self, spec: &str) -> Result<String, String> {
+        let mut vm = self.runner.vm.lock().unwrap();
+
+        if spec.is_empty() {
+            let lines: String = vm.debugger.breakpoints.iter().enumerate().map(|(index, breakpoint)| {
+                let disabled = if breakpoint.enabled { "" } else { " (disabled)" };
+                format!("{}: {}, {} hits{}\n", index, breakpoint.source, breakpoint.hits, disabled)
+            }).collect();
+            return Ok(if lines.is_empty() { "No breakpoints\n".to_string() } else { lines });
+        }
+
+        // A bare address is a PC breakpoint, anything else is the full breakpoint syntax.
+        let breakpoint = match Breakpoint::parse(spec) {
+            Ok(breakpoint) => breakpoint,
+            Err(err) => Breakpoint::parse(&format!("pc {}", spec)).map_err(|_| err)?
+        };
+        let source = breakpoint.source.clone();
+        let index = vm.debugger.add(breakpoint);
+        Ok(format!("Breakpoint {}: {}\n", index, source))
+    }
+
+    fn registers(&self) -> String {
+        let vm = self.runner.vm.lock().unwrap();
+        let mut text = String::new();
+        for (index, value) in vm.variable_registers.iter().enumerate() {
+            let separator = if index % 8 == 7 { "\n" } else { " " };
+            text.push_str(&format!("V{:X}={:02X}{}", index, value, separator));
+        }
+        text.push_str(&format!("I={:03X} PC={:03X} DT={:02X} ST={:02X} SP={}\n", vm.index_register, vm.program_counter, vm.delay_timer, vm.sound_timer, vm.stack.top + 1));
+
+        let stack: Vec<String> = vm.stack.data.iter().take((vm.stack.top + 1) as usize).map(|entry| format!("{:03X}", entry)).collect();
+        if !stack.is_empty() {
+            text.push_str(&format!("Stack: {}\n", stack.join(" ")));
+        }
+        text
+    }
+
+    fn hexdump(vm: &VM, start: usize, length: usize) -> String {
+        let end = start.saturating_add(length).min(vm.memory.len());
+        (start..end).step_by(HEXDUMP_WIDTH).map(|row| {
+            let bytes: Vec<String> = vm.memory[row..(row + HEXDUMP_WIDTH).min(end)].iter().map(|byte| format!("{:02X}", byte)).collect();
+            format!("{:03X}: {}\n", row, bytes.join(" "))
+        }).collect()
+    }
+
+    fn set(&mut self, target: &str, value: &str) -> Result<String, String> {
+        let mut vm = self.runner.vm.lock().unwrap();
+        let value = evaluate(value, &vm)?;
+        let target = target.trim().to_ascii_lowercase();
+
+        match target.as_str() {
+            "i" => vm.index_register = value as u16,
+            "pc" => vm.program_counter = in_memory(value, &vm)?,
+            "dt" => vm.delay_timer = value as u8,
+            "st" => vm.sound_timer = value as u8,
+            _ => {
+                if let Some(inner) = target.strip_prefix('[').and_then(|target| target.strip_suffix(']')) {
+                    let address = address(inner, &vm)?;
+                    vm.memory[address] = value as u8;
+                } else {
+                    let register = target.strip_prefix('v')
+                        .filter(|digit| digit.len() == 1)
+                        .and_then(|digit| usize::from_str_radix(digit, 16).ok())
+                        .ok_or(format!("unknown name {}, expected v0-vf, i, pc, dt, st or [addr]", target))?;
+                    vm.variable_registers[register] = value as u8;
+                }
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn screen(&self) -> String {
+        let vm = self.runner.vm.lock().unwrap();
+        let mut text = String::new();
+        for y in 0..chip8::DISPLAY_HEIGHT {
+            for x in 0..chip8::DISPLAY_WIDTH {
+                text.push(if vm.display.get(x, y) { '#' } else { '.' });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    fn save(&mut self, path: &str) -> Result<String, String> {
+        let snapshot = self.runner.vm.lock().unwrap().snapshot();
+        if path.is_empty() {
+            self.saved = Some(snapshot);
+            return Ok("Saved\n".to_string());
+        }
+
+        std::fs::write(path, snapshot.to_bytes()).map_err(|err| format!("could not write {}: {}", path, err))?;
+        Ok(format!("Saved {}\n", path))
+    }
+
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        let snapshot = if path.is_empty() {
+            self.saved.clone().ok_or("nothing saved yet")?
+        } else {
+            let bytes = std::fs::read(path).map_err(|err| format!("could not read {}: {}", path, err))?;
+            Snapshot::from_bytes(&bytes)?
+        };
+
+        let mut vm = self.runner.vm.lock().unwrap();
+        vm.restore(&snapshot)?;
+        Ok(Self::location(&vm))
+    }
+}
@@ -0,0 +1,186 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    chip8::{VM, VMError},
+    frontend::{VideoSink, AudioSink, InputSource, Clock},
+    scripting::ScriptHost,
+    trace::{self, Tracer},
+    profile::Profiler,
+    capture::Recorder,
+    cheats::CheatList
+};
+
+// About 540 instructions per second at 60Hz, close to what the old 2ms tick thread managed.
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 9;
+pub const MAX_INSTRUCTIONS_PER_FRAME: u32 = 10000;
+
+fn record_trace(tracer: &mut Option<Tracer>, line: Option<String>) {
+    if let (Some(writer), Some(line)) = (tracer.as_mut(), line) {
+        if let Err(err) = writer.record(&line) {
+            println!("Error writing trace, stopping it: {}", err);
+            *tracer = None;
+        }
+    }
+}
+
+// The run loop shared by every frontend. The VM stays behind a mutex because
+// script functions lock it themselves, so hooks run with the lock released.
+pub struct Runner {
+    pub vm: Arc<Mutex<VM>>,
+    pub scripts: Option<ScriptHost>,
+    pub tracer: Option<Tracer>,
+    pub profiler: Option<Profiler>,
+    pub recorder: Option<Recorder>,
+    pub cheats: Option<CheatList>,
+    pub instructions_per_frame: u32
+}
+
+impl Runner {
+    pub fn new(vm: Arc<Mutex<VM>>) -> Runner {
+        Runner {
+            vm,
+            scripts: None,
+            tracer: None,
+            profiler: None,
+            recorder: None,
+            cheats: None,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME
+        }
+    }
+
+    // Clamped so the VM always makes progress and a frame can't take seconds.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.clamp(1, MAX_INSTRUCTIONS_PER_FRAME);
+    }
+
+    // Runs a single instruction and then lets the scripts observe it.
+    // Errors pause the VM so the state can still be inspected.
+    pub fn step(&mut self) -> Result<(), VMError> {
+        let (pc, writes) = {
+            let mut vm_lock = self.vm.lock().unwrap();
+            if vm_lock.paused {
+                return Ok(());
+            }
+
+            // Written once the instruction has run, a breakpoint may stop it first.
+            let line = self.tracer.as_ref().map(|_| trace::format_line(&vm_lock));
+            let pc = vm_lock.program_counter;
+            let instruction = vm_lock.fetch();
+            let result = vm_lock.tick();
+
+            if let Some(sanitizer) = &mut vm_lock.sanitizer {
+                for warning in sanitizer.take_warnings() {
+                    println!("Sanitizer: {}", warning);
+                }
+            }
+
+            if let Err(err) = result {
+                record_trace(&mut self.tracer, line);
+                vm_lock.paused = true;
+                return Err(err);
+            }
+
+            // A breakpoint stopped the VM before the instruction ran.
+            if vm_lock.debugger.stopped.is_some() && vm_lock.program_counter == pc {
+                return Ok(());
+            }
+            record_trace(&mut self.tracer, line);
+
+            if let (Some(profiler), Ok(instruction)) = (&mut self.profiler, &instruction) {
+                profiler.record(pc, instruction, vm_lock.program_counter);
+            }
+            (pc, std::mem::take(&mut vm_lock.memory_writes))
+        };
+
+        if let Some(scripts) = &self.scripts {
+            scripts.on_instruction(pc);
+            self.report_writes(writes);
+        }
+
+        Ok(())
+    }
+
+    // Hands the instruction's writes and those on_instruction or on_frame made through
+    // set_mem to on_memory_write. Writes made by on_memory_write itself are not reported,
+    // so a handler writing memory can't keep calling itself.
+    fn report_writes(&self, mut writes: Vec<usize>) {
+        if let Some(scripts) = &self.scripts {
+            writes.append(&mut self.vm.lock().unwrap().memory_writes);
+            for address in writes {
+                scripts.on_memory_write(address);
+            }
+            self.vm.lock().unwrap().memory_writes.clear();
+        }
+    }
+
+    // One 60Hz frame: the cheats, the instruction budget, then the timers. Paused frames are not recorded.
+    pub fn run_frame(&mut self) -> Result<(), VMError> {
+        if let Some(cheats) = &self.cheats {
+            cheats.apply(&mut self.vm.lock().unwrap());
+        }
+
+        let mut result = Ok(());
+        for _ in 0..self.instructions_per_frame {
+            let pc = self.vm.lock().unwrap().program_counter;
+            if let Err(err) = self.step() {
+                println!("VM error at {:03X}: {}, pausing", pc, err);
+                result = Err(err);
+                break;
+            }
+        }
+
+        {
+            let mut vm_lock = self.vm.lock().unwrap();
+            if !vm_lock.paused {
+                vm_lock.tick_timers();
+                vm_lock.end_frame();
+
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.end_frame();
+                }
+
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.record(&vm_lock.display);
+                }
+            }
+        }
+
+        if let Some(scripts) = &self.scripts {
+            scripts.on_frame();
+            self.report_writes(Vec::new());
+        }
+
+        result
+    }
+
+    // Runs exactly one frame of a paused VM and pauses it again.
+    pub fn advance_frame(&mut self) -> Result<(), VMError> {
+        self.vm.lock().unwrap().paused = false;
+        let result = self.run_frame();
+        self.vm.lock().unwrap().paused = true;
+        result
+    }
+
+    // Emulates however many frames the clock asks for and hands the result to the frontend.
+    pub fn update(
+        &mut self,
+        video: &mut dyn VideoSink,
+        audio: &mut dyn AudioSink,
+        input: &mut dyn InputSource,
+        clock: &mut dyn Clock
+    ) -> Result<(), VMError> {
+        let mut result = Ok(());
+        for _ in 0..clock.frames_due() {
+            input.poll(&mut self.vm.lock().unwrap().keyboard);
+            if let Err(err) = self.run_frame() {
+                result = Err(err);
+            }
+        }
+
+        let vm_lock = self.vm.lock().unwrap();
+        video.present(&vm_lock.display);
+        audio.set_tone(vm_lock.sound_timer > 0 && !vm_lock.paused);
+
+        result
+    }
+}
@@ -0,0 +1,162 @@
+use std::{sync::{Arc, Mutex}, path::Path};
+
+use rhai::{Engine, AST, Scope, EvalAltResult, CallFnOptions, Dynamic, INT};
+
+use crate::chip8::{self, VM};
+
+// Scripts are plain Rhai files that may define any of these functions.
+// They run without the VM lock held, the registered functions take it themselves.
+const ON_FRAME: &str = "on_frame";
+const ON_INSTRUCTION: &str = "on_instruction";
+const ON_MEMORY_WRITE: &str = "on_memory_write";
+
+pub struct OverlayText {
+    pub x: f32,
+    pub y: f32,
+    pub text: String
+}
+
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    scope: Mutex<Scope<'static>>,
+    overlay: Arc<Mutex<Vec<OverlayText>>>,
+    has_on_frame: bool,
+    has_on_instruction: bool,
+    has_on_memory_write: bool
+}
+
+fn register_index(index: INT) -> Result<usize, Box<EvalAltResult>> {
+    if (0..chip8::VREG_COUNT as INT).contains(&index) {
+        Ok(index as usize)
+    } else {
+        Err(format!("register V{:X} does not exist", index).into())
+    }
+}
+
+fn memory_address(address: INT, memory_size: usize) -> Result<usize, Box<EvalAltResult>> {
+    if (0..memory_size as INT).contains(&address) {
+        Ok(address as usize)
+    } else {
+        Err(format!("address {:#X} is outside of memory", address).into())
+    }
+}
+
+fn key_index(key: INT) -> Result<usize, Box<EvalAltResult>> {
+    if (0..16).contains(&key) {
+        Ok(key as usize)
+    } else {
+        Err(format!("key {:X} does not exist", key).into())
+    }
+}
+
+impl ScriptHost {
+    pub fn load(path: &Path, vm: Arc<Mutex<VM>>) -> Result<ScriptHost, Box<EvalAltResult>> {
+        let mut engine = Engine::new();
+        let overlay = Arc::new(Mutex::new(Vec::new()));
+
+        let v = vm.clone();
+        engine.register_fn("reg", move |index: INT| -> Result<INT, Box<EvalAltResult>> {
+            Ok(v.lock().unwrap().variable_registers[register_index(index)?] as INT)
+        });
+        let v = vm.clone();
+        engine.register_fn("set_reg", move |index: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            v.lock().unwrap().variable_registers[register_index(index)?] = value as u8;
+            Ok(())
+        });
+        let v = vm.clone();
+        engine.register_fn("mem", move |address: INT| -> Result<INT, Box<EvalAltResult>> {
+            let vm = v.lock().unwrap();
+            Ok(vm.memory[memory_address(address, vm.memory.len())?] as INT)
+        });
+        let v = vm.clone();
+        engine.register_fn("set_mem", move |address: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut vm = v.lock().unwrap();
+            let address = memory_address(address, vm.memory.len())?;
+            vm.poke(address, value as u8).map_err(|err| err.to_string().into())
+        });
+        let v = vm.clone();
+        engine.register_fn("key", move |key: INT| -> Result<bool, Box<EvalAltResult>> {
+            Ok(v.lock().unwrap().keyboard.keys[key_index(key)?])
+        });
+        let v = vm.clone();
+        engine.register_fn("set_key", move |key: INT, down: bool| -> Result<(), Box<EvalAltResult>> {
+            v.lock().unwrap().keyboard.keys[key_index(key)?] = down;
+            Ok(())
+        });
+        let v = vm.clone();
+        engine.register_fn("pc", move || v.lock().unwrap().program_counter as INT);
+        let v = vm.clone();
+        engine.register_fn("set_pc", move |address: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut vm = v.lock().unwrap();
+            vm.program_counter = memory_address(address, vm.memory.len())?;
+            Ok(())
+        });
+        let v = vm.clone();
+        engine.register_fn("index", move || v.lock().unwrap().index_register as INT);
+        let v = vm.clone();
+        engine.register_fn("set_index", move |value: INT| v.lock().unwrap().index_register = value as u16);
+        let v = vm.clone();
+        engine.register_fn("delay_timer", move || v.lock().unwrap().delay_timer as INT);
+        let v = vm.clone();
+        engine.register_fn("sound_timer", move || v.lock().unwrap().sound_timer as INT);
+        let v = vm.clone();
+        engine.register_fn("pause", move || v.lock().unwrap().paused = true);
+        let v = vm.clone();
+        engine.register_fn("resume", move || v.lock().unwrap().paused = false);
+        let v = vm;
+        engine.register_fn("is_paused", move || v.lock().unwrap().paused);
+        let o = overlay.clone();
+        engine.register_fn("draw_text", move |x: INT, y: INT, text: &str| {
+            o.lock().unwrap().push(OverlayText { x: x as f32, y: y as f32, text: text.to_string() });
+        });
+
+        let ast = engine.compile_file(path.to_path_buf())?;
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast)?;
+
+        let has_function = |name: &str| ast.iter_functions().any(|f| f.name == name);
+
+        Ok(ScriptHost {
+            has_on_frame: has_function(ON_FRAME),
+            has_on_instruction: has_function(ON_INSTRUCTION),
+            has_on_memory_write: has_function(ON_MEMORY_WRITE),
+            engine,
+            ast,
+            scope: Mutex::new(scope),
+            overlay
+        })
+    }
+
+    fn call(&self, name: &str, args: impl rhai::FuncArgs) {
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(false);
+        let mut scope = self.scope.lock().unwrap();
+
+        if let Err(err) = self.engine.call_fn_with_options::<Dynamic>(options, &mut scope, &self.ast, name, args) {
+            println!("Script error in {}: {}", name, err);
+        }
+    }
+
+    pub fn on_frame(&self) {
+        if self.has_on_frame {
+            self.overlay.lock().unwrap().clear();
+            self.call(ON_FRAME, ());
+        }
+    }
+
+    pub fn on_instruction(&self, pc: usize) {
+        if self.has_on_instruction {
+            self.call(ON_INSTRUCTION, (pc as INT,));
+        }
+    }
+
+    pub fn on_memory_write(&self, address: usize) {
+        if self.has_on_memory_write {
+            self.call(ON_MEMORY_WRITE, (address as INT,));
+        }
+    }
+
+    pub fn overlay(&self) -> std::sync::MutexGuard<'_, Vec<OverlayText>> {
+        self.overlay.lock().unwrap()
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+
+use rhai::{Dynamic, Engine, Map};
+
+use crate::{chip8::{FontSet, Quirks}, capture::Palette};
+
+// The keyboard key for each CHIP-8 key 0-F, written as the 16 characters on them in
+// that order. The default is the hex keypad on the left side of a QWERTY keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keymap(pub [char; 16]);
+
+impl Keymap {
+    // Letters and digits only, each used once.
+    pub fn parse(text: &str) -> Option<Keymap> {
+        let keys: Vec<char> = text.trim().chars().map(|c| c.to_ascii_uppercase()).collect();
+        if keys.len() != 16 || !keys.iter().all(char::is_ascii_alphanumeric) {
+            return None;
+        }
+        if keys.iter().enumerate().any(|(index, key)| keys[..index].contains(key)) {
+            return None;
+        }
+
+        let mut keymap = [' '; 16];
+        keymap.copy_from_slice(&keys);
+        Some(Keymap(keymap))
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::parse("X123QWEASDZC4RFV").unwrap()
+    }
+}
+
+impl fmt::Display for Keymap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|key| write!(f, "{}", key))
+    }
+}
+
+// What one ROM wants, each setting `None` when it has no preference. Stored per ROM
+// hash one setting per line, e.g.
+//
+//   quirks schip+vf_reset
+//   ipf 30
+//   palette 000000,FFCC00
+//   keymap X123QWEASDZC4RFV
+//   font chip48
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RomSettings {
+    pub quirks: Option<Quirks>,
+    pub instructions_per_frame: Option<u32>,
+    pub palette: Option<Palette>,
+    pub keymap: Option<Keymap>,
+    pub font: Option<FontSet>
+}
+
+impl RomSettings {
+    pub fn parse(text: &str) -> Result<RomSettings, String> {
+        let mut settings = RomSettings::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let value = value.trim();
+            let invalid = || format!("line {}: invalid {} {}", number + 1, name, value);
+            match name {
+                "quirks" => settings.quirks = Some(Quirks::parse(value).map_err(|err| format!("line {}: {}", number + 1, err))?),
+                "ipf" => settings.instructions_per_frame = Some(value.parse().map_err(|_| invalid())?),
+                "palette" => settings.palette = Some(Palette::parse(value).ok_or_else(invalid)?),
+                "keymap" => settings.keymap = Some(Keymap::parse(value).ok_or_else(invalid)?),
+                "font" => settings.font = Some(FontSet::from_name(value).ok_or_else(invalid)?),
+                _ => return Err(format!("line {}: unknown setting {}", number + 1, name))
+            }
+        }
+
+        Ok(settings)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if let Some(quirks) = &self.quirks {
+            text.push_str(&format!("quirks {}\n", quirks.spec()));
+        }
+        if let Some(instructions_per_frame) = self.instructions_per_frame {
+            text.push_str(&format!("ipf {}\n", instructions_per_frame));
+        }
+        if let Some(palette) = &self.palette {
+            text.push_str(&format!("palette {}\n", palette));
+        }
+        if let Some(keymap) = &self.keymap {
+            text.push_str(&format!("keymap {}\n", keymap));
+        }
+        if let Some(font) = &self.font {
+            text.push_str(&format!("font {}\n", font.name()));
+        }
+        text
+    }
+
+    // These settings with the gaps filled from `other`.
+    pub fn or(self, other: RomSettings) -> RomSettings {
+        RomSettings {
+            quirks: self.quirks.or(other.quirks),
+            instructions_per_frame: self.instructions_per_frame.or(other.instructions_per_frame),
+            palette: self.palette.or(other.palette),
+            keymap: self.keymap.or(other.keymap),
+            font: self.font.or(other.font)
+        }
+    }
+}
+
+// An Octo-style options file kept next to the ROM with the same name and a .json
+// extension. The options can sit at the top level, as Octo writes them, or under
+// "options" as in the CHIP-8 archive, next to the title, author and description.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub settings: RomSettings
+}
+
+fn string(map: &Map, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| {
+        let value = map.get(*key)?;
+        if let Some(array) = value.read_lock::<rhai::Array>() {
+            let names: Vec<String> = array.iter().filter_map(|item| item.clone().into_string().ok()).collect();
+            return Some(names.join(", "));
+        }
+        value.clone().into_string().ok()
+    })
+}
+
+fn flag(map: &Map, key: &str) -> Option<bool> {
+    map.get(key).and_then(|value| value.as_bool().ok())
+}
+
+// Octo names its fonts after where they came from too, apart from its own.
+fn octo_font(name: &str) -> Option<FontSet> {
+    match name {
+        "schip" => Some(FontSet::Chip48),
+        "fish" => Some(FontSet::FishNChips),
+        _ => FontSet::from_name(name)
+    }
+}
+
+fn octo_font_name(font: FontSet) -> &'static str {
+    match font {
+        FontSet::Chip48 => "schip",
+        FontSet::FishNChips => "fish",
+        _ => font.name()
+    }
+}
+
+impl Manifest {
+    pub fn parse(json: &str) -> Result<Manifest, String> {
+        let root = Engine::new_raw().parse_json(json, true).map_err(|err| err.to_string())?;
+        let options = match root.get("options").and_then(|options| options.read_lock::<Map>().map(|map| map.clone())) {
+            Some(options) => options,
+            None => root.clone()
+        };
+
+        Ok(Manifest {
+            title: string(&root, &["title", "name"]),
+            author: string(&root, &["author", "authors"]),
+            description: string(&root, &["description", "desc"]),
+            settings: Manifest::settings(&options)?
+        })
+    }
+
+    fn settings(options: &Map) -> Result<RomSettings, String> {
+        let mut settings = RomSettings::default();
+
+        // Octo leaves every quirk off by default, which is what the XO-CHIP preset is.
+        // Its load/store quirk is the opposite of ours, it stops I from moving.
+        let octo_quirks = ["shiftQuirks", "jumpQuirks", "loadStoreQuirks", "logicQuirks", "clipQuirks"];
+        if octo_quirks.iter().any(|key| options.contains_key(*key)) {
+            let mut quirks = Quirks::xo_chip();
+            quirks.shift_legacy = flag(options, "shiftQuirks").unwrap_or(quirks.shift_legacy);
+            quirks.chip48_mode = flag(options, "jumpQuirks").unwrap_or(quirks.chip48_mode);
+            quirks.memory_increment = flag(options, "loadStoreQuirks").map_or(quirks.memory_increment, |quirk| !quirk);
+            quirks.vf_reset = flag(options, "logicQuirks").unwrap_or(quirks.vf_reset);
+            quirks.clip_sprites = flag(options, "clipQuirks").unwrap_or(quirks.clip_sprites);
+            settings.quirks = Some(quirks);
+        }
+
+        if let Some(tickrate) = options.get("tickrate") {
+            let tickrate = tickrate.as_int().map_err(|_| "tickrate is not a number".to_string())?;
+            settings.instructions_per_frame = Some(u32::try_from(tickrate).map_err(|_| format!("invalid tickrate {}", tickrate))?);
+        }
+
+        if let (Some(off), Some(on)) = (string(options, &["backgroundColor"]), string(options, &["fillColor"])) {
+            settings.palette = Some(Palette::parse(&format!("{},{}", off, on)).ok_or(format!("invalid colors {} and {}", off, on))?);
+        }
+
+        if let Some(keymap) = string(options, &["keymap"]) {
+            settings.keymap = Some(Keymap::parse(&keymap).ok_or(format!("invalid keymap {}", keymap))?);
+        }
+
+        settings.font = string(options, &["fontStyle"]).and_then(|name| octo_font(&name));
+        Ok(settings)
+    }
+
+    // The JSON object `parse` reads back, with the settings Octo has options for.
+    pub fn to_map(&self) -> Map {
+        let mut options = Map::new();
+        if let Some(quirks) = self.settings.quirks {
+            options.insert("shiftQuirks".into(), Dynamic::from_bool(quirks.shift_legacy));
+            options.insert("jumpQuirks".into(), Dynamic::from_bool(quirks.chip48_mode));
+            options.insert("loadStoreQuirks".into(), Dynamic::from_bool(!quirks.memory_increment));
+            options.insert("logicQuirks".into(), Dynamic::from_bool(quirks.vf_reset));
+            options.insert("clipQuirks".into(), Dynamic::from_bool(quirks.clip_sprites));
+        }
+
+        if let Some(instructions_per_frame) = self.settings.instructions_per_frame {
+            options.insert("tickrate".into(), Dynamic::from_int(instructions_per_frame as rhai::INT));
+        }
+        if let Some(palette) = self.settings.palette {
+            let text = palette.to_string();
+            let (off, on) = text.split_once(',').unwrap();
+            options.insert("backgroundColor".into(), format!("#{}", off).into());
+            options.insert("fillColor".into(), format!("#{}", on).into());
+        }
+        if let Some(font) = self.settings.font {
+            options.insert("fontStyle".into(), octo_font_name(font).into());
+        }
+
+        let mut map = Map::new();
+        for (key, value) in [("title", &self.title), ("author", &self.author), ("description", &self.description)] {
+            if let Some(value) = value {
+                map.insert(key.into(), value.clone().into());
+            }
+        }
+        map.insert("options".into(), options.into());
+        map
+    }
+}
@@ -0,0 +1,168 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use macroquad::{prelude::*, ui::{hash, root_ui, widgets}};
+
+use chip8_rs::{
+    chip8::{FontSet, Quirks, VM, RPL_FLAG_COUNT},
+    capture::Palette,
+    settings::{Keymap, Manifest, RomSettings}
+};
+
+pub fn load(path: &Path) -> Result<RomSettings, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => RomSettings::parse(&text),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(RomSettings::default()),
+        Err(err) => Err(err.to_string())
+    }
+}
+
+fn save(path: &Path, settings: &RomSettings) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, settings.to_text())
+}
+
+// Edits the settings in effect for the running ROM. Apply changes them for this
+// session, Save also stores them under the ROM's hash for the next launch.
+pub struct SettingsPanel {
+    pub open: bool,
+    path: PathBuf,
+    manifest: Option<Manifest>,
+    quirks: Quirks,
+    instructions_per_frame: String,
+    palette: String,
+    keymap: String,
+    font: usize,
+    message: Option<String>
+}
+
+impl SettingsPanel {
+    // `current` has every setting filled in.
+    pub fn new(path: PathBuf, current: &RomSettings, manifest: Option<Manifest>) -> SettingsPanel {
+        let font = current.font.unwrap_or_default();
+        SettingsPanel {
+            open: false,
+            path,
+            manifest,
+            quirks: current.quirks.unwrap_or_default(),
+            instructions_per_frame: current.instructions_per_frame.unwrap_or_default().to_string(),
+            palette: current.palette.unwrap_or_default().to_string(),
+            keymap: current.keymap.unwrap_or_default().to_string(),
+            font: FontSet::ALL.iter().position(|set| *set == font).unwrap_or(0),
+            message: None
+        }
+    }
+
+    fn settings(&self) -> Result<RomSettings, String> {
+        Ok(RomSettings {
+            quirks: Some(self.quirks),
+            instructions_per_frame: Some(self.instructions_per_frame.trim().parse().map_err(|_| "Invalid instructions per frame")?),
+            palette: Some(Palette::parse(&self.palette).ok_or("Invalid palette, expected RRGGBB,RRGGBB")?),
+            keymap: Some(Keymap::parse(&self.keymap).ok_or("Invalid keymap, expected 16 different letters or digits for keys 0-F")?),
+            font: Some(FontSet::ALL[self.font])
+        })
+    }
+
+    // The settings to switch to when Apply or Save was clicked. Clearing the RPL
+    // flags happens right away, they are saved like any other change to them.
+    pub fn update(&mut self, vm: &mut VM) -> Option<RomSettings> {
+        if !self.open {
+            return None;
+        }
+
+        let mut apply = false;
+        let mut store = false;
+        let mut forget = false;
+        let mut clear_flags = false;
+
+        widgets::Window::new(hash!(), vec2(10.0, 10.0), vec2(420.0, 420.0))
+            .label("ROM settings")
+            .ui(&mut root_ui(), |ui| {
+                if let Some(manifest) = &self.manifest {
+                    if let Some(title) = &manifest.title {
+                        ui.label(None, title);
+                    }
+                    if let Some(author) = &manifest.author {
+                        ui.label(None, &format!("by {}", author));
+                    }
+                    if let Some(description) = &manifest.description {
+                        for line in description.lines() {
+                            ui.label(None, line);
+                        }
+                    }
+                    ui.separator();
+                }
+
+                ui.label(None, &format!("Quirks: {}", self.quirks.name()));
+                ui.checkbox(hash!(), "shift_legacy", &mut self.quirks.shift_legacy);
+                ui.checkbox(hash!(), "chip48_mode", &mut self.quirks.chip48_mode);
+                ui.checkbox(hash!(), "vf_reset", &mut self.quirks.vf_reset);
+                ui.checkbox(hash!(), "memory_increment", &mut self.quirks.memory_increment);
+                ui.checkbox(hash!(), "clip_sprites", &mut self.quirks.clip_sprites);
+                ui.label(None, "Presets");
+                for (name, quirks) in Quirks::all() {
+                    ui.same_line(0.0);
+                    if ui.button(None, name) {
+                        self.quirks = quirks;
+                    }
+                }
+                ui.separator();
+
+                ui.input_text(hash!(), "Instructions per frame", &mut self.instructions_per_frame);
+                ui.input_text(hash!(), "Palette", &mut self.palette);
+                ui.input_text(hash!(), "Keymap 0-F", &mut self.keymap);
+                let fonts: Vec<&str> = FontSet::ALL.iter().map(|font| font.name()).collect();
+                ui.combo_box(hash!(), "Font", &fonts, &mut self.font);
+
+                apply = ui.button(None, "Apply");
+                ui.same_line(0.0);
+                store = ui.button(None, "Save for this ROM");
+                ui.same_line(0.0);
+                forget = ui.button(None, "Forget saved");
+
+                ui.separator();
+                let flags: Vec<String> = vm.rpl_flags[..vm.platform.rpl_flags].iter().map(|flag| format!("{:02X}", flag)).collect();
+                ui.label(None, &format!("RPL flags: {}", flags.join(" ")));
+                clear_flags = ui.button(None, "Clear RPL flags");
+
+                if let Some(message) = &self.message {
+                    ui.label(None, message);
+                }
+            });
+
+        if clear_flags {
+            vm.rpl_flags = [0; RPL_FLAG_COUNT];
+            vm.rpl_flags_written = true;
+        }
+
+        if forget {
+            self.message = match fs::remove_file(&self.path) {
+                Ok(()) => Some("Saved settings removed".to_string()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Some("Nothing saved".to_string()),
+                Err(err) => Some(format!("Error removing {}: {}", self.path.display(), err))
+            };
+        }
+
+        if !apply && !store {
+            return None;
+        }
+
+        let settings = match self.settings() {
+            Ok(settings) => settings,
+            Err(err) => {
+                self.message = Some(err);
+                return None;
+            }
+        };
+
+        self.message = None;
+        if store {
+            self.message = Some(match save(&self.path, &settings) {
+                Ok(()) => "Saved".to_string(),
+                Err(err) => format!("Error saving {}: {}", self.path.display(), err)
+            });
+        }
+        Some(settings)
+    }
+}
@@ -0,0 +1,75 @@
+use crate::frontend::Clock;
+
+pub const DEFAULT_FAST_FORWARD: u32 = 4;
+// Past this a real frame takes longer to emulate than it lasts, so more isn't faster.
+pub const MAX_FAST_FORWARD: u32 = 64;
+pub const SLOW_MOTION_DIVISORS: [u32; 4] = [1, 2, 4, 8];
+
+// Wraps the frontend clock to run more or fewer emulated frames per real one.
+// Fast-forward multiplies the frames due, slow motion only lets every Nth through.
+pub struct SpeedClock<C: Clock> {
+    pub clock: C,
+    pub fast_forward: bool,
+    pub fast_forward_factor: u32,
+    slow_motion: usize,
+    slow_frames: u32
+}
+
+impl<C: Clock> SpeedClock<C> {
+    pub fn new(clock: C) -> SpeedClock<C> {
+        SpeedClock {
+            clock,
+            fast_forward: false,
+            fast_forward_factor: DEFAULT_FAST_FORWARD,
+            slow_motion: 0,
+            slow_frames: 0
+        }
+    }
+
+    pub fn divisor(&self) -> u32 {
+        SLOW_MOTION_DIVISORS[self.slow_motion]
+    }
+
+    pub fn slower(&mut self) {
+        self.slow_motion = (self.slow_motion + 1).min(SLOW_MOTION_DIVISORS.len() - 1);
+    }
+
+    pub fn faster(&mut self) {
+        self.slow_motion = self.slow_motion.saturating_sub(1);
+        self.slow_frames = 0;
+    }
+
+    // A single line for the status overlay.
+    pub fn status(&self, instructions_per_frame: u32, paused: bool) -> String {
+        let mut status = format!("{} ipf", instructions_per_frame);
+
+        if self.fast_forward {
+            status += &format!("  x{}", self.fast_forward_factor);
+        }
+
+        if self.divisor() > 1 {
+            status += &format!("  1/{}", self.divisor());
+        }
+
+        if paused {
+            status += "  PAUSED";
+        }
+
+        status
+    }
+}
+
+impl<C: Clock> Clock for SpeedClock<C> {
+    fn frames_due(&mut self) -> u32 {
+        let frames = self.clock.frames_due();
+
+        if self.fast_forward {
+            return frames.saturating_mul(self.fast_forward_factor);
+        }
+
+        self.slow_frames += frames;
+        let due = self.slow_frames / self.divisor();
+        self.slow_frames %= self.divisor();
+        due
+    }
+}
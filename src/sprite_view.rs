@@ -0,0 +1,193 @@
+use macroquad::prelude::*;
+
+use crate::chip8::VM;
+
+const FONT_SIZE: u16 = 16;
+const ROW_HEIGHT: f32 = 16.0;
+const HEADER_ROWS: usize = 2;
+const PIXEL_SIZE: f32 = 3.0;
+const CELL_PADDING: f32 = 8.0;
+const MAX_HEIGHT: usize = 15;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    // One byte per row, N rows, like DXYN.
+    Narrow(usize),
+    // Two bytes per row, 16 rows, like the SCHIP DXY0.
+    Wide
+}
+
+impl Mode {
+    fn width(&self) -> usize {
+        match self {
+            Mode::Narrow(_) => 8,
+            Mode::Wide => 16
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Mode::Narrow(height) => *height,
+            Mode::Wide => 16
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        self.width() / 8 * self.height()
+    }
+}
+
+// Renders memory as a grid of sprites so graphics can be found by eye.
+// Sprites drawn during the last frame are outlined, so playing the game
+// a bit points straight at the data it uses.
+pub struct SpriteView {
+    pub open: bool,
+    address: usize,
+    mode: Mode
+}
+
+struct Layout {
+    width: f32,
+    cell_width: f32,
+    cell_height: f32,
+    columns: usize,
+    rows: usize
+}
+
+impl Layout {
+    fn new(mode: Mode) -> Layout {
+        let width = (screen_width() * 0.45).max(200.0);
+        let label_width = measure_text("000", None, FONT_SIZE, 1.0).width;
+        let cell_width = (mode.width() as f32 * PIXEL_SIZE).max(label_width) + CELL_PADDING;
+        let cell_height = mode.height() as f32 * PIXEL_SIZE + ROW_HEIGHT + CELL_PADDING;
+        let top = ROW_HEIGHT * HEADER_ROWS as f32;
+
+        Layout {
+            width,
+            cell_width,
+            cell_height,
+            columns: ((width - 10.0) / cell_width).max(1.0) as usize,
+            rows: ((screen_height() - top) / cell_height).max(1.0) as usize
+        }
+    }
+
+    fn cell_position(&self, index: usize) -> (f32, f32) {
+        let column = index % self.columns;
+        let row = index / self.columns;
+        (5.0 + column as f32 * self.cell_width, ROW_HEIGHT * HEADER_ROWS as f32 + row as f32 * self.cell_height)
+    }
+}
+
+impl SpriteView {
+    pub fn new() -> SpriteView {
+        SpriteView { open: false, address: 0x200, mode: Mode::Narrow(5) }
+    }
+
+    pub fn update(&mut self, vm: &VM) {
+        if !self.open {
+            return;
+        }
+
+        let layout = Layout::new(self.mode);
+
+        if is_key_pressed(KeyCode::M) {
+            self.mode = match self.mode {
+                Mode::Narrow(_) => Mode::Wide,
+                Mode::Wide => Mode::Narrow(5)
+            };
+        }
+
+        if let Mode::Narrow(height) = self.mode {
+            if is_key_pressed(KeyCode::Comma) {
+                self.mode = Mode::Narrow((height - 1).max(1));
+            }
+            if is_key_pressed(KeyCode::Period) {
+                self.mode = Mode::Narrow((height + 1).min(MAX_HEIGHT));
+            }
+        }
+
+        if is_key_pressed(KeyCode::I) {
+            self.address = vm.index_register as usize;
+        }
+
+        // Whole rows of sprites, or single bytes with shift to line the grid up with the data.
+        let wheel = mouse_wheel().1;
+        if wheel != 0.0 && mouse_position().0 < layout.width {
+            let step = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                1
+            } else {
+                layout.columns * self.mode.bytes()
+            };
+
+            self.address = if wheel > 0.0 {
+                self.address.saturating_sub(step)
+            } else {
+                self.address + step
+            };
+        }
+
+        self.address = self.address.min(vm.memory.len() - 1);
+    }
+
+    fn drawn_last_frame(vm: &VM, start: usize, end: usize) -> bool {
+        vm.last_frame_sprites.iter().any(|(address, height)| {
+            let bytes = if *height == 0 { 32 } else { *height as usize };
+            *address < end && start < address + bytes
+        })
+    }
+
+    pub fn draw(&self, vm: &VM) {
+        if !self.open {
+            return;
+        }
+
+        let layout = Layout::new(self.mode);
+        let index = vm.index_register as usize;
+
+        draw_rectangle(0.0, 0.0, layout.width, screen_height(), color_u8!(10, 10, 30, 230));
+
+        let mode = match self.mode {
+            Mode::Narrow(height) => format!("8x{}", height),
+            Mode::Wide => "16x16".to_string()
+        };
+        draw_text(&format!("{} from {:03X}  M mode  , . height  I jump to I {:03X}", mode, self.address, index), 5.0, ROW_HEIGHT, FONT_SIZE as f32, WHITE);
+
+        let bytes = self.mode.bytes();
+        let bytes_per_row = self.mode.width() / 8;
+
+        for cell in 0..layout.columns * layout.rows {
+            let start = self.address + cell * bytes;
+            if start >= vm.memory.len() {
+                break;
+            }
+            let end = (start + bytes).min(vm.memory.len());
+
+            let (x, y) = layout.cell_position(cell);
+            let sprite_y = y + ROW_HEIGHT;
+            let width = self.mode.width() as f32 * PIXEL_SIZE;
+            let height = self.mode.height() as f32 * PIXEL_SIZE;
+
+            let label_color = if (start..end).contains(&index) { ORANGE } else { GRAY };
+            draw_text(&format!("{:03X}", start), x, y + ROW_HEIGHT - 4.0, FONT_SIZE as f32, label_color);
+
+            draw_rectangle(x, sprite_y, width, height, color_u8!(20, 20, 20, 255));
+            if Self::drawn_last_frame(vm, start, end) {
+                draw_rectangle_lines(x - 2.0, sprite_y - 2.0, width + 4.0, height + 4.0, 2.0, YELLOW);
+            }
+
+            for address in start..end {
+                let offset = address - start;
+                let row = offset / bytes_per_row;
+                let byte_x = (offset % bytes_per_row) * 8;
+
+                for bit in 0..8 {
+                    if vm.memory[address] & (0x80 >> bit) != 0 {
+                        let pixel_x = x + (byte_x + bit) as f32 * PIXEL_SIZE;
+                        let pixel_y = sprite_y + row as f32 * PIXEL_SIZE;
+                        draw_rectangle(pixel_x, pixel_y, PIXEL_SIZE, PIXEL_SIZE, color_u8!(200, 200, 200, 255));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use std::{env, fs, io, path::{Path, PathBuf}};
+
+use crate::chip8::RPL_FLAG_COUNT;
+
+// Per-ROM files live in the user's data directory and are keyed by the SHA-1 of the ROM,
+// so renaming or moving a ROM keeps its cheats and saves.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        return PathBuf::from(dir).join("chip8-rs");
+    }
+
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir).join("chip8-rs");
+    }
+
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(".local").join("share").join("chip8-rs"),
+        None => PathBuf::from(".chip8-rs")
+    }
+}
+
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+pub fn rom_file(kind: &str, hash: &str, extension: &str) -> PathBuf {
+    data_dir().join(kind).join(format!("{}.{}", hash, extension))
+}
+
+// RPL flags files hold the flags the platform has, one byte each. A shorter file
+// leaves the rest at 0 and anything past `count` is ignored.
+pub fn load_rpl_flags(path: &Path, count: usize) -> io::Result<[u8; RPL_FLAG_COUNT]> {
+    let bytes = fs::read(path)?;
+    let mut flags = [0; RPL_FLAG_COUNT];
+    let count = bytes.len().min(count).min(RPL_FLAG_COUNT);
+    flags[..count].copy_from_slice(&bytes[..count]);
+    Ok(flags)
+}
+
+pub fn save_rpl_flags(path: &Path, flags: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, flags)
+}
@@ -0,0 +1,118 @@
+use std::{fs::File, io::{self, BufRead, BufReader, LineWriter, Write}, path::Path};
+
+use crate::chip8::VM;
+
+// One line per executed instruction, describing the machine right before it runs:
+//
+//   PC:0200 OP:00E0 V0:00 .. VF:00 I:0000 SP:00 DT:00 ST:00 ; CLS
+//
+// Everything after the ';' is informational, so traces from emulators
+// with a different disassembly syntax still compare field by field.
+pub struct Tracer {
+    writer: LineWriter<File>
+}
+
+impl Tracer {
+    pub fn create(path: &Path) -> io::Result<Tracer> {
+        Ok(Tracer { writer: LineWriter::new(File::create(path)?) })
+    }
+
+    // Takes the line made by format_line before the instruction ran.
+    pub fn record(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.writer, "{}", line)
+    }
+}
+
+pub fn format_line(vm: &VM) -> String {
+    let mut line = match vm.fetch_raw() {
+        Ok(raw_inst) => format!("PC:{:04X} OP:{:04X}", vm.program_counter, raw_inst),
+        Err(_) => format!("PC:{:04X} OP:????", vm.program_counter)
+    };
+
+    for (index, value) in vm.variable_registers.iter().enumerate() {
+        line.push_str(&format!(" V{:X}:{:02X}", index, value));
+    }
+
+    line.push_str(&format!(" I:{:04X} SP:{:02X} DT:{:02X} ST:{:02X}", vm.index_register, vm.stack.top + 1, vm.delay_timer, vm.sound_timer));
+
+    match vm.fetch() {
+        Ok(instruction) => line.push_str(&format!(" ; {}", instruction.disassemble(vm.chip48_mode))),
+        Err(_) => line.push_str(" ; ???")
+    }
+
+    line
+}
+
+pub struct Divergence {
+    pub line: usize,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub fields: Vec<String>
+}
+
+fn fields(line: &str) -> Vec<(&str, &str)> {
+    let data = match line.split_once(';') {
+        Some((data, _)) => data,
+        None => line
+    };
+
+    data.split_whitespace()
+        .map(|field| field.split_once(':').unwrap_or((field, "")))
+        .collect()
+}
+
+fn differing_fields(left: &str, right: &str) -> Vec<String> {
+    let left = fields(left);
+    let right = fields(right);
+    let mut names: Vec<&str> = Vec::new();
+
+    for (name, _) in left.iter().chain(right.iter()) {
+        if !names.contains(name) {
+            names.push(name);
+        }
+    }
+
+    names.into_iter()
+        .filter(|name| {
+            let l = left.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+            let r = right.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+            l != r
+        })
+        .map(|name| name.to_string())
+        .collect()
+}
+
+pub fn diff(left_path: &Path, right_path: &Path) -> io::Result<Option<Divergence>> {
+    let mut left_lines = BufReader::new(File::open(left_path)?).lines();
+    let mut right_lines = BufReader::new(File::open(right_path)?).lines();
+    let mut line = 0;
+
+    loop {
+        line += 1;
+
+        let left = left_lines.next().transpose()?;
+        let right = right_lines.next().transpose()?;
+
+        match (&left, &right) {
+            (None, None) => return Ok(None),
+            (Some(l), Some(r)) => {
+                let fields = differing_fields(l, r);
+                if !fields.is_empty() {
+                    return Ok(Some(Divergence { line, left, right, fields }));
+                }
+            },
+            _ => return Ok(Some(Divergence { line, left, right, fields: Vec::new() }))
+        }
+    }
+}
+
+pub fn print_divergence(divergence: &Divergence) {
+    println!("Traces diverge at line {}", divergence.line);
+
+    if !divergence.fields.is_empty() {
+        println!("Differing fields: {}", divergence.fields.join(", "));
+    }
+
+    println!("< {}", divergence.left.as_deref().unwrap_or("<end of trace>"));
+    println!("> {}", divergence.right.as_deref().unwrap_or("<end of trace>"));
+}
@@ -0,0 +1,69 @@
+// Screenshots and recordings decoded back with the same crates that wrote them.
+use std::{fs::File, path::PathBuf};
+
+use chip8_rs::{capture::{self, Palette, Recorder}, chip8::Display};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chip8-rs-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn palette_parses_background_first() {
+    assert_eq!(Palette::parse("000000,#FFAA00"), Some(Palette { off: [0, 0, 0], on: [0xFF, 0xAA, 0x00] }));
+    assert_eq!(Palette::parse("000000"), None);
+    assert_eq!(Palette::parse("00000,FFAA00"), None);
+}
+
+#[test]
+fn screenshot_is_scaled() {
+    let path = temp_path("shot.png");
+    let mut display = Display::new();
+    display.flip(1, 0);
+
+    capture::save_png(&path, &display, 3, Palette::default()).unwrap();
+
+    let mut reader = png::Decoder::new(File::open(&path).unwrap()).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!((info.width, info.height), (192, 96));
+    assert_eq!(&pixels[..7], &[0, 0, 0, 1, 1, 1, 0]);
+}
+
+#[test]
+fn gif_keeps_sixty_frames_per_second_on_average() {
+    let path = temp_path("clip.gif");
+    let mut recorder = Recorder::new(&path, 1, Palette::default());
+    let mut display = Display::new();
+
+    for frame in 0..60 {
+        if frame % 2 == 0 {
+            display.flip(0, 0);
+        }
+        recorder.record(&display);
+    }
+    assert_eq!(recorder.frame_count(), 60);
+    recorder.finish().unwrap();
+
+    let mut decoder = gif::DecodeOptions::new().read_info(File::open(&path).unwrap()).unwrap();
+    let mut frames = 0;
+    let mut delay = 0;
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        frames += 1;
+        delay += frame.delay as u32;
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(frames, 30);
+    assert_eq!(delay, 100);
+}
+
+#[test]
+fn gif_refuses_scales_past_its_size_limit() {
+    let path = temp_path("huge.gif");
+    let mut recorder = Recorder::new(&path, capture::MAX_SCALE + 1, Palette::default());
+    recorder.record(&Display::new());
+    assert!(recorder.finish().is_err());
+    assert!(!path.exists());
+}
@@ -0,0 +1,58 @@
+// Packing ROMs into cartridge GIFs and reading them back.
+use chip8_rs::{
+    cartridge,
+    chip8::{Display, Quirks},
+    settings::{Manifest, RomSettings}
+};
+
+#[test]
+fn round_trip() {
+    let program: Vec<u8> = (0..=255).cycle().take(3000).collect();
+    let manifest = Manifest {
+        title: Some("Test card".to_string()),
+        author: None,
+        description: Some("Every byte value".to_string()),
+        settings: RomSettings { quirks: Some(Quirks::schip()), instructions_per_frame: Some(30), ..RomSettings::default() }
+    };
+    let mut label = Display::new();
+    label.flip(3, 4);
+
+    // 3000 bytes of program as text take more than one frame
+    let gif = cartridge::pack(&program, &manifest, &label).unwrap();
+    assert!(cartridge::is_cartridge(&gif));
+
+    let unpacked = cartridge::unpack(&gif).unwrap();
+    assert_eq!(unpacked.program, program);
+    assert_eq!(unpacked.manifest, manifest);
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(!cartridge::is_cartridge(&[0x12, 0x00]));
+    assert!(cartridge::unpack(b"GIF89a").is_err());
+
+    // A plain GIF has no length in front of its data
+    let mut gif = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut gif, 4, 4, &[0, 0, 0, 255, 255, 255]).unwrap();
+        encoder.write_frame(&gif::Frame::from_indexed_pixels(4, 4, vec![1; 16], None)).unwrap();
+    }
+    assert!(cartridge::unpack(&gif).is_err());
+}
+
+#[test]
+fn octo_source_is_assembled() {
+    // A cartridge in Octo's layout whose program is tests/data/bounce.8o
+    let unpacked = cartridge::unpack(include_bytes!("data/bounce.gif")).unwrap();
+    assert_eq!(unpacked.program, [
+        0x12, 0x22, 0x60, 0xF0, 0xF0, 0x60,
+        0x80, 0x24, 0x81, 0x34, 0x40, 0x00, 0x62, 0x01, 0x40, 0x3C, 0x62, 0xFF, 0x41, 0x00, 0x63, 0x01,
+        0x6F, 0x1C, 0x8F, 0x17, 0x3F, 0x01, 0x12, 0x20, 0x63, 0xFF, 0x00, 0xEE,
+        0x60, 0x0A, 0x61, 0x05, 0x62, 0x01, 0x63, 0x01, 0xA2, 0x02,
+        0xD0, 0x14, 0x6F, 0x02, 0xFF, 0x15, 0xFF, 0x07, 0x3F, 0x00, 0x12, 0x32, 0xD0, 0x14, 0x22, 0x06, 0x12, 0x2C
+    ]);
+    assert_eq!(unpacked.manifest.settings.instructions_per_frame, Some(20));
+    assert_eq!(unpacked.manifest.settings.quirks, Some(Quirks::xo_chip()));
+    // Octo's own font has no counterpart here
+    assert_eq!(unpacked.manifest.settings.font, None);
+}
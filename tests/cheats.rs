@@ -0,0 +1,93 @@
+// Cheat files, the RAM search and cheats written by the run loop.
+use std::sync::{Arc, Mutex};
+
+use chip8_rs::{
+    chip8::VM,
+    cheats::{self, Cheat, CheatList, RamSearch, SearchFilter},
+    runner::Runner
+};
+
+#[test]
+fn parses_cheat_lines() {
+    let cheat = cheats::parse_cheat("1 02F4 09 Infinite lives").unwrap();
+    assert!(cheat.enabled);
+    assert_eq!((cheat.address, cheat.value, cheat.name.as_str()), (0x2F4, 0x09, "Infinite lives"));
+
+    let cheat = cheats::parse_cheat("0 300 FF").unwrap();
+    assert!(!cheat.enabled);
+    assert_eq!((cheat.address, cheat.value, cheat.name.as_str()), (0x300, 0xFF, ""));
+
+    assert!(cheats::parse_cheat("1 02F4").is_none());
+    assert!(cheats::parse_cheat("1 XYZ 09 Lives").is_none());
+    assert!(cheats::parse_cheat("1 02F4 100 Lives").is_none());
+}
+
+#[test]
+fn ram_search_filters() {
+    let mut memory = vec![5, 5, 5, 5];
+    let mut search = RamSearch::new(&memory);
+
+    memory[1] = 6;
+    memory[2] = 4;
+    search.filter(SearchFilter::Changed, &memory);
+    assert_eq!(search.candidates, vec![1, 2]);
+
+    memory[1] = 7;
+    search.filter(SearchFilter::Increased, &memory);
+    assert_eq!(search.candidates, vec![1]);
+
+    let mut search = RamSearch::new(&memory);
+    search.filter(SearchFilter::Equal(5), &memory);
+    assert_eq!(search.candidates, vec![0, 3]);
+
+    memory[0] = 1;
+    search.filter(SearchFilter::Unchanged, &memory);
+    assert_eq!(search.candidates, vec![3]);
+
+    let mut search = RamSearch::new(&memory);
+    memory[2] = 0;
+    search.filter(SearchFilter::Decreased, &memory);
+    assert_eq!(search.candidates, vec![2]);
+}
+
+#[test]
+fn cheat_files() {
+    let dir = std::env::temp_dir().join(format!("chip8-cheats-{}", std::process::id()));
+    let path = dir.join("cheats").join("rom.cht");
+
+    // A missing file is an empty list
+    assert!(CheatList::load(&path).unwrap().cheats.is_empty());
+
+    let mut list = CheatList::new(&path);
+    list.cheats.push(Cheat { name: "Infinite lives".to_string(), address: 0x2F4, value: 9, enabled: true });
+    list.cheats.push(Cheat { name: "Level 5".to_string(), address: 0x301, value: 5, enabled: false });
+    list.save().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "1 02F4 09 Infinite lives\n0 0301 05 Level 5\n");
+
+    let loaded = CheatList::load(&path).unwrap();
+    assert_eq!(loaded.cheats.len(), 2);
+    assert_eq!((loaded.cheats[1].address, loaded.cheats[1].value, loaded.cheats[1].enabled), (0x301, 5, false));
+
+    std::fs::write(&path, "1 02F4 09 Lives\nnot a cheat\n").unwrap();
+    let err = CheatList::load(&path).err().unwrap();
+    assert!(err.to_string().ends_with(":2: invalid cheat"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn runner_writes_enabled_cheats() {
+    let mut vm = VM::default();
+    // loop
+    vm.load_program(&[0x12, 0x00], 0x200);
+    vm.program_counter = 0x200;
+
+    let mut list = CheatList::new(std::path::Path::new("unused.cht"));
+    list.cheats.push(Cheat { name: "Pinned".to_string(), address: 0x300, value: 7, enabled: true });
+    list.cheats.push(Cheat { name: "Off".to_string(), address: 0x301, value: 7, enabled: false });
+
+    let mut runner = Runner { cheats: Some(list), ..Runner::new(Arc::new(Mutex::new(vm))) };
+    runner.run_frame().unwrap();
+    let vm = runner.vm.lock().unwrap();
+    assert_eq!(vm.memory[0x300..0x302], [7, 0]);
+}
@@ -0,0 +1,44 @@
+// The coverage report of a small ROM that runs, reads and writes parts of itself.
+use chip8_rs::chip8::VM;
+
+#[test]
+fn report_marks_executed_read_and_written_bytes() {
+    let mut vm = VM::default();
+    vm.load_program(&[
+        0x60, 0x7B, // LD V0, 7B
+        0xA2, 0x0E, // LD I, 20E
+        0xF0, 0x33, // LD B, V0, writes 20E-210
+        0xF2, 0x65, // LD V2, [I], reads them back
+        0xA3, 0x00, // LD I, 300
+        0xF0, 0x55, // LD [I], V0, outside the program
+        0x12, 0x0C, // JP 20C
+        0x00, 0x00, 0x00, 0x00
+    ], 0x200);
+    vm.program_counter = 0x200;
+    vm.enable_coverage();
+    for _ in 0..10 {
+        vm.tick().unwrap();
+    }
+
+    let report = vm.coverage.as_ref().unwrap().report(&vm.memory, vm.program.clone());
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(&lines[..6], &[
+        "Program 200-211 (18 bytes)",
+        "  executed:     14 bytes  77.8%",
+        "  read:          3 bytes  16.7%",
+        "  written:       3 bytes  16.7%",
+        "  untouched:     1 bytes   5.6%",
+        "Written outside program: 300-300"
+    ]);
+    assert_eq!(&lines[7..], &[
+        "200  60 7B  X--  LD V0, 7B",
+        "202  A2 0E  X--  LD I, 20E",
+        "204  F0 33  X--  LD B, V0",
+        "206  F2 65  X--  LD V2, [I]",
+        "208  A3 00  X--  LD I, 300",
+        "20A  F0 55  X--  LD [I], V0",
+        "20C  12 0C  X--  JP 20C",
+        "20E  01 02  -RW  ",
+        "210  03 00  -RW  "
+    ]);
+}
@@ -0,0 +1,128 @@
+// Breakpoints, watchpoints and logpoints, and the expression language behind their conditions.
+use chip8_rs::chip8::{Breakpoint, Expression, Trigger, VM, MAX_MESSAGES};
+
+fn vm_with(program: &[u8], breakpoints: &[&str]) -> VM {
+    let mut vm = VM::default();
+    vm.load_rom(program).unwrap();
+    for text in breakpoints {
+        vm.debugger.add(Breakpoint::parse(text).unwrap());
+    }
+    vm
+}
+
+fn run(vm: &mut VM, steps: usize) {
+    for _ in 0..steps {
+        if vm.paused {
+            break;
+        }
+        vm.tick().unwrap();
+    }
+}
+
+#[test]
+fn expressions() {
+    let mut vm = VM::default();
+    vm.variable_registers[3] = 0x10;
+    vm.index_register = 0x310;
+    vm.memory[0x312] = 7;
+
+    let eval = |text: &str| Expression::parse(text).unwrap().eval(&vm, Some(0x42));
+    assert_eq!(eval("v3 == 0x10 && i > 0x300"), 1);
+    assert_eq!(eval("v3 == 0x10 && i > 0x400"), 0);
+    assert_eq!(eval("[i + 2] * 2 + 1"), 15);
+    assert_eq!(eval("(1 + 2) * 3 - 10 / 2 % 3"), 7);
+    assert_eq!(eval("!v0 || 1 / 0"), 1);
+    assert_eq!(eval("1 << 4 | 1"), 0x11);
+    assert_eq!(eval("-v3 + ~0"), -0x11);
+    assert_eq!(eval("addr == 0x42 && sp == 0 && pc == 0x200"), 1);
+
+    assert!(Expression::parse("v3 ==").is_err());
+    assert!(Expression::parse("vg").is_err());
+    assert!(Expression::parse("(1").is_err());
+}
+
+#[test]
+fn parsing() {
+    let breakpoint = Breakpoint::parse("read 300..310 if addr > 0x304 log got {[addr]}").unwrap();
+    assert_eq!(breakpoint.trigger, Trigger::Read(0x300..0x310));
+    assert!(breakpoint.condition.is_some());
+    assert_eq!(breakpoint.log.as_deref(), Some("got {[addr]}"));
+
+    assert_eq!(Breakpoint::parse("write 0x300+4").unwrap().trigger, Trigger::Write(0x300..0x304));
+    assert_eq!(Breakpoint::parse("op Draw").unwrap().trigger, Trigger::Opcode("Draw".to_string()));
+    assert_eq!(Breakpoint::parse("when v0 == 2").unwrap().trigger, Trigger::Always);
+
+    assert!(Breakpoint::parse("jump 200").is_err());
+    assert!(Breakpoint::parse("read 310..300").is_err());
+    assert!(Breakpoint::parse("pc 204 if v0 ==").is_err());
+}
+
+#[test]
+fn pc_breakpoint_stops_before_the_instruction_and_resumes() {
+    // V0 += 1, loop
+    let mut vm = vm_with(&[0x70, 0x01, 0x12, 0x00], &["pc 200 if v0 == 2"]);
+
+    run(&mut vm, 100);
+    assert!(vm.paused);
+    assert_eq!(vm.program_counter, 0x200);
+    assert_eq!(vm.variable_registers[0], 2);
+    assert_eq!(vm.debugger.stopped, Some(0));
+    assert_eq!(vm.debugger.breakpoints[0].hits, 1);
+
+    // Resuming runs the instruction under the breakpoint instead of stopping again.
+    vm.paused = false;
+    run(&mut vm, 4);
+    assert!(!vm.paused);
+    assert_eq!(vm.variable_registers[0], 4);
+    assert_eq!(vm.debugger.stopped, None);
+}
+
+#[test]
+fn opcode_class_breakpoint() {
+    // V0 = 1, I = 0, DRW V0, V0, 1
+    let mut vm = vm_with(&[0x60, 0x01, 0xA0, 0x00, 0xD0, 0x01], &["op draw"]);
+
+    run(&mut vm, 10);
+    assert!(vm.paused);
+    assert_eq!(vm.program_counter, 0x204);
+}
+
+#[test]
+fn write_watchpoint_stops_after_the_instruction() {
+    // V0 = 5, I = 301, LD [I], V0, V0 = 6
+    let mut vm = vm_with(&[0x60, 0x05, 0xA3, 0x01, 0xF0, 0x55, 0x60, 0x06], &["write 300..304 if [addr] == 5", "read 300"]);
+
+    run(&mut vm, 10);
+    assert!(vm.paused);
+    assert_eq!(vm.program_counter, 0x206);
+    assert_eq!(vm.memory[0x301], 5);
+    assert_eq!(vm.debugger.breakpoints[0].hits, 1);
+    assert_eq!(vm.debugger.breakpoints[1].hits, 0);
+    assert!(vm.debugger.take_messages()[0].starts_with("PC 204 (301)"));
+}
+
+#[test]
+fn logpoints_count_without_stopping() {
+    // V0 += 1, loop
+    let mut vm = vm_with(&[0x70, 0x01, 0x12, 0x00], &["pc 202 log v0 is {v0}"]);
+    vm.debugger.breakpoints.push(Breakpoint { enabled: false, ..Breakpoint::at(0x200) });
+
+    run(&mut vm, 6);
+    assert!(!vm.paused);
+    assert_eq!(vm.debugger.breakpoints[0].hits, 3);
+    assert_eq!(vm.debugger.breakpoints[1].hits, 0);
+    assert_eq!(vm.debugger.take_messages(), vec!["PC 202: v0 is 1", "PC 202: v0 is 2", "PC 202: v0 is 3"]);
+}
+
+#[test]
+fn messages_wait_for_a_frontend_up_to_a_limit() {
+    // V0 += 1, loop, with nothing taking the messages
+    let mut vm = vm_with(&[0x70, 0x01, 0x12, 0x00], &["pc 202 log v0 is {v0}"]);
+    run(&mut vm, 2 * (MAX_MESSAGES + 5));
+
+    let messages = vm.debugger.take_messages();
+    assert_eq!(messages.len(), MAX_MESSAGES + 1);
+    assert_eq!(messages[0], "5 older messages dropped");
+    assert_eq!(messages[1], "PC 202: v0 is 6");
+    assert!(vm.debugger.take_messages().is_empty());
+}
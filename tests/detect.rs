@@ -0,0 +1,106 @@
+// Platform and quirk detection from reachable opcodes and the known ROM database.
+use chip8_rs::{chip8::{self, Extension, Platform, Quirks, VM}, detect::{self, Database}, storage};
+
+#[test]
+fn extension_opcodes() {
+    assert_eq!(chip8::extension(0x00FF), Some((Extension::SuperChip, 2)));
+    assert_eq!(chip8::extension(0x00C4), Some((Extension::SuperChip, 2)));
+    assert_eq!(chip8::extension(0xD120), Some((Extension::SuperChip, 2)));
+    assert_eq!(chip8::extension(0xF375), Some((Extension::SuperChip, 2)));
+    assert_eq!(chip8::extension(0xF000), Some((Extension::XoChip, 4)));
+    assert_eq!(chip8::extension(0x5122), Some((Extension::XoChip, 2)));
+    assert_eq!(chip8::extension(0xF201), Some((Extension::XoChip, 2)));
+    assert_eq!(chip8::extension(0x00E0), None);
+    assert_eq!(chip8::extension(0xD125), None);
+    assert_eq!(chip8::extension(0xF165), None);
+}
+
+#[test]
+fn guesses_from_reachable_opcodes() {
+    // HIGH, then an XO-CHIP long load is only found by following past it
+    let xo_chip = detect::guess(&[0x00, 0xFF, 0xF0, 0x00, 0x02, 0x00, 0x12, 0x00], 0x200);
+    assert_eq!(xo_chip.platform, Some(Platform::xo_chip()));
+    assert_eq!(xo_chip.quirks, Some(Quirks::xo_chip()));
+
+    // HIGH, B3 00 jumps use the jump quirk
+    let schip = detect::guess(&[0x00, 0xFF, 0xB3, 0x00], 0x200);
+    assert_eq!(schip.platform, None);
+    assert_eq!(schip.quirks, Some(Quirks::schip()));
+    assert_eq!(schip.reasons.len(), 2);
+
+    // SHR V1, V2 reads V2
+    assert_eq!(detect::guess(&[0x81, 0x26, 0x12, 0x00], 0x200).quirks, Some(Quirks::vip()));
+    // SHR V1, V0 could be either
+    assert_eq!(detect::guess(&[0x81, 0x06, 0x12, 0x00], 0x200).quirks, None);
+
+    // Unreachable SCHIP opcodes are data
+    let plain = detect::guess(&[0x12, 0x00, 0x00, 0xFF], 0x200);
+    assert_eq!(plain.quirks, None);
+    assert!(plain.reasons.is_empty());
+
+    // Too big for 4K
+    let big = vec![0x12; 0x1000];
+    assert_eq!(detect::guess(&big, 0x200).platform, Some(Platform::xo_chip()));
+}
+
+#[test]
+fn database_pins_settings() {
+    let mut database = Database::embedded();
+    database.add("# comment\nABCDEF eti660 vip Some game\n").unwrap();
+    database.add("abcdef chip8 schip Patched game").unwrap();
+
+    let detection = detect::detect(&[0x81, 0x26], "abcdef", 0x200, &database);
+    assert_eq!(detection.platform, Some(Platform::chip8()));
+    assert_eq!(detection.quirks, Some(Quirks::schip()));
+    assert_eq!(detection.title.as_deref(), Some("Patched game"));
+
+    assert_eq!(detect::detect(&[0x81, 0x26], "123456", 0x200, &database).quirks, Some(Quirks::vip()));
+
+    assert!(database.add("abcdef gameboy vip").is_err());
+    assert!(database.add("abcdef chip8").is_err());
+}
+
+// The IBM logo that ships with most interpreters.
+const IBM_LOGO: &[u8] = &[
+    0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0x61, 0x08, 0xD0, 0x1F, 0x70, 0x09, 0xA2, 0x39, 0xD0, 0x1F,
+    0xA2, 0x48, 0x70, 0x08, 0xD0, 0x1F, 0x70, 0x04, 0xA2, 0x57, 0xD0, 0x1F, 0x70, 0x08, 0xA2, 0x66,
+    0xD0, 0x1F, 0x70, 0x08, 0xA2, 0x75, 0xD0, 0x1F, 0x12, 0x28, 0xFF, 0x00, 0xFF, 0x00, 0x3C, 0x00,
+    0x3C, 0x00, 0x3C, 0x00, 0x3C, 0x00, 0xFF, 0x00, 0xFF, 0xFF, 0x00, 0xFF, 0x00, 0x38, 0x00, 0x3F,
+    0x00, 0x3F, 0x00, 0x38, 0x00, 0xFF, 0x00, 0xFF, 0x80, 0x00, 0xE0, 0x00, 0xE0, 0x00, 0x80, 0x00,
+    0x80, 0x00, 0xE0, 0x00, 0xE0, 0x00, 0x80, 0xF8, 0x00, 0xFC, 0x00, 0x3E, 0x00, 0x3F, 0x00, 0x3B,
+    0x00, 0x39, 0x00, 0xF8, 0x00, 0xF8, 0x03, 0x00, 0x07, 0x00, 0x0F, 0x00, 0xBF, 0x00, 0xFB, 0x00,
+    0xF3, 0x00, 0xE3, 0x00, 0x43, 0xE0, 0x00, 0xE0, 0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0x80,
+    0x00, 0xE0, 0x00, 0xE0
+];
+
+// David Winter's Maze, drawing diagonals at random.
+const MAZE: &[u8] = &[
+    0xA2, 0x1E, 0xC2, 0x01, 0x32, 0x01, 0xA2, 0x1A, 0xD0, 0x14, 0x70, 0x04, 0x30, 0x40, 0x12, 0x00,
+    0x60, 0x00, 0x71, 0x04, 0x31, 0x20, 0x12, 0x00, 0x12, 0x18, 0x80, 0x40, 0x20, 0x10, 0x20, 0x40,
+    0x80, 0x10
+];
+
+#[test]
+fn embedded_database_knows_the_shipped_roms() {
+    for (rom, title) in [(IBM_LOGO, "IBM Logo"), (MAZE, "Maze (David Winter)")] {
+        let hash = storage::rom_hash(rom);
+        let detection = detect::detect(rom, &hash, 0x200, &Database::embedded());
+        assert_eq!(detection.title.as_deref(), Some(title));
+        assert_eq!(detection.platform, Some(Platform::chip8()));
+        assert_eq!(detection.quirks, Some(Quirks::vip()));
+        assert_eq!(detection.reasons, vec!["known ROM"]);
+    }
+}
+
+#[test]
+fn quirk_presets() {
+    let mut vm = VM::default();
+    assert_eq!(Quirks::of(&vm), Quirks::default());
+    assert_eq!(Quirks::default().name(), "custom");
+
+    Quirks::from_name("VIP").unwrap().apply(&mut vm);
+    assert!(!vm.shift_legacy && !vm.chip48_mode);
+    assert_eq!(Quirks::of(&vm).name(), "vip");
+    assert_eq!(Quirks::schip().name(), "schip");
+    assert!(Quirks::from_name("octo").is_none());
+}
@@ -0,0 +1,77 @@
+// Running quirk settings in lockstep and pinning down where they diverge.
+use chip8_rs::{chip8::{Quirks, VM, SeededRandom}, explore::{Explorer, Machine}};
+
+fn machine(name: &str, quirks: Quirks, program: &[u8]) -> Machine {
+    let mut vm = VM::default();
+    vm.load_program(program, 0x200);
+    vm.random = Box::new(SeededRandom::new(1));
+    Machine::new(name, quirks, vm)
+}
+
+#[test]
+fn finds_the_quirk_sensitive_instruction() {
+    // LD V1, 3; LD V2, 8; SHR V1, V2; JP 206
+    let program = [0x61, 0x03, 0x62, 0x08, 0x81, 0x26, 0x12, 0x06];
+    let mut explorer = Explorer::new(vec![machine("vip", Quirks::vip(), &program), machine("schip", Quirks::schip(), &program)]);
+    explorer.run_frame(10);
+
+    let divergence = explorer.divergence.clone().unwrap();
+    assert_eq!(divergence.frame, 0);
+    assert_eq!(divergence.instruction, 2);
+    assert_eq!(divergence.pc, 0x204);
+    assert_eq!(divergence.raw_inst, Some(0x8126));
+    assert_eq!(divergence.machines, vec![1]);
+    assert_eq!(divergence.fields, vec!["V1"]);
+    assert_eq!(divergence.quirks, vec!["shift_legacy"]);
+    assert!(explorer.report().contains("Caused by shift_legacy"));
+}
+
+#[test]
+fn identical_settings_never_diverge() {
+    // LD I, 300; LD V0, 5; LD [I], V0; DRW V0, V0, 5; JP 200
+    let program = [0xA3, 0x00, 0x60, 0x05, 0xF0, 0x55, 0xD0, 0x05, 0x12, 0x00];
+    let mut explorer = Explorer::new(vec![machine("a", Quirks::schip(), &program), machine("b", Quirks::schip(), &program)]);
+    for _ in 0..30 {
+        explorer.run_frame(9);
+    }
+
+    assert_eq!(explorer.divergence, None);
+    assert_eq!(explorer.frame, 30);
+    assert!(explorer.report().ends_with("No divergence\n"));
+}
+
+#[test]
+fn quirks_parse_with_changes() {
+    assert_eq!(Quirks::parse("vip").unwrap(), Quirks::vip());
+    let quirks = Quirks::parse("schip+vf_reset-clip_sprites").unwrap();
+    assert!(quirks.vf_reset && !quirks.clip_sprites && quirks.shift_legacy);
+    assert_eq!(Quirks::parse("default").unwrap(), Quirks::default());
+    assert!(Quirks::parse("vip+turbo").is_err());
+    assert_eq!(Quirks::vip().differences(&Quirks::xo_chip()), vec!["vf_reset", "clip_sprites"]);
+}
+
+#[test]
+fn new_quirks_change_behaviour() {
+    // LD I, 300; LD V0, 1; LD V1, 2; LD [I], V1; OR V0, V1
+    let program = [0xA3, 0x00, 0x60, 0x01, 0x61, 0x02, 0xF1, 0x55, 0x80, 0x11];
+    let mut vm = VM::default();
+    vm.load_program(&program, 0x200);
+    vm.variable_registers[0xF] = 7;
+    Quirks::vip().apply(&mut vm);
+    for _ in 0..5 {
+        vm.tick().unwrap();
+    }
+    assert_eq!(vm.index_register, 0x302);
+    assert_eq!(vm.variable_registers[0xF], 0);
+
+    // DRW at x = 60 clips the right half of the sprite instead of wrapping it
+    let mut vm = VM::default();
+    vm.load_program(&[0x60, 0x3C, 0xA3, 0x00, 0xD0, 0x01], 0x200);
+    vm.memory[0x300] = 0xFF;
+    vm.clip_sprites = true;
+    for _ in 0..3 {
+        vm.tick().unwrap();
+    }
+    assert!(vm.display.get(63, 28));
+    assert!(!vm.display.get(0, 28));
+}
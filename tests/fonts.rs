@@ -0,0 +1,34 @@
+// Font sets and the FX29/FX30 lookups at a moved font address.
+use chip8_rs::chip8::{self, FontSet, VM, VMError};
+
+#[test]
+fn font_lookups_follow_the_font_address() {
+    let mut vm = VM::default();
+    vm.load_font(FontSet::Vip, 0x50).unwrap();
+    // V0 = 7, LD F, V0, LD HF, V0
+    vm.load_program(&[0x60, 0x07, 0xF0, 0x29, 0xF0, 0x30], 0x200);
+    vm.program_counter = 0x200;
+
+    vm.tick().unwrap();
+    vm.tick().unwrap();
+    assert_eq!(vm.index_register, 0x50 + 7 * 5);
+    assert_eq!(vm.memory[vm.index_register as usize], 0xF0);
+
+    vm.tick().unwrap();
+    assert_eq!(vm.index_register, 0x50 + 80 + 7 * 10);
+    assert_eq!(&vm.memory[vm.index_register as usize..][..10], &chip8::BIG_FONT_DATA[70..80]);
+}
+
+#[test]
+fn font_must_fit_in_memory() {
+    let mut vm = VM::default();
+    assert!(matches!(vm.load_font(FontSet::Chip48, 0xF80), Err(VMError::MemoryOutOfBounds { .. })));
+    assert!(vm.load_font(FontSet::Chip48, chip8::MEMORY_SIZE - chip8::FONT_SIZE).is_ok());
+}
+
+#[test]
+fn font_names_round_trip() {
+    for font in FontSet::ALL {
+        assert_eq!(FontSet::from_name(font.name()), Some(font));
+    }
+}
@@ -0,0 +1,120 @@
+// The shared run loop driven through the recording frontend.
+use std::sync::{Arc, Mutex};
+
+use chip8_rs::{
+    chip8::{self, Keyboard, VM},
+    frontend::{Clock, FixedClock, InputSource, terminal::TerminalInput, test::{RecordingVideo, RecordingAudio, ScriptedInput}},
+    runner::Runner,
+    settings::Keymap,
+    speed::SpeedClock
+};
+
+fn runner_with_program(program: &[u8]) -> Runner {
+    let mut vm = VM::default();
+    vm.mem_copy(&chip8::FONT_DATA, 0);
+    vm.load_program(program, 0x200);
+    vm.program_counter = 0x200;
+    Runner::new(Arc::new(Mutex::new(vm)))
+}
+
+#[test]
+fn presents_display_and_tone_every_frame() {
+    // V0 = 2, ST = V0, I = font 0, DRW V0, V0, 5, loop
+    let mut runner = runner_with_program(&[0x60, 0x02, 0xF0, 0x18, 0xA0, 0x00, 0xD0, 0x05, 0x12, 0x08]);
+    let mut video = RecordingVideo::default();
+    let mut audio = RecordingAudio::default();
+    let mut input = ScriptedInput::default();
+
+    for _ in 0..3 {
+        runner.update(&mut video, &mut audio, &mut input, &mut FixedClock).unwrap();
+    }
+
+    assert_eq!(video.frames.len(), 3);
+    assert!(video.frames[0].get(2, 2));
+    assert!(!video.frames[0].get(0, 0));
+    assert_eq!(audio.tones, vec![true, false, false]);
+}
+
+#[test]
+fn scripted_input_reaches_the_vm() {
+    // V0 = A, wait until key V0 is down, V1 = 1, loop
+    let mut runner = runner_with_program(&[0x60, 0x0A, 0xE0, 0x9E, 0x12, 0x02, 0x61, 0x01, 0x12, 0x08]);
+    let mut video = RecordingVideo::default();
+    let mut audio = RecordingAudio::default();
+
+    let mut pressed = [false; 16];
+    pressed[0xA] = true;
+    let mut input = ScriptedInput::new(vec![[false; 16], pressed]);
+
+    runner.update(&mut video, &mut audio, &mut input, &mut FixedClock).unwrap();
+    assert_eq!(runner.vm.lock().unwrap().variable_registers[1], 0);
+
+    runner.update(&mut video, &mut audio, &mut input, &mut FixedClock).unwrap();
+    assert_eq!(runner.vm.lock().unwrap().variable_registers[1], 1);
+}
+
+#[test]
+fn speed_clock_scales_frames() {
+    let mut clock = SpeedClock::new(FixedClock);
+    assert_eq!(clock.frames_due(), 1);
+
+    clock.fast_forward = true;
+    assert_eq!(clock.frames_due(), 4);
+
+    clock.fast_forward = false;
+    clock.slower();
+    assert_eq!((0..4).map(|_| clock.frames_due()).collect::<Vec<_>>(), vec![0, 1, 0, 1]);
+    assert_eq!(clock.status(9, true), "9 ipf  1/2  PAUSED");
+}
+
+#[test]
+fn fast_forward_saturates() {
+    struct Behind;
+    impl Clock for Behind {
+        fn frames_due(&mut self) -> u32 {
+            3
+        }
+    }
+
+    let mut clock = SpeedClock::new(Behind);
+    clock.fast_forward = true;
+    clock.fast_forward_factor = u32::MAX;
+    assert_eq!(clock.frames_due(), u32::MAX);
+}
+
+#[test]
+fn frame_advance_runs_one_frame() {
+    // ADD V0, 1, loop
+    let mut runner = runner_with_program(&[0x70, 0x01, 0x12, 0x00]);
+    runner.set_instructions_per_frame(4);
+    runner.vm.lock().unwrap().paused = true;
+
+    runner.advance_frame().unwrap();
+
+    let vm = runner.vm.lock().unwrap();
+    assert_eq!(vm.variable_registers[0], 2);
+    assert!(vm.paused);
+}
+
+#[test]
+fn sprites_drawn_last_frame_are_kept() {
+    // I = font 0, DRW V0, V0, 5, loop
+    let mut runner = runner_with_program(&[0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04]);
+    runner.run_frame().unwrap();
+    assert_eq!(runner.vm.lock().unwrap().last_frame_sprites, vec![(0, 5)]);
+
+    runner.run_frame().unwrap();
+    assert!(runner.vm.lock().unwrap().last_frame_sprites.is_empty());
+}
+
+#[test]
+fn terminal_input_follows_the_keymap() {
+    let mut input = TerminalInput::new(&Keymap::parse("0123456789ABCDEF").unwrap());
+    let mut keyboard = Keyboard::default();
+
+    input.press('a');
+    input.press('x');
+    input.poll(&mut keyboard);
+    assert!(keyboard.keys[0xA]);
+    assert_eq!(keyboard.keys.iter().filter(|key| **key).count(), 1);
+}
@@ -0,0 +1,52 @@
+// The memory view's rows, change highlights and hex typing.
+use chip8_rs::{chip8::{Platform, VM}, hex_edit::{self, Changes, HexEditor}};
+
+#[test]
+fn rows_stop_at_the_end_of_memory() {
+    let vm = VM::new(Platform { memory_size: 0x1001, ..Platform::chip8() });
+    assert_eq!(hex_edit::row_count(&vm.memory), 0x101);
+    assert_eq!(hex_edit::row(&vm.memory, 0xFF0).len(), 16);
+    assert_eq!(hex_edit::row(&vm.memory, 0x1000).len(), 1);
+    assert!(hex_edit::row(&vm.memory, 0x1010).is_empty());
+}
+
+#[test]
+fn changes_last_one_frame() {
+    let mut memory = vec![0; 32];
+    let mut changes = Changes::default();
+    changes.end_frame(&memory);
+
+    memory[3] = 1;
+    changes.end_frame(&memory);
+    assert!(changes.changed(3) && !changes.changed(4));
+    changes.end_frame(&memory);
+    assert!(!changes.changed(3));
+
+    // Edits by hand are not highlighted
+    memory[5] = 7;
+    changes.edited(5, 7);
+    changes.end_frame(&memory);
+    assert!(!changes.changed(5));
+    assert!(!changes.changed(100));
+}
+
+#[test]
+fn typing_hex_over_bytes() {
+    let mut memory = vec![0; 3];
+    let mut editor = HexEditor::default();
+    assert_eq!(editor.type_char('a', &mut memory), None);
+
+    editor.select(Some(1));
+    assert_eq!(editor.type_char('a', &mut memory), None);
+    assert_eq!(editor.pending_nibble, Some(0xA));
+    assert_eq!(editor.type_char('x', &mut memory), None);
+    assert_eq!(editor.type_char('5', &mut memory), Some(1));
+    assert_eq!(memory, [0, 0xA5, 0]);
+    assert_eq!(editor.selected, Some(2));
+
+    // The last byte stays selected
+    editor.type_char('F', &mut memory);
+    editor.type_char('F', &mut memory);
+    assert_eq!(memory[2], 0xFF);
+    assert_eq!(editor.selected, Some(2));
+}
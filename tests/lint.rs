@@ -0,0 +1,78 @@
+// Static checks over hand assembled ROMs, each built to trip one finding.
+use chip8_rs::{chip8::VM, lint::{self, ControlFlowGraph, FindingKind, Severity}};
+
+fn findings(program: &[u8]) -> Vec<(usize, FindingKind)> {
+    let mut vm = VM::default();
+    vm.load_rom(program).unwrap();
+    let cfg = ControlFlowGraph::build(&vm.memory, 0x200);
+    lint::lint(&cfg, vm.memory.len(), vm.program.clone()).into_iter().map(|finding| (finding.address, finding.kind)).collect()
+}
+
+#[test]
+fn clean_loop_has_no_findings() {
+    // CLS, V0 += 1, CALL 208, JP 200, RET
+    assert!(findings(&[0x00, 0xE0, 0x70, 0x01, 0x22, 0x08, 0x12, 0x00, 0x00, 0xEE]).is_empty());
+}
+
+#[test]
+fn only_reachable_invalid_opcodes_count() {
+    // SE V0, 0, invalid, JP 206, then data that never runs
+    let program = [0x30, 0x00, 0xFF, 0xFF, 0x12, 0x04, 0xFF, 0xFF];
+    assert_eq!(findings(&program), vec![(0x202, FindingKind::InvalidOpcode)]);
+}
+
+#[test]
+fn targets_outside_memory_and_odd_targets() {
+    // JP 201 lands between two instructions, where 0112 is not a valid opcode
+    assert_eq!(findings(&[0x12, 0x01, 0x12, 0x01]), vec![(0x200, FindingKind::OddTarget), (0x201, FindingKind::InvalidOpcode)]);
+    // CALL 800 is past the ROM, JP 0FFF leaves no room for a whole instruction
+    let program = [0x28, 0x00, 0x1F, 0xFF];
+    let result = findings(&program);
+    assert!(result.contains(&(0x200, FindingKind::OutsideProgram)));
+    assert!(result.contains(&(0x202, FindingKind::TargetOutsideMemory)));
+}
+
+#[test]
+fn unbalanced_calls_and_returns() {
+    // RET from main
+    assert_eq!(findings(&[0x00, 0xEE]), vec![(0x200, FindingKind::UnbalancedReturn)]);
+    // CALL 204, JP 200, sub 204 jumps to itself
+    assert_eq!(findings(&[0x22, 0x04, 0x12, 0x00, 0x12, 0x04]), vec![(0x204, FindingKind::MissingReturn)]);
+}
+
+#[test]
+fn recursion_and_stack_depth() {
+    // CALL 204, JP 200, sub 204: SE V0, 0, RET, CALL 204, RET
+    let program = [0x22, 0x04, 0x12, 0x00, 0x30, 0x00, 0x00, 0xEE, 0x22, 0x04, 0x00, 0xEE];
+    assert_eq!(findings(&program), vec![(0x208, FindingKind::Recursion)]);
+
+    // Seventeen routines, each calling the next: CALL n+2, RET
+    let mut program = vec![0x22, 0x04, 0x12, 0x00];
+    for routine in 0..17 {
+        let next = 0x204 + (routine + 1) * 4;
+        if routine < 16 {
+            program.extend([0x20 | (next >> 8) as u8, next as u8, 0x00, 0xEE]);
+        } else {
+            program.extend([0x00, 0xEE, 0x00, 0xEE]);
+        }
+    }
+    let mut vm = VM::default();
+    vm.load_rom(&program).unwrap();
+    let cfg = ControlFlowGraph::build(&vm.memory, 0x200);
+    let found = lint::lint(&cfg, vm.memory.len(), vm.program.clone());
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].kind, FindingKind::StackDepth);
+    assert_eq!(found[0].severity, Severity::Error);
+    assert!(found[0].message.contains("17 deep"));
+}
+
+#[test]
+fn quirk_sensitive_opcodes() {
+    // SHR V1, V2, SHL V3, V3, I = 300, LD [I], V1, DRW V0, V0, 1, JP 20A (SHL in place is the same everywhere)
+    let program = [0x81, 0x26, 0x83, 0x3E, 0xA3, 0x00, 0xF1, 0x55, 0xD0, 0x01, 0x12, 0x0A];
+    assert_eq!(findings(&program), vec![(0x200, FindingKind::Quirk), (0x206, FindingKind::Quirk)]);
+
+    // B210 adds V2 on CHIP-48, B010 is the same everywhere but still a computed jump
+    assert_eq!(findings(&[0xB2, 0x10]), vec![(0x200, FindingKind::Quirk), (0x200, FindingKind::IndirectJump)]);
+    assert_eq!(findings(&[0xB0, 0x10]), vec![(0x200, FindingKind::IndirectJump)]);
+}
@@ -0,0 +1,99 @@
+// Assembling the Octo source that cartridges carry.
+use chip8_rs::octo;
+
+#[test]
+fn instructions_and_labels() {
+    let source = "
+        : main
+          clear
+          i := smile       # a label further down
+          v0 := 8 v1 := 0x10
+          sprite v0 v1 5
+          v0 += v1  v2 -= 1  v3 =- v4  v5 >>= v5
+          v6 := random 0x3F  v7 := key  delay := v7
+          save v3  load v2 - v4  bcd v1
+          jump main
+        : smile
+          0x24 0x24 0x00 0x81 0x7E
+    ";
+    let rom = octo::assemble(source).unwrap();
+    assert_eq!(rom, [
+        0x00, 0xE0, 0xA2, 0x20, 0x60, 0x08, 0x61, 0x10, 0xD0, 0x15,
+        0x80, 0x14, 0x72, 0xFF, 0x83, 0x47, 0x85, 0x56,
+        0xC6, 0x3F, 0xF7, 0x0A, 0xF7, 0x15,
+        0xF3, 0x55, 0x52, 0x43, 0xF1, 0x33,
+        0x12, 0x00,
+        0x24, 0x24, 0x00, 0x81, 0x7E
+    ]);
+}
+
+#[test]
+fn blocks_and_comparisons() {
+    let source = "
+        : main
+          loop
+            if v0 == 5 then v1 := 1
+            if v0 key begin
+              v2 := 1
+            else
+              v2 := 2
+            end
+            while v0 < v3
+            v0 += 1
+          again
+    ";
+    let rom = octo::assemble(source).unwrap();
+    assert_eq!(rom, [
+        0x40, 0x05, 0x61, 0x01,             // 200 skip unless v0 == 5
+        0xE0, 0x9E, 0x12, 0x0C,             // 204 skip when pressed, else jump to 20C
+        0x62, 0x01, 0x12, 0x0E,             // 208 then jump past the else
+        0x62, 0x02,                         // 20C
+        0x8F, 0x30, 0x8F, 0x07, 0x3F, 0x00, // 20E vF = v0 >= v3, skip when it is 0
+        0x12, 0x1A,                         // 214 out of the loop
+        0x70, 0x01, 0x12, 0x00              // 216
+    ]);
+}
+
+#[test]
+fn directives() {
+    let source = "
+        :alias x v4
+        :const SPEED 3
+        :calc DOUBLE { SPEED * 2 + 1 }
+        :macro twice what { what what }
+        : data 0x01
+        : main
+          x += SPEED
+          x += DOUBLE
+          twice clear
+          :unpack 0xA data
+          :next target v5 := 0
+          i := long data
+          helper
+        : helper
+          :byte { 0x100 - 1 }
+    ";
+    let rom = octo::assemble(source).unwrap();
+    assert_eq!(rom, [
+        0x12, 0x03, 0x01,       // the jump to main, then data
+        0x74, 0x03, 0x74, 0x09, // right to left, SPEED * (2 + 1)
+        0x00, 0xE0, 0x00, 0xE0,
+        0x60, 0xA2, 0x61, 0x02,
+        0x65, 0x00,
+        0xF0, 0x00, 0x02, 0x02,
+        0x22, 0x17,
+        0xFF
+    ]);
+}
+
+#[test]
+fn errors_name_the_line() {
+    assert_eq!(octo::assemble(": start clear").unwrap_err(), "the program has no main label");
+    assert_eq!(octo::assemble(": main\nv0 := 256").unwrap_err(), "line 2: 256 does not fit in a byte");
+    assert_eq!(octo::assemble(": main\n  nowhere").unwrap_err(), "line 2: nowhere is never defined");
+    assert_eq!(octo::assemble(": main\nloop clear").unwrap_err(), "a block is never closed with end or again");
+    assert!(octo::assemble(": main :stringmode").unwrap_err().contains("not supported"));
+    // The jump to main is already at 0x200
+    assert_eq!(octo::assemble(":org 0x200\n: main\n  clear").unwrap_err(), "line 3: 0x200 already holds an earlier byte");
+    assert_eq!(octo::assemble(": main\n  clear\n:org 0x201\n  clear").unwrap_err(), "line 4: 0x201 already holds an earlier byte");
+}
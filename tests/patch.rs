@@ -0,0 +1,116 @@
+// IPS and BPS patches, created from two ROMs and applied at load time.
+use std::path::PathBuf;
+
+use chip8_rs::chip8::{self, LoadError, PatchFormat, VM};
+
+fn roms() -> (Vec<u8>, Vec<u8>) {
+    let original: Vec<u8> = (0..200).map(|byte| byte as u8).collect();
+    let mut patched = original.clone();
+    patched[3] = 0xFF;
+    patched[10..20].fill(0xAA);
+    patched.extend_from_slice(&[1, 2, 3]);
+    (original, patched)
+}
+
+#[test]
+fn round_trips() {
+    let (original, patched) = roms();
+    for format in [PatchFormat::Ips, PatchFormat::Bps] {
+        let patch = chip8::create_patch(format, &original, &patched).unwrap();
+        assert_eq!(chip8::apply_patch(&patch, &original).unwrap(), patched);
+
+        // Shrinking needs the IPS truncation extension
+        let patch = chip8::create_patch(format, &patched, &original).unwrap();
+        assert_eq!(chip8::apply_patch(&patch, &patched).unwrap(), original);
+    }
+}
+
+#[test]
+fn bps_checks_checksums() {
+    assert_eq!(chip8::crc32(b"123456789"), 0xCBF43926);
+
+    let (original, patched) = roms();
+    let mut patch = chip8::create_patch(PatchFormat::Bps, &original, &patched).unwrap();
+    assert!(chip8::apply_patch(&patch, &patched).unwrap_err().contains("different ROM"));
+
+    patch[6] ^= 1;
+    assert!(chip8::apply_patch(&patch, &original).unwrap_err().contains("corrupt"));
+    assert!(chip8::apply_patch(b"not a patch", &original).is_err());
+}
+
+#[test]
+fn patches_apply_when_loading() {
+    let dir = std::env::temp_dir().join(format!("chip8-patch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let rom = dir.join("game.ch8");
+    std::fs::write(&rom, [0x60, 0x01, 0x12, 0x02]).unwrap();
+    let patch: PathBuf = dir.join("game.ips");
+    std::fs::write(&patch, chip8::create_patch(PatchFormat::Ips, &[0x60, 0x01, 0x12, 0x02], &[0x60, 0x07, 0x12, 0x02]).unwrap()).unwrap();
+
+    let mut vm = VM::default();
+    assert_eq!(vm.load_program_from_file(&rom, std::slice::from_ref(&patch)).unwrap(), 4);
+    assert_eq!(vm.memory[0x201], 0x07);
+
+    // The patch no longer fits what the first one made of the ROM
+    let bps = dir.join("game.bps");
+    std::fs::write(&bps, chip8::create_patch(PatchFormat::Bps, &[0x60, 0x01, 0x12, 0x02], &[0x60, 0x08, 0x12, 0x02]).unwrap()).unwrap();
+    let err = VM::default().load_program_from_file(&rom, &[patch, bps]).unwrap_err();
+    assert!(matches!(err, LoadError::Patch { .. }));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ips_run_length_records() {
+    let patch = [
+        b"PATCH".as_slice(),
+        &[0x00, 0x00, 0x01, 0x00, 0x02, 0xAB, 0xCD], // two bytes at 1
+        &[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x03, 0xEE], // EE three times at 4, past the end
+        b"EOF"
+    ].concat();
+    assert_eq!(chip8::apply_patch(&patch, &[0; 5]).unwrap(), [0x00, 0xAB, 0xCD, 0x00, 0xEE, 0xEE, 0xEE]);
+}
+
+fn number(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let low = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | low);
+            return;
+        }
+        out.push(low);
+        value -= 1;
+    }
+}
+
+#[test]
+fn bps_target_size_is_limited() {
+    let rom = [0x12, 0x00];
+    let mut patch = b"BPS1".to_vec();
+    number(&mut patch, rom.len());
+    number(&mut patch, 1 << 40);
+    number(&mut patch, 0);
+    patch.extend(chip8::crc32(&rom).to_le_bytes());
+    patch.extend(0u32.to_le_bytes());
+    patch.extend(chip8::crc32(&patch).to_le_bytes());
+
+    assert!(chip8::apply_patch(&patch, &rom).unwrap_err().contains("more than any platform holds"));
+}
+
+#[test]
+fn bps_source_copies_far_outside_the_rom() {
+    let rom = [0x12, 0x00];
+    let mut patch = b"BPS1".to_vec();
+    number(&mut patch, rom.len());
+    number(&mut patch, 2);
+    number(&mut patch, 0);
+    // A two byte source copy moved forward as far as the patch can move it
+    number(&mut patch, (1 << 2) | 2);
+    number(&mut patch, usize::MAX >> 1 << 1);
+    patch.extend(chip8::crc32(&rom).to_le_bytes());
+    patch.extend(chip8::crc32(&rom).to_le_bytes());
+    patch.extend(chip8::crc32(&patch).to_le_bytes());
+
+    assert!(chip8::apply_patch(&patch, &rom).unwrap_err().contains("past the end of the ROM"));
+}
@@ -0,0 +1,125 @@
+// Memory maps other than the 4K CHIP-8 one, and strict mode faults.
+use chip8_rs::chip8::{FaultKind, LoadError, Platform, VM, VMError};
+
+#[test]
+fn eti660_programs_load_and_start_at_600() {
+    let mut vm = VM::new(Platform::eti660());
+    assert_eq!(vm.load_rom(&[0x00, 0xE0]).unwrap(), 2);
+    assert_eq!(vm.program_counter, 0x600);
+    assert_eq!(vm.memory[0x600..0x602], [0x00, 0xE0]);
+}
+
+#[test]
+fn memory_size_follows_the_platform() {
+    assert_eq!(VM::new(Platform::vip_2k()).memory.len(), 0x800);
+    assert_eq!(VM::new(Platform::xo_chip()).memory.len(), 0x10000);
+}
+
+#[test]
+fn rom_that_does_not_fit_is_rejected() {
+    let mut vm = VM::new(Platform::vip_2k());
+    let err = vm.load_rom(&[0; 0x601]).unwrap_err();
+
+    assert!(matches!(err, LoadError::TooLarge { size: 0x601, available: 0x600, load_address: 0x200 }));
+    assert_eq!(err.to_string(), "the ROM is 1537 bytes but only 1536 fit between the load address 0x200 and the end of memory");
+}
+
+#[test]
+fn rom_may_not_overlap_the_reserved_region() {
+    let platform = Platform { reserved: 0x300..0x400, ..Platform::chip8() };
+    let mut vm = VM::new(platform);
+    assert!(matches!(vm.load_rom(&[0; 0x101]), Err(LoadError::OverlapsReserved { .. })));
+    assert!(vm.load_rom(&[0; 0x100]).is_ok());
+}
+
+#[test]
+fn invalid_platforms_are_reported() {
+    assert!(Platform { load_address: 0x100, ..Platform::chip8() }.validate().is_err());
+    assert!(Platform { memory_size: 0x20000, ..Platform::xo_chip() }.validate().is_err());
+    assert!(Platform { entry_point: 0x1000, ..Platform::chip8() }.validate().is_err());
+    for platform in Platform::all() {
+        assert_eq!(platform.validate(), Ok(()));
+    }
+}
+
+fn strict_vm(program: &[u8]) -> VM {
+    let mut vm = VM { strict: true, ..VM::default() };
+    vm.load_rom(program).unwrap();
+    vm
+}
+
+#[test]
+fn strict_mode_faults_on_reserved_writes() {
+    // I = 050, LD [I], V0
+    let mut vm = strict_vm(&[0xA0, 0x50, 0xF0, 0x55]);
+    vm.tick().unwrap();
+    assert!(matches!(vm.tick(), Err(VMError::MemoryFault { kind: FaultKind::ReservedWrite, addr: 0x50, pc: 0x202 })));
+}
+
+#[test]
+fn strict_mode_faults_on_fetch_outside_program() {
+    // JP 300
+    let mut vm = strict_vm(&[0x13, 0x00]);
+    vm.tick().unwrap();
+    assert!(matches!(vm.tick(), Err(VMError::MemoryFault { kind: FaultKind::FetchOutsideProgram, addr: 0x300, pc: 0x300 })));
+}
+
+#[test]
+fn strict_mode_faults_on_index_beyond_memory() {
+    // I = FFF, LD V1, [I]
+    let mut vm = strict_vm(&[0xAF, 0xFF, 0xF1, 0x65]);
+    vm.tick().unwrap();
+    let err = vm.tick().unwrap_err();
+    assert!(matches!(err, VMError::MemoryFault { kind: FaultKind::IndexOutOfBounds, addr: 0x1000, pc: 0x202 }));
+    assert_eq!(err.to_string(), "memory fault at PC 202: I-relative access beyond memory at 0x1000");
+}
+
+#[test]
+fn rpl_flags_reach_as_far_as_the_platform_has_them() {
+    // LD V0..VF, then LD R, VF and LD VF, R after clearing the registers
+    let mut program = Vec::new();
+    for register in 0..16u8 {
+        program.extend_from_slice(&[0x60 | register, register + 1]);
+    }
+    program.extend_from_slice(&[0xFF, 0x75]);
+
+    for (platform, count) in [(Platform::chip8(), 8), (Platform::xo_chip(), 16)] {
+        let mut vm = VM::new(platform);
+        vm.load_rom(&program).unwrap();
+        for _ in 0..17 {
+            vm.tick().unwrap();
+        }
+        assert!(vm.rpl_flags_written);
+        assert_eq!(vm.rpl_flags.iter().filter(|flag| **flag != 0).count(), count);
+
+        vm.variable_registers = [0; 16];
+        vm.memory[vm.program_counter..vm.program_counter + 2].copy_from_slice(&[0xF3, 0x85]);
+        vm.tick().unwrap();
+        assert_eq!(vm.variable_registers[..5], [1, 2, 3, 4, 0]);
+    }
+
+    assert!(Platform { rpl_flags: 17, ..Platform::chip8() }.validate().is_err());
+}
+
+#[test]
+fn vip_2k_keeps_the_top_of_memory() {
+    let platform = Platform::vip_2k();
+    assert_eq!(platform.program_space(), 0x4A0);
+    assert!(platform.is_reserved(0x6A0) && !platform.is_reserved(0x69F));
+
+    let mut vm = VM::new(platform);
+    let err = vm.load_rom(&[0; 0x4A1]).unwrap_err();
+    assert!(matches!(err, LoadError::OverlapsReserved { end: 0x6A1, .. }));
+    assert_eq!(err.to_string(), "the ROM would occupy 0x200..0x6A1, which overlaps the reserved region 0x6A0..0x800");
+    assert!(vm.load_rom(&[0; 0x4A0]).is_ok());
+
+    // A bigger VIP keeps the same amount at its own top
+    assert_eq!(Platform { memory_size: 0x1000, ..Platform::vip_2k() }.program_end(), 0xEA0);
+    assert!(Platform { load_address: 0x6A0, ..Platform::vip_2k() }.validate().is_err());
+
+    // I = 6A0, LD [I], V0
+    let mut vm = VM { strict: true, ..VM::new(Platform::vip_2k()) };
+    vm.load_rom(&[0xA6, 0xA0, 0xF0, 0x55]).unwrap();
+    vm.tick().unwrap();
+    assert!(matches!(vm.tick(), Err(VMError::MemoryFault { kind: FaultKind::ReservedWrite, addr: 0x6A0, pc: 0x202 })));
+}
@@ -0,0 +1,43 @@
+// Profiling a main loop that calls one subroutine.
+use std::sync::{Arc, Mutex};
+
+use chip8_rs::{chip8::VM, profile::Profiler, runner::Runner};
+
+#[test]
+fn attributes_routines_and_loops() {
+    let mut vm = VM::default();
+    vm.load_program(&[
+        0x22, 0x06, // CALL 206
+        0x12, 0x00, // JP 200
+        0x00, 0x00,
+        0x70, 0x01, // ADD V0, 01
+        0x00, 0xEE  // RET
+    ], 0x200);
+    vm.program_counter = 0x200;
+
+    let mut runner = Runner::new(Arc::new(Mutex::new(vm)));
+    runner.profiler = Some(Profiler::new(0x200));
+    runner.set_instructions_per_frame(16);
+    runner.run_frame().unwrap();
+
+    let vm = runner.vm.lock().unwrap();
+    let report = runner.profiler.as_ref().unwrap().report(&vm.memory, 10);
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "16 instructions over 1 frames");
+
+    // The subroutine's instructions count towards it, not towards main
+    let hotspots = lines.iter().position(|line| *line == "Hotspots by subroutine").unwrap();
+    assert_eq!(&lines[hotspots + 1..hotspots + 7], &[
+        "main (200)              8  50.0%",
+        "  200           4  25.0%  CALL 206",
+        "  202           4  25.0%  JP 200",
+        "sub 206                 8  50.0%",
+        "  206           4  25.0%  ADD V0, 01",
+        "  208           4  25.0%  RET"
+    ]);
+
+    // RET goes back up but only the jump makes a loop
+    let loops = lines.iter().position(|line| *line == "Hot loops").unwrap();
+    assert_eq!(lines[loops + 1], "  200-202  taken        4   50.0% of instructions");
+    assert_eq!(lines[loops + 2], "");
+}
@@ -0,0 +1,94 @@
+// Inputs that used to panic the VM, found by the fuzz targets in fuzz/.
+use chip8_rs::chip8::{self, VM, VMError};
+
+fn vm_with_program(program: &[u8]) -> VM {
+    let mut vm = VM::default();
+    vm.mem_copy(&chip8::FONT_DATA, 0);
+    vm.load_program(program, 0x200);
+    vm.program_counter = 0x200;
+    vm
+}
+
+fn run(vm: &mut VM, steps: usize) -> Result<(), VMError> {
+    for _ in 0..steps {
+        vm.tick()?;
+    }
+    Ok(())
+}
+
+#[test]
+fn decode_never_panics() {
+    for raw_inst in 0..=u16::MAX {
+        let _ = chip8::decode(raw_inst);
+    }
+}
+
+#[test]
+fn sub_inverse_with_borrow_wraps() {
+    // V0 = 5, V1 = 3, SUBN V0, V1
+    let mut vm = vm_with_program(&[0x60, 0x05, 0x61, 0x03, 0x80, 0x17]);
+    run(&mut vm, 3).unwrap();
+    assert_eq!(vm.variable_registers[0], 0xFE);
+    assert_eq!(vm.variable_registers[0xF], 0);
+}
+
+#[test]
+fn load_past_end_of_memory() {
+    // I = FFF, LD V1, [I]
+    let mut vm = vm_with_program(&[0xAF, 0xFF, 0xF1, 0x65]);
+    assert!(matches!(run(&mut vm, 2), Err(VMError::MemoryOutOfBounds { address: 0x1000 })));
+}
+
+#[test]
+fn save_past_end_of_memory() {
+    // I = FFE, LD [I], V2
+    let mut vm = vm_with_program(&[0xAF, 0xFE, 0xF2, 0x55]);
+    assert!(matches!(run(&mut vm, 2), Err(VMError::MemoryOutOfBounds { address: 0x1000 })));
+}
+
+#[test]
+fn bcd_past_end_of_memory() {
+    // I = FFF, LD B, V0
+    let mut vm = vm_with_program(&[0xAF, 0xFF, 0xF0, 0x33]);
+    assert!(matches!(run(&mut vm, 2), Err(VMError::MemoryOutOfBounds { address: 0x1000 })));
+}
+
+#[test]
+fn draw_past_end_of_memory() {
+    // I = FFF, DRW V0, V0, F
+    let mut vm = vm_with_program(&[0xAF, 0xFF, 0xD0, 0x0F]);
+    assert!(matches!(run(&mut vm, 2), Err(VMError::MemoryOutOfBounds { address: 0x1000 })));
+}
+
+#[test]
+fn fetch_at_end_of_memory() {
+    let mut vm = vm_with_program(&[]);
+    vm.program_counter = 0xFFF;
+    assert!(matches!(vm.tick(), Err(VMError::MemoryOutOfBounds { address: 0x1000 })));
+}
+
+#[test]
+fn jump_offset_past_end_of_memory() {
+    // V0 = FF, JP V0, FFF
+    let mut vm = vm_with_program(&[0x60, 0xFF, 0xBF, 0xFF]);
+    vm.chip48_mode = false;
+    run(&mut vm, 2).unwrap();
+    assert_eq!(vm.program_counter, 0x10FE);
+    assert!(matches!(vm.tick(), Err(VMError::MemoryOutOfBounds { .. })));
+}
+
+#[test]
+fn key_above_f() {
+    // V0 = 10, SKP V0, SKNP V0
+    let mut vm = vm_with_program(&[0x60, 0x10, 0xE0, 0x9E, 0xE0, 0xA1]);
+    run(&mut vm, 3).unwrap();
+}
+
+#[test]
+fn index_register_overflow() {
+    // V0 = FF, ADD I, V0
+    let mut vm = vm_with_program(&[0x60, 0xFF, 0xF0, 0x1E]);
+    vm.index_register = 0xFFFF;
+    run(&mut vm, 2).unwrap();
+    assert_eq!(vm.index_register, 0x00FE);
+}
@@ -0,0 +1,91 @@
+// The debug prompt driven by a command script, the way `chip8 debug rom < commands.txt` runs it.
+use std::sync::{Arc, Mutex};
+
+use chip8_rs::{chip8::{Snapshot, VM}, repl::Repl, runner::Runner};
+
+// V0 += 1, I = 300, LD [I], V0, DRW V0, V0, 1, loop
+const PROGRAM: &[u8] = &[0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0xD0, 0x01, 0x12, 0x00];
+
+fn runner() -> Runner {
+    let mut vm = VM::default();
+    vm.load_rom(PROGRAM).unwrap();
+    Runner::new(Arc::new(Mutex::new(vm)))
+}
+
+fn transcript(runner: &mut Runner, commands: &str) -> String {
+    let mut output = Vec::new();
+    Repl::new(runner).run(commands.as_bytes(), &mut output, true).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn step_registers_and_memory() {
+    let mut runner = runner();
+    let output = transcript(&mut runner, "step 3\nregs\nmem 0x300 2\ndisasm 0x200 2\n");
+
+    assert!(output.contains("(chip8) step 3\n206: D001  DRW V0, V0, 1\n"));
+    assert!(output.contains("V0=01 V1=00"));
+    assert!(output.contains("I=300 PC=206"));
+    assert!(output.contains("300: 01 00\n"));
+    assert!(output.contains("200: 7001  ADD V0, 01\n202: A300  LD I, 300\n"));
+    assert!(runner.vm.lock().unwrap().paused);
+}
+
+#[test]
+fn breakpoints_stop_continue() {
+    let mut runner = runner();
+    let output = transcript(&mut runner, "break 204 if v0 == 3\ncontinue\nregs\nbreak\ndelete 0\ncontinue\nbreak\n");
+
+    assert!(output.contains("Breakpoint 0: pc 204 if v0 == 3\n"));
+    assert!(output.contains("Breakpoint 0 (pc 204 if v0 == 3), hit 1 times\n204: F055  LD [I], V0\n"));
+    assert!(output.contains("V0=03"));
+    assert!(output.contains("0: pc 204 if v0 == 3, 1 hits\n"));
+    assert!(output.contains("Deleted pc 204 if v0 == 3\n"));
+    assert!(output.contains("No breakpoint hit after 3600 frames\n"));
+    assert!(output.contains("No breakpoints\n"));
+}
+
+#[test]
+fn set_screen_keys_and_state() {
+    let mut runner = runner();
+    let output = transcript(&mut runner, "save\nset v0 0x3F\nset [0x300] v0 + 1\nkey a down\nstep 4\nscreen\nload\nkey b down\nbogus\n");
+
+    {
+        let vm = runner.vm.lock().unwrap();
+        // Keys are part of the snapshot too.
+        assert!(!vm.keyboard.keys[0xA]);
+        assert!(vm.keyboard.keys[0xB]);
+        assert_eq!(vm.variable_registers[0], 0);
+        assert_eq!(vm.memory[0x300], 0);
+        assert_eq!(vm.program_counter, 0x200);
+    }
+
+    // V0 = 40 draws the top bit of 0x40 at (0, 0) after wrapping.
+    let screen: Vec<&str> = output.lines().filter(|line| line.len() == 64).collect();
+    assert_eq!(screen.len(), 32);
+    assert!(screen[0].starts_with(".#......"));
+    assert!(output.contains("Error: unknown command bogus, try help\n"));
+}
+
+#[test]
+fn logpoints_and_hits_reach_the_transcript() {
+    let mut runner = runner();
+    let output = transcript(&mut runner, "break op Draw log v0={v0}\nstep 4\nbreak 200\ncontinue\n");
+
+    assert!(output.contains("(chip8) step 4\nDebugger: PC 206: v0=1\n"));
+    assert!(output.contains("(chip8) continue\nDebugger: PC 200: breakpoint 1 \"pc 200\" hit 1 times\n"));
+}
+
+#[test]
+fn snapshots_keep_rpl_flags_and_quirks() {
+    let mut vm = VM::default();
+    vm.rpl_flags[0] = 7;
+    vm.vf_reset = true;
+    let snapshot = Snapshot::from_bytes(&vm.snapshot().to_bytes()).unwrap();
+
+    vm.rpl_flags[0] = 0;
+    vm.vf_reset = false;
+    vm.restore(&snapshot).unwrap();
+    assert_eq!(vm.rpl_flags[0], 7);
+    assert!(vm.vf_reset);
+}
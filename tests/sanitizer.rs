@@ -0,0 +1,57 @@
+// Each sanitizer warning triggered by the smallest program that shows it.
+use chip8_rs::chip8::{VM, WarningKind};
+
+fn warnings(program: &[u8], steps: usize) -> Vec<(usize, WarningKind)> {
+    let mut vm = VM::default();
+    vm.load_rom(program).unwrap();
+    vm.enable_sanitizer();
+
+    for _ in 0..steps {
+        if vm.tick().is_err() {
+            break;
+        }
+    }
+
+    vm.sanitizer.as_mut().unwrap().take_warnings().iter().map(|warning| (warning.pc, warning.kind)).collect()
+}
+
+#[test]
+fn uninitialised_register() {
+    // V0 = 1, ADD V0, V1
+    assert_eq!(warnings(&[0x60, 0x01, 0x80, 0x14], 2), vec![(0x202, WarningKind::UninitialisedRegister)]);
+}
+
+#[test]
+fn uninitialised_memory() {
+    // I = 800, LD V0, [I]
+    assert_eq!(warnings(&[0xA8, 0x00, 0xF0, 0x65], 2), vec![(0x202, WarningKind::UninitialisedMemory)]);
+}
+
+#[test]
+fn return_with_empty_stack() {
+    assert_eq!(warnings(&[0x00, 0xEE], 1), vec![(0x200, WarningKind::ReturnWithEmptyStack)]);
+}
+
+#[test]
+fn skip_into_long_opcode() {
+    // V0 = 0, SE V0, 0, F000 NNNN
+    assert_eq!(warnings(&[0x60, 0x00, 0x30, 0x00, 0xF0, 0x00, 0x12, 0x34], 2), vec![(0x202, WarningKind::SkipIntoOpcode)]);
+}
+
+#[test]
+fn flag_register_as_operand() {
+    // VF = 1, V0 = 1, ADD V0, VF
+    assert_eq!(warnings(&[0x6F, 0x01, 0x60, 0x01, 0x80, 0xF4], 3), vec![(0x204, WarningKind::FlagOperand)]);
+}
+
+#[test]
+fn self_modifying_code() {
+    // V0 = 0, I = 200, LD [I], V0
+    assert_eq!(warnings(&[0x60, 0x00, 0xA2, 0x00, 0xF0, 0x55], 3), vec![(0x204, WarningKind::SelfModifyingCode)]);
+}
+
+#[test]
+fn clean_program_has_no_warnings() {
+    // V0 = 5, V1 = 6, ADD V0, V1, I = 0, DRW V0, V1, 5, loop
+    assert!(warnings(&[0x60, 0x05, 0x61, 0x06, 0x80, 0x14, 0xA0, 0x00, 0xD0, 0x15, 0x12, 0x0A], 20).is_empty());
+}
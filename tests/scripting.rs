@@ -0,0 +1,43 @@
+// Script hooks driven through the shared run loop.
+use std::sync::{Arc, Mutex};
+
+use chip8_rs::{
+    chip8::{Breakpoint, VM},
+    runner::Runner,
+    scripting::ScriptHost
+};
+
+#[test]
+fn script_writes_go_through_the_vm() {
+    let path = std::env::temp_dir().join(format!("chip8-rs-{}-set-mem.rhai", std::process::id()));
+    std::fs::write(&path, "
+        fn on_instruction(pc) {
+            if pc == 0x200 { set_mem(0x300, 7); }
+        }
+        fn on_memory_write(address) {
+            if address == 0x300 { set_reg(5, mem(0x300)); }
+        }
+    ").unwrap();
+
+    // JP 200
+    let mut vm = VM::default();
+    vm.load_program(&[0x12, 0x00], 0x200);
+    vm.program_counter = 0x200;
+    vm.enable_coverage();
+    vm.debugger.add(Breakpoint::parse("write 300").unwrap());
+
+    let vm = Arc::new(Mutex::new(vm));
+    let mut runner = Runner::new(vm.clone());
+    runner.scripts = Some(ScriptHost::load(&path, vm.clone()).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    runner.step().unwrap();
+
+    let vm = vm.lock().unwrap();
+    assert_eq!(vm.memory[0x300], 7);
+    assert_eq!(vm.variable_registers[5], 7);
+    // Only written, never read or executed
+    assert_ne!(vm.coverage.as_ref().unwrap().flags[0x300], 0);
+    assert!(vm.paused);
+    assert_eq!(vm.debugger.stopped, Some(0));
+}
@@ -0,0 +1,76 @@
+// Per-ROM settings files and Octo-style options manifests.
+use chip8_rs::{chip8::{FontSet, Quirks}, capture::Palette, settings::{Keymap, Manifest, RomSettings}};
+
+#[test]
+fn settings_round_trip() {
+    let text = "quirks schip+vf_reset\nipf 30\npalette 000000,FFCC00\nkeymap 0123456789ABCDEF\nfont vip\n";
+    let settings = RomSettings::parse(text).unwrap();
+    assert_eq!(settings.quirks, Some(Quirks { vf_reset: true, ..Quirks::schip() }));
+    assert_eq!(settings.instructions_per_frame, Some(30));
+    assert_eq!(settings.palette, Palette::parse("000000,FFCC00"));
+    assert_eq!(settings.font, Some(FontSet::Vip));
+    assert_eq!(RomSettings::parse(&settings.to_text()).unwrap(), settings);
+
+    assert_eq!(RomSettings::parse("# nothing yet\n").unwrap(), RomSettings::default());
+    assert!(RomSettings::parse("ipf fast").is_err());
+    assert!(RomSettings::parse("speed 10").is_err());
+}
+
+#[test]
+fn settings_fill_gaps() {
+    let stored = RomSettings { instructions_per_frame: Some(15), ..RomSettings::default() };
+    let manifest = RomSettings { instructions_per_frame: Some(500), font: Some(FontSet::Eti660), ..RomSettings::default() };
+    let merged = stored.or(manifest);
+    assert_eq!(merged.instructions_per_frame, Some(15));
+    assert_eq!(merged.font, Some(FontSet::Eti660));
+    assert_eq!(merged.quirks, None);
+}
+
+#[test]
+fn keymaps_and_quirk_specs() {
+    assert_eq!(Keymap::default().to_string(), "X123QWEASDZC4RFV");
+    assert_eq!(Keymap::parse("x123qweasdzc4rfv"), Some(Keymap::default()));
+    assert_eq!(Keymap::parse("X123QWEASDZC4RF"), None);
+    assert_eq!(Keymap::parse("X123QWEASDZC4RFX"), None);
+
+    assert_eq!(Quirks::vip().spec(), "vip");
+    let custom = Quirks { clip_sprites: true, ..Quirks::default() };
+    assert_eq!(custom.spec(), "default+clip_sprites");
+    assert_eq!(Quirks::parse(&custom.spec()).unwrap(), custom);
+}
+
+#[test]
+fn octo_options() {
+    let json = r##"{"tickrate":20,"fillColor":"#FFCC00","backgroundColor":"#996600",
+        "shiftQuirks":true,"loadStoreQuirks":true,"jumpQuirks":false,"vBlankQuirks":false,"fontStyle":"schip"}"##;
+    let manifest = Manifest::parse(json).unwrap();
+    assert_eq!(manifest.title, None);
+    let quirks = manifest.settings.quirks.unwrap();
+    assert!(quirks.shift_legacy && !quirks.memory_increment && !quirks.chip48_mode && !quirks.vf_reset);
+    assert_eq!(manifest.settings.instructions_per_frame, Some(20));
+    assert_eq!(manifest.settings.palette, Palette::parse("996600,FFCC00"));
+    assert_eq!(manifest.settings.font, Some(FontSet::Chip48));
+
+    let json = r#"{"title":"Outlaw","authors":["John Earnest"],"desc":"A western shootout.","options":{"tickrate":1000}}"#;
+    let manifest = Manifest::parse(json).unwrap();
+    assert_eq!(manifest.title.as_deref(), Some("Outlaw"));
+    assert_eq!(manifest.author.as_deref(), Some("John Earnest"));
+    assert_eq!(manifest.description.as_deref(), Some("A western shootout."));
+    assert_eq!(manifest.settings.instructions_per_frame, Some(1000));
+    assert_eq!(manifest.settings.quirks, None);
+
+    assert!(Manifest::parse("not json").is_err());
+}
+
+#[test]
+fn manifests_write_octo_options_only() {
+    let manifest = Manifest {
+        title: None,
+        author: None,
+        description: None,
+        settings: RomSettings { font: Some(FontSet::FishNChips), keymap: Keymap::parse("X123QWEASDZC4RFV"), ..RomSettings::default() }
+    };
+    let json = rhai::format_map_as_json(&manifest.to_map());
+    assert_eq!(json, r#"{"options":{"fontStyle":"fish"}}"#);
+    assert_eq!(Manifest::parse(&json).unwrap().settings.font, Some(FontSet::FishNChips));
+}
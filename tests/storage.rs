@@ -0,0 +1,25 @@
+// The RPL flags files kept per ROM in the data directory.
+use chip8_rs::{chip8::RPL_FLAG_COUNT, storage};
+
+#[test]
+fn rpl_flags_files() {
+    let dir = std::env::temp_dir().join(format!("chip8-storage-{}", std::process::id()));
+    let path = dir.join("flags").join("rom.bin");
+
+    storage::save_rpl_flags(&path, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+    let flags = storage::load_rpl_flags(&path, 8).unwrap();
+    assert_eq!(flags[..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(flags[8..].iter().all(|flag| *flag == 0));
+
+    // Short files fill what they have
+    std::fs::write(&path, [9, 9]).unwrap();
+    assert_eq!(storage::load_rpl_flags(&path, 8).unwrap()[..3], [9, 9, 0]);
+
+    // Long files give no more than the platform has, and never more than there are
+    std::fs::write(&path, [7; 40]).unwrap();
+    assert_eq!(storage::load_rpl_flags(&path, 8).unwrap()[7..9], [7, 0]);
+    assert_eq!(storage::load_rpl_flags(&path, RPL_FLAG_COUNT).unwrap(), [7; RPL_FLAG_COUNT]);
+
+    assert!(storage::load_rpl_flags(&dir.join("missing.bin"), 8).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,84 @@
+// Trace lines and finding where two traces part ways.
+use std::{path::PathBuf, sync::{Arc, Mutex}};
+
+use chip8_rs::{chip8::{Breakpoint, VM}, runner::Runner, trace::{self, Tracer}};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chip8-rs-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn line_shows_the_state_before_the_instruction() {
+    // LD V3, 2A
+    let mut vm = VM::default();
+    vm.load_program(&[0x63, 0x2A], 0x200);
+    vm.program_counter = 0x200;
+    vm.variable_registers[0xF] = 1;
+    vm.index_register = 0x123;
+    vm.delay_timer = 0x3C;
+
+    assert_eq!(
+        trace::format_line(&vm),
+        "PC:0200 OP:632A V0:00 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:01 \
+         I:0123 SP:00 DT:3C ST:00 ; LD V3, 2A"
+    );
+}
+
+#[test]
+fn instructions_stopped_by_a_breakpoint_are_traced_once() {
+    // V0 += 1, loop
+    let mut vm = VM::default();
+    vm.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+    vm.debugger.add(Breakpoint::at(0x202));
+
+    let path = temp_path("breakpoint.log");
+    let mut runner = Runner { tracer: Some(Tracer::create(&path).unwrap()), ..Runner::new(Arc::new(Mutex::new(vm))) };
+    runner.step().unwrap();
+    runner.step().unwrap();
+    assert!(runner.vm.lock().unwrap().paused);
+
+    runner.vm.lock().unwrap().paused = false;
+    runner.step().unwrap();
+    drop(runner);
+
+    let pcs: Vec<String> = std::fs::read_to_string(&path).unwrap().lines().map(|line| line[..7].to_string()).collect();
+    assert_eq!(pcs, ["PC:0200", "PC:0202"]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn jump_offset_follows_the_quirk() {
+    // JP V0, 345 or JP V3, 345
+    let mut vm = VM::default();
+    vm.load_program(&[0xB3, 0x45], 0x200);
+    vm.program_counter = 0x200;
+
+    vm.chip48_mode = false;
+    assert!(trace::format_line(&vm).ends_with("; JP V0, 345"));
+    vm.chip48_mode = true;
+    assert!(trace::format_line(&vm).ends_with("; JP V3, 345"));
+}
+
+#[test]
+fn diff_reports_the_first_divergence() {
+    let left = temp_path("left.log");
+    let right = temp_path("right.log");
+    std::fs::write(&left, "PC:0200 OP:6001 V0:00 ; LD V0, 01\nPC:0202 OP:7001 V0:01 I:0000 ; ADD V0, 01\nPC:0204 OP:1204 V0:02\n").unwrap();
+    // The same machine state with another disassembly, then V0 and I part ways
+    std::fs::write(&right, "PC:0200 OP:6001 V0:00 ; ld v0, 0x01\nPC:0202 OP:7001 V0:02 I:0010 ; ADD V0, 01\nPC:0204 OP:1204 V0:03\n").unwrap();
+
+    let divergence = trace::diff(&left, &right).unwrap().unwrap();
+    assert_eq!(divergence.line, 2);
+    assert_eq!(divergence.fields, vec!["V0", "I"]);
+    assert!(divergence.left.unwrap().starts_with("PC:0202 OP:7001 V0:01"));
+
+    // A trace that stops early diverges where it ends
+    std::fs::write(&right, "PC:0200 OP:6001 V0:00\n").unwrap();
+    let divergence = trace::diff(&left, &right).unwrap().unwrap();
+    assert_eq!(divergence.line, 2);
+    assert_eq!(divergence.right, None);
+
+    assert!(trace::diff(&left, &left).unwrap().is_none());
+    std::fs::remove_file(left).unwrap();
+    std::fs::remove_file(right).unwrap();
+}